@@ -1,11 +1,93 @@
 use crate::packet_engine::PacketStats;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::collections::HashMap;
-use tokio::sync::RwLock;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::sync::{broadcast, RwLock};
 use tokio::time::{Duration, Instant};
 use serde::{Serialize, Deserialize};
 
+/// Broadcast capacity for [`AnalyticsEngine::subscribe_blocklist`]: how many
+/// newly-flagged sources a slow subscriber can fall behind by before it
+/// starts missing them.
+const BLOCKLIST_BROADCAST_CAPACITY: usize = 256;
+
+/// Broadcast capacity for [`AnalyticsEngine::subscribe_expired_flows`]: how
+/// many just-expired flows a slow subscriber can fall behind by before it
+/// starts missing them.
+const EXPIRED_FLOW_BROADCAST_CAPACITY: usize = 1024;
+
+/// A flow is treated as part of a port scan's footprint (rather than
+/// ordinary short-lived traffic) when it carries at most this many packets.
+const PORT_SCAN_TINY_FLOW_PACKETS: u64 = 3;
+
+/// Counters kept per [`SpaceSaving`] estimator. More than the top-10 actually
+/// reported, so the reported counts stay close to exact even though the
+/// structure's memory is bounded regardless of how many distinct keys stream
+/// through `process_packet`.
+const SPACE_SAVING_CAPACITY: usize = 50;
+
+/// Milliseconds since the Unix epoch. The wall-clock companion to a
+/// monotonic `Instant` timestamp: `Instant` has no meaning across a process
+/// restart, so it can't round-trip through `export_snapshot`/`import_snapshot`
+/// even though the structs derive `Serialize`/`Deserialize`.
+fn unix_millis_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
+
+/// Streaming approximate top-K counter (Space-Saving / Misra-Gries style).
+/// Keeps at most `capacity` `(key -> (count, error))` entries: a known key's
+/// count is incremented exactly; a new key either takes a free slot with
+/// count 1, or, once full, evicts the minimum-count entry and takes its slot
+/// with `count = min_count + 1` and `error = min_count`. `error` is the
+/// estimate's overestimate bound -- the true count is in `[count - error, count]`.
+struct SpaceSaving<K: Eq + std::hash::Hash + Clone> {
+    capacity: usize,
+    counters: HashMap<K, (u64, u64)>,
+}
+
+impl<K: Eq + std::hash::Hash + Clone> SpaceSaving<K> {
+    fn new(capacity: usize) -> Self {
+        Self { capacity: capacity.max(1), counters: HashMap::new() }
+    }
+
+    fn record(&mut self, key: K) {
+        if let Some((count, _)) = self.counters.get_mut(&key) {
+            *count += 1;
+            return;
+        }
+
+        if self.counters.len() < self.capacity {
+            self.counters.insert(key, (1, 0));
+            return;
+        }
+
+        let Some((min_key, min_count)) = self
+            .counters
+            .iter()
+            .min_by_key(|(_, &(count, _))| count)
+            .map(|(k, &(count, _))| (k.clone(), count))
+        else {
+            return;
+        };
+        self.counters.remove(&min_key);
+        self.counters.insert(key, (min_count + 1, min_count));
+    }
+
+    /// Top `n` keys by estimated count, descending, each with its error bound.
+    /// Equal counts are ordered by smaller error bound first.
+    fn top(&self, n: usize) -> Vec<(K, u64, u64)> {
+        let mut entries: Vec<(K, u64, u64)> =
+            self.counters.iter().map(|(k, &(count, error))| (k.clone(), count, error)).collect();
+        entries.sort_by(|a, b| b.1.cmp(&a.1).then(a.2.cmp(&b.2)));
+        entries.truncate(n);
+        entries
+    }
+}
+
 /// Analytics engine for collecting and processing network metrics
 pub struct AnalyticsEngine {
     packet_stats: Arc<PacketStats>,
@@ -13,6 +95,19 @@ pub struct AnalyticsEngine {
     route_metrics: Arc<RwLock<Vec<RouteMetrics>>>,
     system_metrics: Arc<RwLock<SystemMetrics>>,
     start_time: Instant,
+    blocklist: Arc<RwLock<HashMap<String, BlockEntry>>>,
+    thresholds: DetectionThresholds,
+    offenders: broadcast::Sender<BlockEntry>,
+    /// Incremental top-K estimators updated per-packet in `process_packet`,
+    /// so `get_stats` doesn't need to rescan the whole flow table.
+    top_applications: Arc<RwLock<SpaceSaving<String>>>,
+    top_protocols: Arc<RwLock<SpaceSaving<u8>>>,
+    top_sources: Arc<RwLock<SpaceSaving<String>>>,
+    top_destinations: Arc<RwLock<SpaceSaving<String>>>,
+    /// Flows `cleanup_old_flows` just aged out, for [`FlowExporter`] (or any
+    /// other subscriber) to flush immediately instead of waiting for its
+    /// next timer tick.
+    expired_flows: broadcast::Sender<TrafficFlow>,
 }
 
 /// Traffic flow tracking
@@ -26,8 +121,15 @@ pub struct TrafficFlow {
     pub protocol: u8,
     pub packet_count: u64,
     pub byte_count: u64,
+    #[serde(skip, default = "Instant::now")]
     pub first_seen: Instant,
+    #[serde(skip, default = "Instant::now")]
     pub last_seen: Instant,
+    /// Wall-clock companions to `first_seen`/`last_seen` (Unix epoch
+    /// milliseconds) -- the only form of these timestamps that survives
+    /// `export_snapshot`/`import_snapshot`, since `Instant` doesn't.
+    pub first_seen_unix_ms: u64,
+    pub last_seen_unix_ms: u64,
     pub application: String,
     pub dscp: u8,
     pub is_encrypted: bool,
@@ -37,7 +139,11 @@ pub struct TrafficFlow {
 /// Route metrics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RouteMetrics {
+    #[serde(skip, default = "Instant::now")]
     pub timestamp: Instant,
+    /// Wall-clock companion to `timestamp` (Unix epoch milliseconds); see
+    /// `TrafficFlow::first_seen_unix_ms`.
+    pub timestamp_unix_ms: u64,
     pub network: String,
     pub prefix_length: u8,
     pub next_hop: String,
@@ -54,7 +160,11 @@ pub struct RouteMetrics {
 /// System metrics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SystemMetrics {
+    #[serde(skip, default = "Instant::now")]
     pub timestamp: Instant,
+    /// Wall-clock companion to `timestamp` (Unix epoch milliseconds); see
+    /// `TrafficFlow::first_seen_unix_ms`.
+    pub timestamp_unix_ms: u64,
     pub cpu_usage: f64,
     pub memory_usage: f64,
     pub disk_usage: f64,
@@ -70,6 +180,15 @@ pub struct SystemMetrics {
     pub packet_loss_rate: f64,
 }
 
+/// On-disk/wire shape produced by [`AnalyticsEngine::export_snapshot`] and
+/// consumed by [`AnalyticsEngine::import_snapshot`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AnalyticsSnapshot {
+    flows: Vec<TrafficFlow>,
+    route_metrics: Vec<RouteMetrics>,
+    system_metrics: SystemMetrics,
+}
+
 /// Analytics statistics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnalyticsStats {
@@ -81,21 +200,135 @@ pub struct AnalyticsStats {
     pub packets_per_second: f64,
     pub bytes_per_second: f64,
     pub average_flow_duration: f64,
-    pub top_applications: Vec<(String, u64)>,
-    pub top_protocols: Vec<(u8, u64)>,
-    pub top_sources: Vec<(String, u64)>,
-    pub top_destinations: Vec<(String, u64)>,
+    /// `(key, estimated packet count, error bound)`, from bounded-memory
+    /// streaming estimators rather than a full flow-table scan -- `error` is
+    /// how far above the true count the estimate could be.
+    pub top_applications: Vec<(String, u64, u64)>,
+    pub top_protocols: Vec<(u8, u64, u64)>,
+    pub top_sources: Vec<(String, u64, u64)>,
+    pub top_destinations: Vec<(String, u64, u64)>,
+}
+
+/// Why [`AnalyticsEngine::detect_attacks`] flagged a source IP.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BlockReason {
+    /// Touched `distinct_destinations` distinct destination IP/port pairs,
+    /// each with only a handful of packets, within the detection window.
+    PortScan { distinct_destinations: usize },
+    /// Opened new flows faster than the configured rate.
+    SynFlood { new_flows_per_second: f64 },
+    /// Sustained more bytes/sec than the configured volumetric threshold.
+    Volumetric { bytes_per_second: f64 },
+}
+
+/// A source IP flagged by [`AnalyticsEngine::detect_attacks`], for an
+/// nftables integration or similar active-defense consumer to act on.
+#[derive(Debug, Clone)]
+pub struct BlockEntry {
+    pub source_ip: String,
+    pub reason: BlockReason,
+    /// How far past its threshold the triggering signal was, in `[0, 1)`;
+    /// approaches 1 as the signal grows arbitrarily far over threshold.
+    pub confidence: f64,
+    pub first_seen: Instant,
+    pub last_seen: Instant,
+}
+
+/// Configurable thresholds for [`AnalyticsEngine::detect_attacks`].
+#[derive(Debug, Clone)]
+pub struct DetectionThresholds {
+    pub port_scan_min_destinations: usize,
+    pub syn_flood_min_new_flows_per_second: f64,
+    pub volumetric_min_bytes_per_second: f64,
+    /// How long a blocklist entry survives without being re-flagged before
+    /// [`AnalyticsEngine::cleanup_old_blocklist_entries`] ages it out, the
+    /// same way [`AnalyticsEngine::cleanup_old_flows`] ages out flows.
+    pub entry_expiry: Duration,
+}
+
+impl Default for DetectionThresholds {
+    fn default() -> Self {
+        Self {
+            port_scan_min_destinations: 20,
+            syn_flood_min_new_flows_per_second: 50.0,
+            volumetric_min_bytes_per_second: 10_000_000.0,
+            entry_expiry: Duration::from_secs(600),
+        }
+    }
+}
+
+/// A live subscription returned by [`AnalyticsEngine::subscribe_blocklist`].
+pub struct BlocklistSubscription {
+    receiver: broadcast::Receiver<BlockEntry>,
+}
+
+impl BlocklistSubscription {
+    /// Wait indefinitely for the next newly-flagged source IP.
+    pub async fn recv(&mut self) -> Option<BlockEntry> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(entry) => return Some(entry),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Wait up to `timeout` for the next newly-flagged source IP. Returns
+    /// `None` on timeout or once the publisher side has gone away.
+    pub async fn poll(&mut self, timeout: Duration) -> Option<BlockEntry> {
+        tokio::time::timeout(timeout, self.recv()).await.ok().flatten()
+    }
+}
+
+/// A live subscription returned by [`AnalyticsEngine::subscribe_expired_flows`].
+pub struct ExpiredFlowSubscription {
+    receiver: broadcast::Receiver<TrafficFlow>,
+}
+
+impl ExpiredFlowSubscription {
+    /// Wait indefinitely for the next flow `cleanup_old_flows` ages out.
+    pub async fn recv(&mut self) -> Option<TrafficFlow> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(flow) => return Some(flow),
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Wait up to `timeout` for the next expired flow. Returns `None` on
+    /// timeout or once the publisher side has gone away.
+    pub async fn poll(&mut self, timeout: Duration) -> Option<TrafficFlow> {
+        tokio::time::timeout(timeout, self.recv()).await.ok().flatten()
+    }
 }
 
 impl AnalyticsEngine {
-    /// Create a new analytics engine
+    /// Create a new analytics engine with the default detection thresholds.
     pub fn new(packet_stats: Arc<PacketStats>) -> Self {
+        Self::with_thresholds(packet_stats, DetectionThresholds::default())
+    }
+
+    /// Create a new analytics engine with custom attack-detection thresholds.
+    pub fn with_thresholds(packet_stats: Arc<PacketStats>, thresholds: DetectionThresholds) -> Self {
+        let (offenders, _) = broadcast::channel(BLOCKLIST_BROADCAST_CAPACITY);
+        let (expired_flows, _) = broadcast::channel(EXPIRED_FLOW_BROADCAST_CAPACITY);
         Self {
             packet_stats,
             flow_table: Arc::new(RwLock::new(HashMap::new())),
             route_metrics: Arc::new(RwLock::new(Vec::new())),
             system_metrics: Arc::new(RwLock::new(SystemMetrics::default())),
             start_time: Instant::now(),
+            blocklist: Arc::new(RwLock::new(HashMap::new())),
+            thresholds,
+            offenders,
+            top_applications: Arc::new(RwLock::new(SpaceSaving::new(SPACE_SAVING_CAPACITY))),
+            top_protocols: Arc::new(RwLock::new(SpaceSaving::new(SPACE_SAVING_CAPACITY))),
+            top_sources: Arc::new(RwLock::new(SpaceSaving::new(SPACE_SAVING_CAPACITY))),
+            top_destinations: Arc::new(RwLock::new(SpaceSaving::new(SPACE_SAVING_CAPACITY))),
+            expired_flows,
         }
     }
 
@@ -113,6 +346,8 @@ impl AnalyticsEngine {
     ) -> Result<(), Box<dyn std::error::Error>> {
         let flow_id = self.generate_flow_id(source_ip, destination_ip, source_port, destination_port, protocol);
         let now = Instant::now();
+        let wall_now = unix_millis_now();
+        let application = self.detect_application(protocol, destination_port);
 
         // Update flow table
         let mut flows = self.flow_table.write().await;
@@ -120,6 +355,7 @@ impl AnalyticsEngine {
             flow.packet_count += 1;
             flow.byte_count += packet_size as u64;
             flow.last_seen = now;
+            flow.last_seen_unix_ms = wall_now;
         } else {
             let flow = TrafficFlow {
                 flow_id: flow_id.clone(),
@@ -132,13 +368,23 @@ impl AnalyticsEngine {
                 byte_count: packet_size as u64,
                 first_seen: now,
                 last_seen: now,
-                application: self.detect_application(protocol, destination_port),
+                first_seen_unix_ms: wall_now,
+                last_seen_unix_ms: wall_now,
+                application: application.clone(),
                 dscp,
                 is_encrypted,
                 traffic_class: self.classify_traffic(dscp, protocol),
             };
             flows.insert(flow_id, flow);
         }
+        drop(flows);
+
+        // Feed the streaming top-K estimators so `get_stats` never has to
+        // rescan the flow table.
+        self.top_applications.write().await.record(application);
+        self.top_protocols.write().await.record(protocol);
+        self.top_sources.write().await.record(source_ip.to_string());
+        self.top_destinations.write().await.record(destination_ip.to_string());
 
         Ok(())
     }
@@ -188,41 +434,12 @@ impl AnalyticsEngine {
             0.0
         };
 
-        // Calculate top applications
-        let mut app_counts: HashMap<String, u64> = HashMap::new();
-        for flow in flows.values() {
-            *app_counts.entry(flow.application.clone()).or_insert(0) += flow.packet_count;
-        }
-        let mut top_applications: Vec<(String, u64)> = app_counts.into_iter().collect();
-        top_applications.sort_by(|a, b| b.1.cmp(&a.1));
-        top_applications.truncate(10);
-
-        // Calculate top protocols
-        let mut protocol_counts: HashMap<u8, u64> = HashMap::new();
-        for flow in flows.values() {
-            *protocol_counts.entry(flow.protocol).or_insert(0) += flow.packet_count;
-        }
-        let mut top_protocols: Vec<(u8, u64)> = protocol_counts.into_iter().collect();
-        top_protocols.sort_by(|a, b| b.1.cmp(&a.1));
-        top_protocols.truncate(10);
-
-        // Calculate top sources
-        let mut source_counts: HashMap<String, u64> = HashMap::new();
-        for flow in flows.values() {
-            *source_counts.entry(flow.source_ip.clone()).or_insert(0) += flow.packet_count;
-        }
-        let mut top_sources: Vec<(String, u64)> = source_counts.into_iter().collect();
-        top_sources.sort_by(|a, b| b.1.cmp(&a.1));
-        top_sources.truncate(10);
-
-        // Calculate top destinations
-        let mut dest_counts: HashMap<String, u64> = HashMap::new();
-        for flow in flows.values() {
-            *dest_counts.entry(flow.destination_ip.clone()).or_insert(0) += flow.packet_count;
-        }
-        let mut top_destinations: Vec<(String, u64)> = dest_counts.into_iter().collect();
-        top_destinations.sort_by(|a, b| b.1.cmp(&a.1));
-        top_destinations.truncate(10);
+        // Top-K dimensions come from the incremental Space-Saving estimators
+        // maintained per-packet in `process_packet`, not a flow-table scan.
+        let top_applications = self.top_applications.read().await.top(10);
+        let top_protocols = self.top_protocols.read().await.top(10);
+        let top_sources = self.top_sources.read().await.top(10);
+        let top_destinations = self.top_destinations.read().await.top(10);
 
         AnalyticsStats {
             total_flows,
@@ -262,7 +479,176 @@ impl AnalyticsEngine {
     pub async fn cleanup_old_flows(&self, max_age: Duration) {
         let mut flows = self.flow_table.write().await;
         let now = Instant::now();
-        flows.retain(|_, flow| now.duration_since(flow.last_seen) < max_age);
+        let expired_ids: Vec<String> = flows
+            .iter()
+            .filter(|(_, flow)| now.duration_since(flow.last_seen) >= max_age)
+            .map(|(flow_id, _)| flow_id.clone())
+            .collect();
+        for flow_id in expired_ids {
+            if let Some(flow) = flows.remove(&flow_id) {
+                let _ = self.expired_flows.send(flow);
+            }
+        }
+    }
+
+    /// Serialize the current flow table, recent route metrics, and system
+    /// metrics to a single JSON document, for an external log/analytics
+    /// pipeline or a restart-time checkpoint. Every timestamp in the output
+    /// is the wall-clock `*_unix_ms` field, since the monotonic `Instant`s
+    /// don't mean anything outside this process. See [`Self::import_snapshot`]
+    /// for the inverse.
+    pub async fn export_snapshot(&self) -> Result<String, String> {
+        let snapshot = AnalyticsSnapshot {
+            flows: self.flow_table.read().await.values().cloned().collect(),
+            route_metrics: self.route_metrics.read().await.clone(),
+            system_metrics: self.system_metrics.read().await.clone(),
+        };
+        serde_json::to_string_pretty(&snapshot).map_err(|e| e.to_string())
+    }
+
+    /// Replace the flow table, route metrics, and system metrics with the
+    /// contents of a JSON snapshot produced by [`Self::export_snapshot`].
+    /// The top-K estimators are reseeded with one observation per restored
+    /// flow (rather than replaying its full `packet_count`) so import stays
+    /// bounded regardless of how much traffic a flow had accumulated; their
+    /// counts sharpen again as new packets arrive.
+    pub async fn import_snapshot(&self, snapshot_json: &str) -> Result<(), String> {
+        let snapshot: AnalyticsSnapshot = serde_json::from_str(snapshot_json).map_err(|e| e.to_string())?;
+
+        let mut top_applications = SpaceSaving::new(SPACE_SAVING_CAPACITY);
+        let mut top_protocols = SpaceSaving::new(SPACE_SAVING_CAPACITY);
+        let mut top_sources = SpaceSaving::new(SPACE_SAVING_CAPACITY);
+        let mut top_destinations = SpaceSaving::new(SPACE_SAVING_CAPACITY);
+        let mut flows = HashMap::with_capacity(snapshot.flows.len());
+        for flow in snapshot.flows {
+            top_applications.record(flow.application.clone());
+            top_protocols.record(flow.protocol);
+            top_sources.record(flow.source_ip.clone());
+            top_destinations.record(flow.destination_ip.clone());
+            flows.insert(flow.flow_id.clone(), flow);
+        }
+
+        *self.flow_table.write().await = flows;
+        *self.route_metrics.write().await = snapshot.route_metrics;
+        *self.system_metrics.write().await = snapshot.system_metrics;
+        *self.top_applications.write().await = top_applications;
+        *self.top_protocols.write().await = top_protocols;
+        *self.top_sources.write().await = top_sources;
+        *self.top_destinations.write().await = top_destinations;
+
+        Ok(())
+    }
+
+    /// Scan the current flow table for abusive source IPs and update the
+    /// blocklist, broadcasting any newly-flagged source over
+    /// [`Self::subscribe_blocklist`]. Meant to be called periodically (e.g.
+    /// alongside `cleanup_old_flows`) rather than per-packet. Detects three
+    /// signals per source: a port scan (many distinct destinations, each
+    /// with only a handful of packets), a SYN/connection flood (a high new-flow
+    /// creation rate), and a volumetric source (sustained high bytes/sec).
+    pub async fn detect_attacks(&self) -> Vec<BlockEntry> {
+        struct SourceAggregate {
+            tiny_flow_destinations: HashSet<(String, u16)>,
+            new_flows: u64,
+            total_bytes: u64,
+            oldest_first_seen: Instant,
+        }
+
+        let now = Instant::now();
+        let mut by_source: HashMap<String, SourceAggregate> = HashMap::new();
+        {
+            let flows = self.flow_table.read().await;
+            for flow in flows.values() {
+                let agg = by_source.entry(flow.source_ip.clone()).or_insert_with(|| SourceAggregate {
+                    tiny_flow_destinations: HashSet::new(),
+                    new_flows: 0,
+                    total_bytes: 0,
+                    oldest_first_seen: flow.first_seen,
+                });
+                if flow.packet_count <= PORT_SCAN_TINY_FLOW_PACKETS {
+                    agg.tiny_flow_destinations.insert((flow.destination_ip.clone(), flow.destination_port));
+                }
+                agg.new_flows += 1;
+                agg.total_bytes += flow.byte_count;
+                agg.oldest_first_seen = agg.oldest_first_seen.min(flow.first_seen);
+            }
+        }
+
+        let mut flagged = Vec::new();
+        for (source_ip, agg) in by_source {
+            let window_secs = now.duration_since(agg.oldest_first_seen).as_secs_f64().max(1.0);
+            let new_flows_per_second = agg.new_flows as f64 / window_secs;
+            let bytes_per_second = agg.total_bytes as f64 / window_secs;
+
+            let reason = if agg.tiny_flow_destinations.len() >= self.thresholds.port_scan_min_destinations {
+                Some((
+                    BlockReason::PortScan { distinct_destinations: agg.tiny_flow_destinations.len() },
+                    agg.tiny_flow_destinations.len() as f64 / self.thresholds.port_scan_min_destinations as f64,
+                ))
+            } else if new_flows_per_second >= self.thresholds.syn_flood_min_new_flows_per_second {
+                Some((
+                    BlockReason::SynFlood { new_flows_per_second },
+                    new_flows_per_second / self.thresholds.syn_flood_min_new_flows_per_second,
+                ))
+            } else if bytes_per_second >= self.thresholds.volumetric_min_bytes_per_second {
+                Some((
+                    BlockReason::Volumetric { bytes_per_second },
+                    bytes_per_second / self.thresholds.volumetric_min_bytes_per_second,
+                ))
+            } else {
+                None
+            };
+
+            let Some((reason, threshold_ratio)) = reason else { continue };
+            let confidence = (1.0 - 1.0 / threshold_ratio).clamp(0.0, 1.0);
+
+            let mut blocklist = self.blocklist.write().await;
+            let is_new = !blocklist.contains_key(&source_ip);
+            let entry = blocklist.entry(source_ip.clone()).or_insert_with(|| BlockEntry {
+                source_ip: source_ip.clone(),
+                reason: reason.clone(),
+                confidence,
+                first_seen: now,
+                last_seen: now,
+            });
+            entry.reason = reason;
+            entry.confidence = confidence;
+            entry.last_seen = now;
+            let emitted = entry.clone();
+            drop(blocklist);
+
+            if is_new {
+                let _ = self.offenders.send(emitted.clone());
+            }
+            flagged.push(emitted);
+        }
+
+        flagged
+    }
+
+    /// Current blocklist, as flagged by the most recent `detect_attacks` run.
+    pub async fn get_blocklist(&self) -> Vec<BlockEntry> {
+        self.blocklist.read().await.values().cloned().collect()
+    }
+
+    /// Subscribe to newly-flagged sources as `detect_attacks` finds them.
+    pub fn subscribe_blocklist(&self) -> BlocklistSubscription {
+        BlocklistSubscription { receiver: self.offenders.subscribe() }
+    }
+
+    /// Subscribe to flows as `cleanup_old_flows` ages them out, so a
+    /// consumer like [`FlowExporter`] can flush them immediately instead of
+    /// waiting for its own timer.
+    pub fn subscribe_expired_flows(&self) -> ExpiredFlowSubscription {
+        ExpiredFlowSubscription { receiver: self.expired_flows.subscribe() }
+    }
+
+    /// Age out blocklist entries that haven't been re-flagged within
+    /// `self.thresholds.entry_expiry`, mirroring `cleanup_old_flows`.
+    pub async fn cleanup_old_blocklist_entries(&self) {
+        let mut blocklist = self.blocklist.write().await;
+        let now = Instant::now();
+        blocklist.retain(|_, entry| now.duration_since(entry.last_seen) < self.thresholds.entry_expiry);
     }
 
     /// Generate flow ID
@@ -345,10 +731,330 @@ impl AnalyticsEngine {
     }
 }
 
+/// Serves an [`AnalyticsEngine`]'s stats as Prometheus text exposition format
+/// over plain HTTP, so operators can wire this into an existing
+/// Prometheus/Grafana stack instead of polling `get_stats()` in-process.
+pub struct MetricsServer {
+    engine: Arc<AnalyticsEngine>,
+    addr: SocketAddr,
+    path: String,
+}
+
+impl MetricsServer {
+    pub fn new(engine: Arc<AnalyticsEngine>, addr: SocketAddr, path: impl Into<String>) -> Self {
+        Self { engine, addr, path: path.into() }
+    }
+
+    /// Bind `addr` and serve scrape requests on `path` in the background
+    /// until the returned task is dropped or aborted.
+    pub fn spawn(self) -> tokio::task::JoinHandle<std::io::Result<()>> {
+        tokio::spawn(async move { self.serve().await })
+    }
+
+    async fn serve(self) -> std::io::Result<()> {
+        let listener = TcpListener::bind(self.addr).await?;
+        let match_prefix = format!("GET {}", self.path);
+
+        loop {
+            let (mut socket, _) = listener.accept().await?;
+            let engine = self.engine.clone();
+            let match_prefix = match_prefix.clone();
+
+            tokio::spawn(async move {
+                let mut buf = [0u8; 1024];
+                // Only the request line is needed to decide path; headers/body are ignored.
+                if socket.read(&mut buf).await.is_err() {
+                    return;
+                }
+
+                let request_line = String::from_utf8_lossy(&buf);
+                let response = if request_line.starts_with(&match_prefix) {
+                    let stats = engine.get_stats().await;
+                    let system = engine.get_system_metrics().await;
+                    let body = render_prometheus(&stats, &system);
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                } else {
+                    "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_string()
+                };
+
+                let _ = socket.write_all(response.as_bytes()).await;
+            });
+        }
+    }
+}
+
+/// Render `stats`/`system` in Prometheus text exposition format (see
+/// https://prometheus.io/docs/instrumenting/exposition_formats/).
+fn render_prometheus(stats: &AnalyticsStats, system: &SystemMetrics) -> String {
+    let mut out = String::new();
+
+    out.push_str("# HELP router_packets_per_second Packets processed per second.\n");
+    out.push_str("# TYPE router_packets_per_second gauge\n");
+    out.push_str(&format!("router_packets_per_second {}\n", stats.packets_per_second));
+
+    out.push_str("# HELP router_bytes_per_second Bytes processed per second.\n");
+    out.push_str("# TYPE router_bytes_per_second gauge\n");
+    out.push_str(&format!("router_bytes_per_second {}\n", stats.bytes_per_second));
+
+    out.push_str("# HELP router_active_flows Flows seen within the active window.\n");
+    out.push_str("# TYPE router_active_flows gauge\n");
+    out.push_str(&format!("router_active_flows {}\n", stats.active_flows));
+
+    out.push_str("# HELP router_active_routes Routes currently marked active.\n");
+    out.push_str("# TYPE router_active_routes gauge\n");
+    out.push_str(&format!("router_active_routes {}\n", stats.active_routes));
+
+    out.push_str("# HELP router_packet_loss_rate Fraction of packets lost.\n");
+    out.push_str("# TYPE router_packet_loss_rate gauge\n");
+    out.push_str(&format!("router_packet_loss_rate {}\n", system.packet_loss_rate));
+
+    out.push_str("# HELP router_flow_packets Packets observed for the top applications by volume.\n");
+    out.push_str("# TYPE router_flow_packets counter\n");
+    for (application, count, _error) in &stats.top_applications {
+        out.push_str(&format!(
+            "router_flow_packets{{application=\"{}\"}} {}\n",
+            escape_label_value(application),
+            count
+        ));
+    }
+
+    out.push_str("# HELP router_protocol_packets Packets observed for the top IP protocols by volume.\n");
+    out.push_str("# TYPE router_protocol_packets counter\n");
+    for (protocol, count, _error) in &stats.top_protocols {
+        out.push_str(&format!("router_protocol_packets{{protocol=\"{}\"}} {}\n", protocol, count));
+    }
+
+    out
+}
+
+fn escape_label_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// NetFlow v9 template ID used for IPv4 flow records.
+const NETFLOW_V9_TEMPLATE_ID_V4: u16 = 256;
+/// NetFlow v9 template ID used for IPv6 flow records.
+const NETFLOW_V9_TEMPLATE_ID_V6: u16 = 257;
+
+/// `(information element type, field length in bytes)`, in the IANA
+/// IPFIX/NetFlow v9 numbering, for the IPv4 flow record template: source and
+/// destination address, ports, protocol, ToS (`dscp`), packet/byte counts,
+/// and the flow's first/last-seen timestamps.
+const NETFLOW_V9_FIELDS_V4: &[(u16, u16)] = &[
+    (8, 4),  // IPV4_SRC_ADDR
+    (12, 4), // IPV4_DST_ADDR
+    (7, 2),  // L4_SRC_PORT
+    (11, 2), // L4_DST_PORT
+    (4, 1),  // PROTOCOL
+    (5, 1),  // SRC_TOS
+    (2, 4),  // IN_PKTS
+    (1, 4),  // IN_BYTES
+    (22, 4), // FIRST_SWITCHED
+    (21, 4), // LAST_SWITCHED
+];
+
+/// Same fields as [`NETFLOW_V9_FIELDS_V4`], but with 16-byte IPv6 addresses.
+const NETFLOW_V9_FIELDS_V6: &[(u16, u16)] = &[
+    (27, 16), // IPV6_SRC_ADDR
+    (28, 16), // IPV6_DST_ADDR
+    (7, 2),
+    (11, 2),
+    (4, 1),
+    (5, 1),
+    (2, 4),
+    (1, 4),
+    (22, 4),
+    (21, 4),
+];
+
+/// Encode a NetFlow v9 Template FlowSet (FlowSet ID 0) describing `fields`
+/// under `template_id`.
+fn netflow_v9_template_flowset(template_id: u16, fields: &[(u16, u16)]) -> Vec<u8> {
+    let mut record = Vec::new();
+    record.extend_from_slice(&template_id.to_be_bytes());
+    record.extend_from_slice(&(fields.len() as u16).to_be_bytes());
+    for &(field_type, field_len) in fields {
+        record.extend_from_slice(&field_type.to_be_bytes());
+        record.extend_from_slice(&field_len.to_be_bytes());
+    }
+
+    let mut flowset = Vec::new();
+    flowset.extend_from_slice(&0u16.to_be_bytes()); // FlowSet ID 0 marks a template
+    flowset.extend_from_slice(&((4 + record.len()) as u16).to_be_bytes());
+    flowset.extend_from_slice(&record);
+    flowset
+}
+
+/// Encode `flow` as one NetFlow v9 data record matching [`NETFLOW_V9_FIELDS_V4`]
+/// or [`NETFLOW_V9_FIELDS_V6`] (selected by its own address family). Returns
+/// `None` if the flow's addresses don't parse, or source/destination are
+/// from different families (can't happen from `process_packet`, but a
+/// restored snapshot isn't guaranteed consistent).
+fn netflow_v9_data_record(flow: &TrafficFlow, engine_start: Instant) -> Option<Vec<u8>> {
+    let source: IpAddr = flow.source_ip.parse().ok()?;
+    let destination: IpAddr = flow.destination_ip.parse().ok()?;
+    let first_switched = flow.first_seen.saturating_duration_since(engine_start).as_millis() as u32;
+    let last_switched = flow.last_seen.saturating_duration_since(engine_start).as_millis() as u32;
+
+    let mut record = Vec::new();
+    match (source, destination) {
+        (IpAddr::V4(source), IpAddr::V4(destination)) => {
+            record.extend_from_slice(&source.octets());
+            record.extend_from_slice(&destination.octets());
+        }
+        (IpAddr::V6(source), IpAddr::V6(destination)) => {
+            record.extend_from_slice(&source.octets());
+            record.extend_from_slice(&destination.octets());
+        }
+        _ => return None,
+    }
+    record.extend_from_slice(&flow.source_port.to_be_bytes());
+    record.extend_from_slice(&flow.destination_port.to_be_bytes());
+    record.push(flow.protocol);
+    record.push(flow.dscp);
+    record.extend_from_slice(&(flow.packet_count as u32).to_be_bytes());
+    record.extend_from_slice(&(flow.byte_count as u32).to_be_bytes());
+    record.extend_from_slice(&first_switched.to_be_bytes());
+    record.extend_from_slice(&last_switched.to_be_bytes());
+    Some(record)
+}
+
+/// Encode a NetFlow v9 Data FlowSet under `template_id` holding `records`,
+/// padded with zero bytes so the FlowSet's total length is a multiple of 4
+/// as the spec requires. Returns `None` for an empty `records`, since an
+/// empty FlowSet isn't useful to emit.
+fn netflow_v9_data_flowset(template_id: u16, records: &[Vec<u8>]) -> Option<Vec<u8>> {
+    if records.is_empty() {
+        return None;
+    }
+    let mut body: Vec<u8> = records.concat();
+    let unpadded_len = 4 + body.len();
+    let padding = (4 - unpadded_len % 4) % 4;
+    body.resize(body.len() + padding, 0);
+
+    let mut flowset = Vec::new();
+    flowset.extend_from_slice(&template_id.to_be_bytes());
+    flowset.extend_from_slice(&((4 + body.len()) as u16).to_be_bytes());
+    flowset.extend_from_slice(&body);
+    Some(flowset)
+}
+
+/// Render one NetFlow v9 export packet for `flows`: the header, the cached
+/// IPv4/IPv6 template FlowSets (resent with every packet so a collector that
+/// missed an earlier export can still decode the data), and a data FlowSet
+/// per address family that has at least one flow.
+fn render_netflow_v9_packet(
+    flows: &[TrafficFlow],
+    engine_start: Instant,
+    sequence: u32,
+    source_id: u32,
+    template_v4: &[u8],
+    template_v6: &[u8],
+) -> Vec<u8> {
+    let sys_uptime_ms = Instant::now().saturating_duration_since(engine_start).as_millis() as u32;
+    let unix_secs = (unix_millis_now() / 1000) as u32;
+
+    let mut v4_records = Vec::new();
+    let mut v6_records = Vec::new();
+    for flow in flows {
+        let Ok(source) = flow.source_ip.parse::<IpAddr>() else { continue };
+        let Some(record) = netflow_v9_data_record(flow, engine_start) else { continue };
+        match source {
+            IpAddr::V4(_) => v4_records.push(record),
+            IpAddr::V6(_) => v6_records.push(record),
+        }
+    }
+
+    let v4_flowset = netflow_v9_data_flowset(NETFLOW_V9_TEMPLATE_ID_V4, &v4_records);
+    let v6_flowset = netflow_v9_data_flowset(NETFLOW_V9_TEMPLATE_ID_V6, &v6_records);
+    let record_count = 2 + v4_records.len() + v6_records.len(); // 2 template records + one per flow
+
+    let mut packet = Vec::new();
+    packet.extend_from_slice(&9u16.to_be_bytes()); // version
+    packet.extend_from_slice(&(record_count as u16).to_be_bytes());
+    packet.extend_from_slice(&sys_uptime_ms.to_be_bytes());
+    packet.extend_from_slice(&unix_secs.to_be_bytes());
+    packet.extend_from_slice(&sequence.to_be_bytes());
+    packet.extend_from_slice(&source_id.to_be_bytes());
+    packet.extend_from_slice(template_v4);
+    packet.extend_from_slice(template_v6);
+    if let Some(flowset) = v4_flowset {
+        packet.extend_from_slice(&flowset);
+    }
+    if let Some(flowset) = v6_flowset {
+        packet.extend_from_slice(&flowset);
+    }
+    packet
+}
+
+/// Periodically exports an [`AnalyticsEngine`]'s flow table as NetFlow v9
+/// records over UDP, so standard flow-collection tooling (nfcapd, an IPFIX
+/// collector, etc.) can ingest it instead of scraping `get_flows()`.
+/// Flushes every `template_refresh` with a full snapshot of current flows,
+/// and immediately for each flow [`AnalyticsEngine::cleanup_old_flows`] ages
+/// out, so a short-lived flow isn't stuck waiting for the next timer tick.
+pub struct FlowExporter {
+    engine: Arc<AnalyticsEngine>,
+    collector_addr: SocketAddr,
+    template_refresh: Duration,
+}
+
+impl FlowExporter {
+    pub fn new(engine: Arc<AnalyticsEngine>, collector_addr: SocketAddr, template_refresh: Duration) -> Self {
+        Self { engine, collector_addr, template_refresh }
+    }
+
+    /// Export in the background until the returned task is dropped or
+    /// aborted.
+    pub fn spawn(self) -> tokio::task::JoinHandle<std::io::Result<()>> {
+        tokio::spawn(async move { self.serve().await })
+    }
+
+    async fn serve(self) -> std::io::Result<()> {
+        let socket = UdpSocket::bind("0.0.0.0:0").await?;
+        socket.connect(self.collector_addr).await?;
+
+        // Built once and resent with every packet rather than re-derived
+        // from `TrafficFlow` on each export.
+        let template_v4 = netflow_v9_template_flowset(NETFLOW_V9_TEMPLATE_ID_V4, NETFLOW_V9_FIELDS_V4);
+        let template_v6 = netflow_v9_template_flowset(NETFLOW_V9_TEMPLATE_ID_V6, NETFLOW_V9_FIELDS_V6);
+        let source_id = std::process::id();
+        let mut sequence: u32 = 0;
+        let mut expired = self.engine.subscribe_expired_flows();
+        let mut ticker = tokio::time::interval(self.template_refresh);
+
+        loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    let flows = self.engine.get_flows().await;
+                    let packet = render_netflow_v9_packet(
+                        &flows, self.engine.start_time, sequence, source_id, &template_v4, &template_v6,
+                    );
+                    sequence = sequence.wrapping_add(1);
+                    socket.send(&packet).await?;
+                }
+                flow = expired.recv() => {
+                    let Some(flow) = flow else { return Ok(()) };
+                    let packet = render_netflow_v9_packet(
+                        std::slice::from_ref(&flow), self.engine.start_time, sequence, source_id, &template_v4, &template_v6,
+                    );
+                    sequence = sequence.wrapping_add(1);
+                    socket.send(&packet).await?;
+                }
+            }
+        }
+    }
+}
+
 impl Default for SystemMetrics {
     fn default() -> Self {
         Self {
             timestamp: Instant::now(),
+            timestamp_unix_ms: unix_millis_now(),
             cpu_usage: 0.0,
             memory_usage: 0.0,
             disk_usage: 0.0,
@@ -420,4 +1126,141 @@ mod tests {
         let flows = engine.get_flows().await;
         assert_eq!(flows[0].application, "HTTP");
     }
+
+    #[tokio::test]
+    async fn test_render_prometheus_includes_application_label() {
+        let packet_stats = Arc::new(PacketStats::new());
+        let engine = AnalyticsEngine::new(packet_stats);
+
+        engine.process_packet("192.168.1.1", "192.168.1.2", 8080, 80, 6, 1500, 0, false).await.unwrap();
+
+        let stats = engine.get_stats().await;
+        let system = engine.get_system_metrics().await;
+        let rendered = render_prometheus(&stats, &system);
+
+        assert!(rendered.contains("router_active_flows 1"));
+        assert!(rendered.contains("router_flow_packets{application=\"HTTP\"} 1"));
+    }
+
+    #[tokio::test]
+    async fn test_detect_attacks_flags_a_port_scan_source() {
+        let packet_stats = Arc::new(PacketStats::new());
+        let thresholds = DetectionThresholds { port_scan_min_destinations: 5, ..DetectionThresholds::default() };
+        let engine = AnalyticsEngine::with_thresholds(packet_stats, thresholds);
+
+        for port in 0..10u16 {
+            engine.process_packet("10.0.0.1", "192.168.1.1", 12345, 1000 + port, 6, 60, 0, false).await.unwrap();
+        }
+
+        let flagged = engine.detect_attacks().await;
+        assert_eq!(flagged.len(), 1);
+        assert_eq!(flagged[0].source_ip, "10.0.0.1");
+        assert!(matches!(flagged[0].reason, BlockReason::PortScan { distinct_destinations } if distinct_destinations >= 5));
+
+        let blocklist = engine.get_blocklist().await;
+        assert_eq!(blocklist.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_detect_attacks_broadcasts_only_new_offenders() {
+        let packet_stats = Arc::new(PacketStats::new());
+        let thresholds = DetectionThresholds { port_scan_min_destinations: 3, ..DetectionThresholds::default() };
+        let engine = AnalyticsEngine::with_thresholds(packet_stats, thresholds);
+        let mut subscription = engine.subscribe_blocklist();
+
+        for port in 0..5u16 {
+            engine.process_packet("10.0.0.2", "192.168.1.1", 12345, 2000 + port, 6, 60, 0, false).await.unwrap();
+        }
+        engine.detect_attacks().await;
+        let first = subscription.poll(Duration::from_millis(50)).await;
+        assert!(first.is_some());
+
+        // Detecting the same still-offending source again shouldn't re-broadcast it.
+        engine.detect_attacks().await;
+        let second = subscription.poll(Duration::from_millis(50)).await;
+        assert!(second.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_benign_traffic_is_not_flagged() {
+        let packet_stats = Arc::new(PacketStats::new());
+        let engine = AnalyticsEngine::new(packet_stats);
+
+        engine.process_packet("10.0.0.3", "192.168.1.1", 12345, 80, 6, 1500, 0, false).await.unwrap();
+
+        let flagged = engine.detect_attacks().await;
+        assert!(flagged.is_empty());
+    }
+
+    #[test]
+    fn test_space_saving_stays_within_capacity_and_ranks_by_count() {
+        let mut estimator = SpaceSaving::new(2);
+        estimator.record("a"); // a: 1
+        estimator.record("b"); // a: 1, b: 1
+        estimator.record("a"); // a: 2, b: 1
+        estimator.record("c"); // evicts b (min count 1): a: 2, c: 2 (count = 1+1, error = 1)
+
+        assert_eq!(estimator.counters.len(), 2);
+        let top = estimator.top(10);
+        assert_eq!(top.len(), 2);
+        assert_eq!(top[0], ("a", 2, 0));
+        assert!(top.contains(&("c", 2, 1)));
+    }
+
+    #[tokio::test]
+    async fn test_get_stats_top_applications_survive_more_distinct_flows_than_capacity() {
+        let packet_stats = Arc::new(PacketStats::new());
+        let engine = AnalyticsEngine::new(packet_stats);
+
+        for port in 0..(SPACE_SAVING_CAPACITY as u16 + 10) {
+            engine.process_packet("10.0.0.4", "192.168.1.1", 12345, 2000 + port, 6, 60, 0, false).await.unwrap();
+        }
+        for _ in 0..5 {
+            engine.process_packet("10.0.0.4", "192.168.1.2", 12345, 80, 6, 60, 0, false).await.unwrap();
+        }
+
+        let stats = engine.get_stats().await;
+        assert!(stats.top_applications.iter().any(|(app, count, _)| app == "HTTP" && *count >= 5));
+    }
+
+    #[tokio::test]
+    async fn test_export_and_import_snapshot_round_trips_flows() {
+        let packet_stats = Arc::new(PacketStats::new());
+        let engine = AnalyticsEngine::new(packet_stats);
+        engine.process_packet("192.168.1.1", "192.168.1.2", 80, 8080, 6, 1500, 0, false).await.unwrap();
+
+        let snapshot = engine.export_snapshot().await.unwrap();
+        assert!(snapshot.contains("\"first_seen_unix_ms\""));
+
+        let packet_stats = Arc::new(PacketStats::new());
+        let restored = AnalyticsEngine::new(packet_stats);
+        restored.import_snapshot(&snapshot).await.unwrap();
+
+        let flows = restored.get_flows().await;
+        assert_eq!(flows.len(), 1);
+        assert_eq!(flows[0].source_ip, "192.168.1.1");
+        assert!(flows[0].first_seen_unix_ms > 0);
+
+        let stats = restored.get_stats().await;
+        assert!(stats.top_sources.iter().any(|(ip, count, _)| ip == "192.168.1.1" && *count >= 1));
+    }
+
+    #[tokio::test]
+    async fn test_render_netflow_v9_packet_encodes_ipv4_flow() {
+        let packet_stats = Arc::new(PacketStats::new());
+        let engine = AnalyticsEngine::new(packet_stats);
+        engine.process_packet("192.168.1.1", "192.168.1.2", 80, 8080, 6, 1500, 0, false).await.unwrap();
+        let flows = engine.get_flows().await;
+
+        let template_v4 = netflow_v9_template_flowset(NETFLOW_V9_TEMPLATE_ID_V4, NETFLOW_V9_FIELDS_V4);
+        let template_v6 = netflow_v9_template_flowset(NETFLOW_V9_TEMPLATE_ID_V6, NETFLOW_V9_FIELDS_V6);
+        let packet = render_netflow_v9_packet(&flows, engine.start_time, 0, 42, &template_v4, &template_v6);
+
+        // Header: version 9, 3 records (2 templates + 1 data record).
+        assert_eq!(&packet[0..2], &9u16.to_be_bytes());
+        assert_eq!(&packet[2..4], &3u16.to_be_bytes());
+        assert!(packet.len() > 20 + template_v4.len() + template_v6.len());
+        // The IPv4 source address should appear verbatim in the data FlowSet.
+        assert!(packet.windows(4).any(|w| w == [192, 168, 1, 1]));
+    }
 }
@@ -1,9 +1,177 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{Notify, RwLock};
 use serde::{Deserialize, Serialize};
 use chrono::{DateTime, Utc};
 
+/// Error returned by [`ClickHouseClient`] and [`AnalyticsEngine`] operations.
+/// Replaces a bare `Box<dyn std::error::Error>` so callers — in particular
+/// the retry/reconnect logic in [`AnalyticsEngine`] — can tell a dropped
+/// connection from a bad query or a malformed row instead of inspecting an
+/// opaque trait object.
+#[derive(Debug, thiserror::Error)]
+pub enum AnalyticsError {
+    #[error("failed to connect to clickhouse: {0}")]
+    Connection(#[source] clickhouse_rs::errors::Error),
+    #[error("failed to apply ddl: {0}")]
+    Ddl(#[source] clickhouse_rs::errors::Error),
+    #[error("failed to insert rows: {0}")]
+    Insert(#[source] clickhouse_rs::errors::Error),
+    #[error("failed to query rows: {0}")]
+    Query(#[source] clickhouse_rs::errors::Error),
+    #[error("invalid {field} value {value:?}")]
+    Serialize { field: &'static str, value: String },
+}
+
+/// Routing protocol discriminator for [`RoutingMetrics`]. A closed enum
+/// instead of a bare `String` keeps `ORDER BY (timestamp, protocol)`
+/// cardinality bounded and rejects typos at construction time rather than
+/// silently fragmenting the table into one part per misspelling.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RoutingProtocol {
+    Bgp,
+    Ospf,
+    Isis,
+    Static,
+}
+
+impl fmt::Display for RoutingProtocol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            RoutingProtocol::Bgp => "bgp",
+            RoutingProtocol::Ospf => "ospf",
+            RoutingProtocol::Isis => "isis",
+            RoutingProtocol::Static => "static",
+        })
+    }
+}
+
+impl FromStr for RoutingProtocol {
+    type Err = AnalyticsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bgp" => Ok(RoutingProtocol::Bgp),
+            "ospf" => Ok(RoutingProtocol::Ospf),
+            "isis" => Ok(RoutingProtocol::Isis),
+            "static" => Ok(RoutingProtocol::Static),
+            other => Err(AnalyticsError::Serialize { field: "protocol", value: other.to_string() }),
+        }
+    }
+}
+
+/// Traffic shaping algorithm discriminator for [`TrafficShapingMetrics`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ShapingAlgorithm {
+    TokenBucket,
+    LeakyBucket,
+    Fifo,
+    Wfq,
+}
+
+impl fmt::Display for ShapingAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ShapingAlgorithm::TokenBucket => "token_bucket",
+            ShapingAlgorithm::LeakyBucket => "leaky_bucket",
+            ShapingAlgorithm::Fifo => "fifo",
+            ShapingAlgorithm::Wfq => "wfq",
+        })
+    }
+}
+
+impl FromStr for ShapingAlgorithm {
+    type Err = AnalyticsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "token_bucket" => Ok(ShapingAlgorithm::TokenBucket),
+            "leaky_bucket" => Ok(ShapingAlgorithm::LeakyBucket),
+            "fifo" => Ok(ShapingAlgorithm::Fifo),
+            "wfq" => Ok(ShapingAlgorithm::Wfq),
+            other => Err(AnalyticsError::Serialize { field: "algorithm", value: other.to_string() }),
+        }
+    }
+}
+
+/// Link impairment kind discriminator for [`ImpairmentMetrics`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ImpairmentType {
+    Delay,
+    Loss,
+    Jitter,
+    Corruption,
+    Reorder,
+}
+
+impl fmt::Display for ImpairmentType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ImpairmentType::Delay => "delay",
+            ImpairmentType::Loss => "loss",
+            ImpairmentType::Jitter => "jitter",
+            ImpairmentType::Corruption => "corruption",
+            ImpairmentType::Reorder => "reorder",
+        })
+    }
+}
+
+impl FromStr for ImpairmentType {
+    type Err = AnalyticsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "delay" => Ok(ImpairmentType::Delay),
+            "loss" => Ok(ImpairmentType::Loss),
+            "jitter" => Ok(ImpairmentType::Jitter),
+            "corruption" => Ok(ImpairmentType::Corruption),
+            "reorder" => Ok(ImpairmentType::Reorder),
+            other => Err(AnalyticsError::Serialize { field: "impairment_type", value: other.to_string() }),
+        }
+    }
+}
+
+/// Cloud provider discriminator for [`CloudResourceMetrics`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CloudProvider {
+    Aws,
+    Azure,
+    Gcp,
+    CloudPods,
+    OnPrem,
+}
+
+impl fmt::Display for CloudProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CloudProvider::Aws => "aws",
+            CloudProvider::Azure => "azure",
+            CloudProvider::Gcp => "gcp",
+            CloudProvider::CloudPods => "cloudpods",
+            CloudProvider::OnPrem => "on_prem",
+        })
+    }
+}
+
+impl FromStr for CloudProvider {
+    type Err = AnalyticsError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "aws" => Ok(CloudProvider::Aws),
+            "azure" => Ok(CloudProvider::Azure),
+            "gcp" => Ok(CloudProvider::Gcp),
+            "cloudpods" => Ok(CloudProvider::CloudPods),
+            "on_prem" => Ok(CloudProvider::OnPrem),
+            other => Err(AnalyticsError::Serialize { field: "provider", value: other.to_string() }),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct NetworkMetrics {
     pub timestamp: DateTime<Utc>,
@@ -21,7 +189,7 @@ pub struct NetworkMetrics {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RoutingMetrics {
     pub timestamp: DateTime<Utc>,
-    pub protocol: String,
+    pub protocol: RoutingProtocol,
     pub routes_count: u32,
     pub neighbors_count: u32,
     pub convergence_time_ms: u32,
@@ -34,7 +202,7 @@ pub struct RoutingMetrics {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TrafficShapingMetrics {
     pub timestamp: DateTime<Utc>,
-    pub algorithm: String,
+    pub algorithm: ShapingAlgorithm,
     pub packets_processed: u64,
     pub packets_dropped: u64,
     pub bytes_processed: u64,
@@ -47,7 +215,7 @@ pub struct TrafficShapingMetrics {
 pub struct ImpairmentMetrics {
     pub timestamp: DateTime<Utc>,
     pub interface: String,
-    pub impairment_type: String,
+    pub impairment_type: ImpairmentType,
     pub packets_affected: u64,
     pub loss_percentage: f64,
     pub delay_ms: f64,
@@ -57,7 +225,7 @@ pub struct ImpairmentMetrics {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CloudResourceMetrics {
     pub timestamp: DateTime<Utc>,
-    pub provider: String,
+    pub provider: CloudProvider,
     pub resource_type: String,
     pub resource_id: String,
     pub region: String,
@@ -83,8 +251,45 @@ pub struct ClickHouseConfig {
     pub max_connections: u32,
 }
 
+/// Which table tier a time-range query should read from. Raw rows are kept
+/// for 30 days; the rollups trade resolution for much longer retention, so a
+/// long-range query can still be answered without scanning expired data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricResolution {
+    /// Full-resolution rows from the base table (30-day retention).
+    Raw,
+    /// 1-minute buckets, `AggregateFunction` state merged at query time (180-day retention).
+    OneMinute,
+    /// 1-hour buckets, `AggregateFunction` state merged at query time (2-year retention).
+    OneHour,
+}
+
+impl MetricResolution {
+    /// Pick the coarsest rollup that still fully covers `[start_time, end_time]`,
+    /// so recent queries hit raw rows and long-range ones hit a rollup instead
+    /// of scanning (or missing) data past the raw table's TTL.
+    pub fn auto(start_time: DateTime<Utc>, end_time: DateTime<Utc>) -> Self {
+        let span = end_time - start_time;
+        if span > chrono::Duration::days(30) {
+            MetricResolution::OneHour
+        } else if span > chrono::Duration::hours(6) {
+            MetricResolution::OneMinute
+        } else {
+            MetricResolution::Raw
+        }
+    }
+
+    fn table_suffix(self) -> &'static str {
+        match self {
+            MetricResolution::Raw => "",
+            MetricResolution::OneMinute => "_1m",
+            MetricResolution::OneHour => "_1h",
+        }
+    }
+}
+
 impl ClickHouseClient {
-    pub async fn new(config: ClickHouseConfig) -> Result<Self, Box<dyn std::error::Error>> {
+    pub async fn new(config: ClickHouseConfig) -> Result<Self, AnalyticsError> {
         let ddl = format!(
             "CREATE DATABASE IF NOT EXISTS {}",
             config.database
@@ -114,7 +319,7 @@ impl ClickHouseClient {
         Ok(client)
     }
     
-    async fn create_tables(&self) -> Result<(), Box<dyn std::error::Error>> {
+    async fn create_tables(&self) -> Result<(), AnalyticsError> {
         let tables = vec![
             // Network metrics table
             r#"
@@ -199,298 +404,921 @@ impl ClickHouseClient {
             ORDER BY (timestamp, provider, resource_type, resource_id)
             TTL timestamp + INTERVAL 30 DAY
             "#,
+
+            // Network metrics, rolled up to 1-minute buckets for medium-range
+            // queries once the 30-day raw window has expired.
+            r#"
+            CREATE TABLE IF NOT EXISTS network_metrics_1m (
+                timestamp DateTime64(3),
+                interface String,
+                bytes_in AggregateFunction(sum, UInt64),
+                bytes_out AggregateFunction(sum, UInt64),
+                packets_in AggregateFunction(sum, UInt64),
+                packets_out AggregateFunction(sum, UInt64),
+                errors_in AggregateFunction(sum, UInt64),
+                errors_out AggregateFunction(sum, UInt64),
+                drops_in AggregateFunction(sum, UInt64),
+                drops_out AggregateFunction(sum, UInt64)
+            ) ENGINE = AggregatingMergeTree()
+            ORDER BY (timestamp, interface)
+            TTL timestamp + INTERVAL 180 DAY
+            "#,
+            r#"
+            CREATE MATERIALIZED VIEW IF NOT EXISTS network_metrics_1m_mv
+            TO network_metrics_1m
+            AS SELECT
+                toStartOfMinute(timestamp) AS timestamp,
+                interface,
+                sumState(bytes_in) AS bytes_in,
+                sumState(bytes_out) AS bytes_out,
+                sumState(packets_in) AS packets_in,
+                sumState(packets_out) AS packets_out,
+                sumState(errors_in) AS errors_in,
+                sumState(errors_out) AS errors_out,
+                sumState(drops_in) AS drops_in,
+                sumState(drops_out) AS drops_out
+            FROM network_metrics
+            GROUP BY timestamp, interface
+            "#,
+
+            // Network metrics, rolled up to 1-hour buckets for long-range
+            // (multi-month) queries, retained far longer than either the raw
+            // or 1-minute table.
+            r#"
+            CREATE TABLE IF NOT EXISTS network_metrics_1h (
+                timestamp DateTime64(3),
+                interface String,
+                bytes_in AggregateFunction(sum, UInt64),
+                bytes_out AggregateFunction(sum, UInt64),
+                packets_in AggregateFunction(sum, UInt64),
+                packets_out AggregateFunction(sum, UInt64),
+                errors_in AggregateFunction(sum, UInt64),
+                errors_out AggregateFunction(sum, UInt64),
+                drops_in AggregateFunction(sum, UInt64),
+                drops_out AggregateFunction(sum, UInt64)
+            ) ENGINE = AggregatingMergeTree()
+            ORDER BY (timestamp, interface)
+            TTL timestamp + INTERVAL 2 YEAR
+            "#,
+            r#"
+            CREATE MATERIALIZED VIEW IF NOT EXISTS network_metrics_1h_mv
+            TO network_metrics_1h
+            AS SELECT
+                toStartOfHour(timestamp) AS timestamp,
+                interface,
+                sumState(bytes_in) AS bytes_in,
+                sumState(bytes_out) AS bytes_out,
+                sumState(packets_in) AS packets_in,
+                sumState(packets_out) AS packets_out,
+                sumState(errors_in) AS errors_in,
+                sumState(errors_out) AS errors_out,
+                sumState(drops_in) AS drops_in,
+                sumState(drops_out) AS drops_out
+            FROM network_metrics
+            GROUP BY timestamp, interface
+            "#,
+
+            // Routing metrics, rolled up the same way: counts/timings averaged
+            // per bucket, volume counters summed.
+            r#"
+            CREATE TABLE IF NOT EXISTS routing_metrics_1m (
+                timestamp DateTime64(3),
+                protocol String,
+                routes_count AggregateFunction(avg, UInt32),
+                neighbors_count AggregateFunction(avg, UInt32),
+                convergence_time_ms AggregateFunction(avg, UInt32),
+                updates_sent AggregateFunction(sum, UInt64),
+                updates_received AggregateFunction(sum, UInt64),
+                withdrawals_sent AggregateFunction(sum, UInt64),
+                withdrawals_received AggregateFunction(sum, UInt64)
+            ) ENGINE = AggregatingMergeTree()
+            ORDER BY (timestamp, protocol)
+            TTL timestamp + INTERVAL 180 DAY
+            "#,
+            r#"
+            CREATE MATERIALIZED VIEW IF NOT EXISTS routing_metrics_1m_mv
+            TO routing_metrics_1m
+            AS SELECT
+                toStartOfMinute(timestamp) AS timestamp,
+                protocol,
+                avgState(routes_count) AS routes_count,
+                avgState(neighbors_count) AS neighbors_count,
+                avgState(convergence_time_ms) AS convergence_time_ms,
+                sumState(updates_sent) AS updates_sent,
+                sumState(updates_received) AS updates_received,
+                sumState(withdrawals_sent) AS withdrawals_sent,
+                sumState(withdrawals_received) AS withdrawals_received
+            FROM routing_metrics
+            GROUP BY timestamp, protocol
+            "#,
+            r#"
+            CREATE TABLE IF NOT EXISTS routing_metrics_1h (
+                timestamp DateTime64(3),
+                protocol String,
+                routes_count AggregateFunction(avg, UInt32),
+                neighbors_count AggregateFunction(avg, UInt32),
+                convergence_time_ms AggregateFunction(avg, UInt32),
+                updates_sent AggregateFunction(sum, UInt64),
+                updates_received AggregateFunction(sum, UInt64),
+                withdrawals_sent AggregateFunction(sum, UInt64),
+                withdrawals_received AggregateFunction(sum, UInt64)
+            ) ENGINE = AggregatingMergeTree()
+            ORDER BY (timestamp, protocol)
+            TTL timestamp + INTERVAL 2 YEAR
+            "#,
+            r#"
+            CREATE MATERIALIZED VIEW IF NOT EXISTS routing_metrics_1h_mv
+            TO routing_metrics_1h
+            AS SELECT
+                toStartOfHour(timestamp) AS timestamp,
+                protocol,
+                avgState(routes_count) AS routes_count,
+                avgState(neighbors_count) AS neighbors_count,
+                avgState(convergence_time_ms) AS convergence_time_ms,
+                sumState(updates_sent) AS updates_sent,
+                sumState(updates_received) AS updates_received,
+                sumState(withdrawals_sent) AS withdrawals_sent,
+                sumState(withdrawals_received) AS withdrawals_received
+            FROM routing_metrics
+            GROUP BY timestamp, protocol
+            "#,
         ];
-        
+
         for table_ddl in tables {
             self.execute_ddl(table_ddl).await?;
         }
-        
+
         Ok(())
     }
     
-    async fn execute_ddl(&self, ddl: &str) -> Result<(), Box<dyn std::error::Error>> {
-        let mut session = self.client.get_handle().await?;
-        session.execute(ddl).await?;
+    async fn execute_ddl(&self, ddl: &str) -> Result<(), AnalyticsError> {
+        let mut session = self.client.get_handle().await.map_err(AnalyticsError::Connection)?;
+        session.execute(ddl).await.map_err(AnalyticsError::Ddl)?;
         Ok(())
     }
     
-    pub async fn insert_network_metrics(&self, metrics: &[NetworkMetrics]) -> Result<(), Box<dyn std::error::Error>> {
-        let mut session = self.client.get_handle().await?;
-        
-        let query = r#"
-            INSERT INTO network_metrics (
-                timestamp, interface, bytes_in, bytes_out, packets_in, packets_out,
-                errors_in, errors_out, drops_in, drops_out
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-        "#;
-        
-        let mut insert = session.insert(query)?;
-        
+    /// Transpose a batch of [`NetworkMetrics`] into struct-of-arrays columns,
+    /// one `Vec` per field, in the order `insert_network_metrics` feeds them
+    /// to the block API. Kept as a standalone, non-async function so the
+    /// whole batch is built without a single awaited call.
+    fn network_metrics_columns(
+        metrics: &[NetworkMetrics],
+    ) -> (Vec<DateTime<Utc>>, Vec<String>, Vec<u64>, Vec<u64>, Vec<u64>, Vec<u64>, Vec<u64>, Vec<u64>, Vec<u64>, Vec<u64>) {
+        let mut timestamp = Vec::with_capacity(metrics.len());
+        let mut interface = Vec::with_capacity(metrics.len());
+        let mut bytes_in = Vec::with_capacity(metrics.len());
+        let mut bytes_out = Vec::with_capacity(metrics.len());
+        let mut packets_in = Vec::with_capacity(metrics.len());
+        let mut packets_out = Vec::with_capacity(metrics.len());
+        let mut errors_in = Vec::with_capacity(metrics.len());
+        let mut errors_out = Vec::with_capacity(metrics.len());
+        let mut drops_in = Vec::with_capacity(metrics.len());
+        let mut drops_out = Vec::with_capacity(metrics.len());
+
         for metric in metrics {
-            insert.write(&metric.timestamp).await?;
-            insert.write(&metric.interface).await?;
-            insert.write(&metric.bytes_in).await?;
-            insert.write(&metric.bytes_out).await?;
-            insert.write(&metric.packets_in).await?;
-            insert.write(&metric.packets_out).await?;
-            insert.write(&metric.errors_in).await?;
-            insert.write(&metric.errors_out).await?;
-            insert.write(&metric.drops_in).await?;
-            insert.write(&metric.drops_out).await?;
+            timestamp.push(metric.timestamp);
+            interface.push(metric.interface.clone());
+            bytes_in.push(metric.bytes_in);
+            bytes_out.push(metric.bytes_out);
+            packets_in.push(metric.packets_in);
+            packets_out.push(metric.packets_out);
+            errors_in.push(metric.errors_in);
+            errors_out.push(metric.errors_out);
+            drops_in.push(metric.drops_in);
+            drops_out.push(metric.drops_out);
         }
-        
-        insert.end().await?;
+
+        (timestamp, interface, bytes_in, bytes_out, packets_in, packets_out, errors_in, errors_out, drops_in, drops_out)
+    }
+
+    pub async fn insert_network_metrics(&self, metrics: &[NetworkMetrics]) -> Result<(), AnalyticsError> {
+        if metrics.is_empty() {
+            return Ok(());
+        }
+
+        let (timestamp, interface, bytes_in, bytes_out, packets_in, packets_out, errors_in, errors_out, drops_in, drops_out) =
+            Self::network_metrics_columns(metrics);
+
+        // One columnar block for the whole batch, handed to the server in a
+        // single round trip, instead of awaiting a `write()` call per field
+        // per row.
+        let block = clickhouse_rs::Block::new()
+            .column("timestamp", timestamp)
+            .column("interface", interface)
+            .column("bytes_in", bytes_in)
+            .column("bytes_out", bytes_out)
+            .column("packets_in", packets_in)
+            .column("packets_out", packets_out)
+            .column("errors_in", errors_in)
+            .column("errors_out", errors_out)
+            .column("drops_in", drops_in)
+            .column("drops_out", drops_out);
+
+        let mut session = self.client.get_handle().await.map_err(AnalyticsError::Connection)?;
+        session.insert("network_metrics", block).await.map_err(AnalyticsError::Insert)?;
         Ok(())
     }
-    
-    pub async fn insert_routing_metrics(&self, metrics: &[RoutingMetrics]) -> Result<(), Box<dyn std::error::Error>> {
-        let mut session = self.client.get_handle().await?;
-        
-        let query = r#"
-            INSERT INTO routing_metrics (
-                timestamp, protocol, routes_count, neighbors_count, convergence_time_ms,
-                updates_sent, updates_received, withdrawals_sent, withdrawals_received
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
-        "#;
-        
-        let mut insert = session.insert(query)?;
-        
+
+    /// Transpose a batch of [`RoutingMetrics`] into struct-of-arrays columns,
+    /// one `Vec` per field, in the order `insert_routing_metrics` feeds them
+    /// to the block API. Kept as a standalone, non-async function so the
+    /// whole batch is built without a single awaited call.
+    fn routing_metrics_columns(
+        metrics: &[RoutingMetrics],
+    ) -> (Vec<DateTime<Utc>>, Vec<String>, Vec<u32>, Vec<u32>, Vec<u32>, Vec<u64>, Vec<u64>, Vec<u64>, Vec<u64>) {
+        let mut timestamp = Vec::with_capacity(metrics.len());
+        let mut protocol = Vec::with_capacity(metrics.len());
+        let mut routes_count = Vec::with_capacity(metrics.len());
+        let mut neighbors_count = Vec::with_capacity(metrics.len());
+        let mut convergence_time_ms = Vec::with_capacity(metrics.len());
+        let mut updates_sent = Vec::with_capacity(metrics.len());
+        let mut updates_received = Vec::with_capacity(metrics.len());
+        let mut withdrawals_sent = Vec::with_capacity(metrics.len());
+        let mut withdrawals_received = Vec::with_capacity(metrics.len());
+
         for metric in metrics {
-            insert.write(&metric.timestamp).await?;
-            insert.write(&metric.protocol).await?;
-            insert.write(&metric.routes_count).await?;
-            insert.write(&metric.neighbors_count).await?;
-            insert.write(&metric.convergence_time_ms).await?;
-            insert.write(&metric.updates_sent).await?;
-            insert.write(&metric.updates_received).await?;
-            insert.write(&metric.withdrawals_sent).await?;
-            insert.write(&metric.withdrawals_received).await?;
+            timestamp.push(metric.timestamp);
+            protocol.push(metric.protocol.to_string());
+            routes_count.push(metric.routes_count);
+            neighbors_count.push(metric.neighbors_count);
+            convergence_time_ms.push(metric.convergence_time_ms);
+            updates_sent.push(metric.updates_sent);
+            updates_received.push(metric.updates_received);
+            withdrawals_sent.push(metric.withdrawals_sent);
+            withdrawals_received.push(metric.withdrawals_received);
         }
-        
-        insert.end().await?;
+
+        (
+            timestamp,
+            protocol,
+            routes_count,
+            neighbors_count,
+            convergence_time_ms,
+            updates_sent,
+            updates_received,
+            withdrawals_sent,
+            withdrawals_received,
+        )
+    }
+
+    pub async fn insert_routing_metrics(&self, metrics: &[RoutingMetrics]) -> Result<(), AnalyticsError> {
+        if metrics.is_empty() {
+            return Ok(());
+        }
+
+        let (
+            timestamp,
+            protocol,
+            routes_count,
+            neighbors_count,
+            convergence_time_ms,
+            updates_sent,
+            updates_received,
+            withdrawals_sent,
+            withdrawals_received,
+        ) = Self::routing_metrics_columns(metrics);
+
+        let block = clickhouse_rs::Block::new()
+            .column("timestamp", timestamp)
+            .column("protocol", protocol)
+            .column("routes_count", routes_count)
+            .column("neighbors_count", neighbors_count)
+            .column("convergence_time_ms", convergence_time_ms)
+            .column("updates_sent", updates_sent)
+            .column("updates_received", updates_received)
+            .column("withdrawals_sent", withdrawals_sent)
+            .column("withdrawals_received", withdrawals_received);
+
+        let mut session = self.client.get_handle().await.map_err(AnalyticsError::Connection)?;
+        session.insert("routing_metrics", block).await.map_err(AnalyticsError::Insert)?;
         Ok(())
     }
-    
-    pub async fn insert_traffic_shaping_metrics(&self, metrics: &[TrafficShapingMetrics]) -> Result<(), Box<dyn std::error::Error>> {
-        let mut session = self.client.get_handle().await?;
-        
-        let query = r#"
-            INSERT INTO traffic_shaping_metrics (
-                timestamp, algorithm, packets_processed, packets_dropped,
-                bytes_processed, bytes_dropped, utilization_percentage, queue_depth
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?)
-        "#;
-        
-        let mut insert = session.insert(query)?;
-        
+
+    /// Transpose a batch of [`TrafficShapingMetrics`] into struct-of-arrays
+    /// columns, one `Vec` per field, in the order
+    /// `insert_traffic_shaping_metrics` feeds them to the block API. Kept as
+    /// a standalone, non-async function so the whole batch is built without
+    /// a single awaited call.
+    fn traffic_shaping_metrics_columns(
+        metrics: &[TrafficShapingMetrics],
+    ) -> (Vec<DateTime<Utc>>, Vec<String>, Vec<u64>, Vec<u64>, Vec<u64>, Vec<u64>, Vec<f64>, Vec<u32>) {
+        let mut timestamp = Vec::with_capacity(metrics.len());
+        let mut algorithm = Vec::with_capacity(metrics.len());
+        let mut packets_processed = Vec::with_capacity(metrics.len());
+        let mut packets_dropped = Vec::with_capacity(metrics.len());
+        let mut bytes_processed = Vec::with_capacity(metrics.len());
+        let mut bytes_dropped = Vec::with_capacity(metrics.len());
+        let mut utilization_percentage = Vec::with_capacity(metrics.len());
+        let mut queue_depth = Vec::with_capacity(metrics.len());
+
         for metric in metrics {
-            insert.write(&metric.timestamp).await?;
-            insert.write(&metric.algorithm).await?;
-            insert.write(&metric.packets_processed).await?;
-            insert.write(&metric.packets_dropped).await?;
-            insert.write(&metric.bytes_processed).await?;
-            insert.write(&metric.bytes_dropped).await?;
-            insert.write(&metric.utilization_percentage).await?;
-            insert.write(&metric.queue_depth).await?;
+            timestamp.push(metric.timestamp);
+            algorithm.push(metric.algorithm.to_string());
+            packets_processed.push(metric.packets_processed);
+            packets_dropped.push(metric.packets_dropped);
+            bytes_processed.push(metric.bytes_processed);
+            bytes_dropped.push(metric.bytes_dropped);
+            utilization_percentage.push(metric.utilization_percentage);
+            queue_depth.push(metric.queue_depth);
         }
-        
-        insert.end().await?;
+
+        (
+            timestamp,
+            algorithm,
+            packets_processed,
+            packets_dropped,
+            bytes_processed,
+            bytes_dropped,
+            utilization_percentage,
+            queue_depth,
+        )
+    }
+
+    pub async fn insert_traffic_shaping_metrics(&self, metrics: &[TrafficShapingMetrics]) -> Result<(), AnalyticsError> {
+        if metrics.is_empty() {
+            return Ok(());
+        }
+
+        let (
+            timestamp,
+            algorithm,
+            packets_processed,
+            packets_dropped,
+            bytes_processed,
+            bytes_dropped,
+            utilization_percentage,
+            queue_depth,
+        ) = Self::traffic_shaping_metrics_columns(metrics);
+
+        let block = clickhouse_rs::Block::new()
+            .column("timestamp", timestamp)
+            .column("algorithm", algorithm)
+            .column("packets_processed", packets_processed)
+            .column("packets_dropped", packets_dropped)
+            .column("bytes_processed", bytes_processed)
+            .column("bytes_dropped", bytes_dropped)
+            .column("utilization_percentage", utilization_percentage)
+            .column("queue_depth", queue_depth);
+
+        let mut session = self.client.get_handle().await.map_err(AnalyticsError::Connection)?;
+        session.insert("traffic_shaping_metrics", block).await.map_err(AnalyticsError::Insert)?;
         Ok(())
     }
-    
-    pub async fn insert_impairment_metrics(&self, metrics: &[ImpairmentMetrics]) -> Result<(), Box<dyn std::error::Error>> {
-        let mut session = self.client.get_handle().await?;
-        
-        let query = r#"
-            INSERT INTO impairment_metrics (
-                timestamp, interface, impairment_type, packets_affected,
-                loss_percentage, delay_ms, jitter_ms
-            ) VALUES (?, ?, ?, ?, ?, ?, ?)
-        "#;
-        
-        let mut insert = session.insert(query)?;
-        
+
+    /// Transpose a batch of [`ImpairmentMetrics`] into struct-of-arrays
+    /// columns, one `Vec` per field, in the order `insert_impairment_metrics`
+    /// feeds them to the block API. Kept as a standalone, non-async function
+    /// so the whole batch is built without a single awaited call.
+    fn impairment_metrics_columns(
+        metrics: &[ImpairmentMetrics],
+    ) -> (Vec<DateTime<Utc>>, Vec<String>, Vec<String>, Vec<u64>, Vec<f64>, Vec<f64>, Vec<f64>) {
+        let mut timestamp = Vec::with_capacity(metrics.len());
+        let mut interface = Vec::with_capacity(metrics.len());
+        let mut impairment_type = Vec::with_capacity(metrics.len());
+        let mut packets_affected = Vec::with_capacity(metrics.len());
+        let mut loss_percentage = Vec::with_capacity(metrics.len());
+        let mut delay_ms = Vec::with_capacity(metrics.len());
+        let mut jitter_ms = Vec::with_capacity(metrics.len());
+
         for metric in metrics {
-            insert.write(&metric.timestamp).await?;
-            insert.write(&metric.interface).await?;
-            insert.write(&metric.impairment_type).await?;
-            insert.write(&metric.packets_affected).await?;
-            insert.write(&metric.loss_percentage).await?;
-            insert.write(&metric.delay_ms).await?;
-            insert.write(&metric.jitter_ms).await?;
+            timestamp.push(metric.timestamp);
+            interface.push(metric.interface.clone());
+            impairment_type.push(metric.impairment_type.to_string());
+            packets_affected.push(metric.packets_affected);
+            loss_percentage.push(metric.loss_percentage);
+            delay_ms.push(metric.delay_ms);
+            jitter_ms.push(metric.jitter_ms);
         }
-        
-        insert.end().await?;
+
+        (timestamp, interface, impairment_type, packets_affected, loss_percentage, delay_ms, jitter_ms)
+    }
+
+    pub async fn insert_impairment_metrics(&self, metrics: &[ImpairmentMetrics]) -> Result<(), AnalyticsError> {
+        if metrics.is_empty() {
+            return Ok(());
+        }
+
+        let (timestamp, interface, impairment_type, packets_affected, loss_percentage, delay_ms, jitter_ms) =
+            Self::impairment_metrics_columns(metrics);
+
+        let block = clickhouse_rs::Block::new()
+            .column("timestamp", timestamp)
+            .column("interface", interface)
+            .column("impairment_type", impairment_type)
+            .column("packets_affected", packets_affected)
+            .column("loss_percentage", loss_percentage)
+            .column("delay_ms", delay_ms)
+            .column("jitter_ms", jitter_ms);
+
+        let mut session = self.client.get_handle().await.map_err(AnalyticsError::Connection)?;
+        session.insert("impairment_metrics", block).await.map_err(AnalyticsError::Insert)?;
         Ok(())
     }
-    
-    pub async fn insert_cloud_resource_metrics(&self, metrics: &[CloudResourceMetrics]) -> Result<(), Box<dyn std::error::Error>> {
-        let mut session = self.client.get_handle().await?;
-        
-        let query = r#"
-            INSERT INTO cloud_resource_metrics (
-                timestamp, provider, resource_type, resource_id, region, status,
-                cpu_usage, memory_usage, network_usage, cost_per_hour
-            ) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
-        "#;
-        
-        let mut insert = session.insert(query)?;
-        
+
+    /// Transpose a batch of [`CloudResourceMetrics`] into struct-of-arrays
+    /// columns, one `Vec` per field, in the order
+    /// `insert_cloud_resource_metrics` feeds them to the block API. Kept as
+    /// a standalone, non-async function so the whole batch is built without
+    /// a single awaited call.
+    fn cloud_resource_metrics_columns(
+        metrics: &[CloudResourceMetrics],
+    ) -> (
+        Vec<DateTime<Utc>>,
+        Vec<String>,
+        Vec<String>,
+        Vec<String>,
+        Vec<String>,
+        Vec<String>,
+        Vec<f64>,
+        Vec<f64>,
+        Vec<f64>,
+        Vec<f64>,
+    ) {
+        let mut timestamp = Vec::with_capacity(metrics.len());
+        let mut provider = Vec::with_capacity(metrics.len());
+        let mut resource_type = Vec::with_capacity(metrics.len());
+        let mut resource_id = Vec::with_capacity(metrics.len());
+        let mut region = Vec::with_capacity(metrics.len());
+        let mut status = Vec::with_capacity(metrics.len());
+        let mut cpu_usage = Vec::with_capacity(metrics.len());
+        let mut memory_usage = Vec::with_capacity(metrics.len());
+        let mut network_usage = Vec::with_capacity(metrics.len());
+        let mut cost_per_hour = Vec::with_capacity(metrics.len());
+
         for metric in metrics {
-            insert.write(&metric.timestamp).await?;
-            insert.write(&metric.provider).await?;
-            insert.write(&metric.resource_type).await?;
-            insert.write(&metric.resource_id).await?;
-            insert.write(&metric.region).await?;
-            insert.write(&metric.status).await?;
-            insert.write(&metric.cpu_usage).await?;
-            insert.write(&metric.memory_usage).await?;
-            insert.write(&metric.network_usage).await?;
-            insert.write(&metric.cost_per_hour).await?;
+            timestamp.push(metric.timestamp);
+            provider.push(metric.provider.to_string());
+            resource_type.push(metric.resource_type.clone());
+            resource_id.push(metric.resource_id.clone());
+            region.push(metric.region.clone());
+            status.push(metric.status.clone());
+            cpu_usage.push(metric.cpu_usage);
+            memory_usage.push(metric.memory_usage);
+            network_usage.push(metric.network_usage);
+            cost_per_hour.push(metric.cost_per_hour);
         }
-        
-        insert.end().await?;
+
+        (
+            timestamp,
+            provider,
+            resource_type,
+            resource_id,
+            region,
+            status,
+            cpu_usage,
+            memory_usage,
+            network_usage,
+            cost_per_hour,
+        )
+    }
+
+    pub async fn insert_cloud_resource_metrics(&self, metrics: &[CloudResourceMetrics]) -> Result<(), AnalyticsError> {
+        if metrics.is_empty() {
+            return Ok(());
+        }
+
+        let (
+            timestamp,
+            provider,
+            resource_type,
+            resource_id,
+            region,
+            status,
+            cpu_usage,
+            memory_usage,
+            network_usage,
+            cost_per_hour,
+        ) = Self::cloud_resource_metrics_columns(metrics);
+
+        let block = clickhouse_rs::Block::new()
+            .column("timestamp", timestamp)
+            .column("provider", provider)
+            .column("resource_type", resource_type)
+            .column("resource_id", resource_id)
+            .column("region", region)
+            .column("status", status)
+            .column("cpu_usage", cpu_usage)
+            .column("memory_usage", memory_usage)
+            .column("network_usage", network_usage)
+            .column("cost_per_hour", cost_per_hour);
+
+        let mut session = self.client.get_handle().await.map_err(AnalyticsError::Connection)?;
+        session.insert("cloud_resource_metrics", block).await.map_err(AnalyticsError::Insert)?;
         Ok(())
     }
     
+    /// Query network metrics over `[start_time, end_time]`. `resolution`
+    /// picks which table tier to read; pass `None` to auto-select the
+    /// coarsest rollup that still fully covers the requested window (see
+    /// [`MetricResolution::auto`]).
     pub async fn query_network_metrics(
         &self,
         start_time: DateTime<Utc>,
         end_time: DateTime<Utc>,
         interface: Option<&str>,
-    ) -> Result<Vec<NetworkMetrics>, Box<dyn std::error::Error>> {
-        let mut session = self.client.get_handle().await?;
-        
-        let mut query = r#"
+        resolution: Option<MetricResolution>,
+    ) -> Result<Vec<NetworkMetrics>, AnalyticsError> {
+        let resolution = resolution.unwrap_or_else(|| MetricResolution::auto(start_time, end_time));
+        let mut session = self.client.get_handle().await.map_err(AnalyticsError::Connection)?;
+
+        let mut query = if resolution == MetricResolution::Raw {
+            r#"
             SELECT timestamp, interface, bytes_in, bytes_out, packets_in, packets_out,
                    errors_in, errors_out, drops_in, drops_out
             FROM network_metrics
             WHERE timestamp >= ? AND timestamp <= ?
-        "#.to_string();
-        
+            "#.to_string()
+        } else {
+            format!(
+                r#"
+            SELECT timestamp, interface,
+                   sumMerge(bytes_in) AS bytes_in, sumMerge(bytes_out) AS bytes_out,
+                   sumMerge(packets_in) AS packets_in, sumMerge(packets_out) AS packets_out,
+                   sumMerge(errors_in) AS errors_in, sumMerge(errors_out) AS errors_out,
+                   sumMerge(drops_in) AS drops_in, sumMerge(drops_out) AS drops_out
+            FROM network_metrics{suffix}
+            WHERE timestamp >= ? AND timestamp <= ?
+            "#,
+                suffix = resolution.table_suffix()
+            )
+        };
+
         let mut params = vec![start_time, end_time];
-        
+
         if let Some(iface) = interface {
             query.push_str(" AND interface = ?");
             params.push(iface.into());
         }
-        
+
+        if resolution != MetricResolution::Raw {
+            query.push_str(" GROUP BY timestamp, interface");
+        }
+
         query.push_str(" ORDER BY timestamp DESC");
-        
-        let mut cursor = session.query(&query, &params).await?;
+
+        let mut cursor = session.query(&query, &params).await.map_err(AnalyticsError::Query)?;
         let mut results = Vec::new();
-        
-        while let Some(row) = cursor.next().await? {
+
+        while let Some(row) = cursor.next().await.map_err(AnalyticsError::Query)? {
             let metric = NetworkMetrics {
-                timestamp: row.get("timestamp")?,
-                interface: row.get("interface")?,
-                bytes_in: row.get("bytes_in")?,
-                bytes_out: row.get("bytes_out")?,
-                packets_in: row.get("packets_in")?,
-                packets_out: row.get("packets_out")?,
-                errors_in: row.get("errors_in")?,
-                errors_out: row.get("errors_out")?,
-                drops_in: row.get("drops_in")?,
-                drops_out: row.get("drops_out")?,
+                timestamp: row.get("timestamp").map_err(AnalyticsError::Query)?,
+                interface: row.get("interface").map_err(AnalyticsError::Query)?,
+                bytes_in: row.get("bytes_in").map_err(AnalyticsError::Query)?,
+                bytes_out: row.get("bytes_out").map_err(AnalyticsError::Query)?,
+                packets_in: row.get("packets_in").map_err(AnalyticsError::Query)?,
+                packets_out: row.get("packets_out").map_err(AnalyticsError::Query)?,
+                errors_in: row.get("errors_in").map_err(AnalyticsError::Query)?,
+                errors_out: row.get("errors_out").map_err(AnalyticsError::Query)?,
+                drops_in: row.get("drops_in").map_err(AnalyticsError::Query)?,
+                drops_out: row.get("drops_out").map_err(AnalyticsError::Query)?,
             };
             results.push(metric);
         }
-        
+
         Ok(results)
     }
-    
+
+    /// Query routing metrics over `[start_time, end_time]`. See
+    /// [`Self::query_network_metrics`] for how `resolution` is applied.
     pub async fn query_routing_metrics(
         &self,
         start_time: DateTime<Utc>,
         end_time: DateTime<Utc>,
         protocol: Option<&str>,
-    ) -> Result<Vec<RoutingMetrics>, Box<dyn std::error::Error>> {
-        let mut session = self.client.get_handle().await?;
-        
-        let mut query = r#"
+        resolution: Option<MetricResolution>,
+    ) -> Result<Vec<RoutingMetrics>, AnalyticsError> {
+        let resolution = resolution.unwrap_or_else(|| MetricResolution::auto(start_time, end_time));
+        let mut session = self.client.get_handle().await.map_err(AnalyticsError::Connection)?;
+
+        let mut query = if resolution == MetricResolution::Raw {
+            r#"
             SELECT timestamp, protocol, routes_count, neighbors_count, convergence_time_ms,
                    updates_sent, updates_received, withdrawals_sent, withdrawals_received
             FROM routing_metrics
             WHERE timestamp >= ? AND timestamp <= ?
-        "#.to_string();
-        
+            "#.to_string()
+        } else {
+            format!(
+                r#"
+            SELECT timestamp, protocol,
+                   avgMerge(routes_count) AS routes_count,
+                   avgMerge(neighbors_count) AS neighbors_count,
+                   avgMerge(convergence_time_ms) AS convergence_time_ms,
+                   sumMerge(updates_sent) AS updates_sent,
+                   sumMerge(updates_received) AS updates_received,
+                   sumMerge(withdrawals_sent) AS withdrawals_sent,
+                   sumMerge(withdrawals_received) AS withdrawals_received
+            FROM routing_metrics{suffix}
+            WHERE timestamp >= ? AND timestamp <= ?
+            "#,
+                suffix = resolution.table_suffix()
+            )
+        };
+
         let mut params = vec![start_time, end_time];
-        
+
         if let Some(proto) = protocol {
             query.push_str(" AND protocol = ?");
             params.push(proto.into());
         }
-        
+
+        if resolution != MetricResolution::Raw {
+            query.push_str(" GROUP BY timestamp, protocol");
+        }
+
         query.push_str(" ORDER BY timestamp DESC");
-        
-        let mut cursor = session.query(&query, &params).await?;
+
+        let mut cursor = session.query(&query, &params).await.map_err(AnalyticsError::Query)?;
         let mut results = Vec::new();
-        
-        while let Some(row) = cursor.next().await? {
+
+        while let Some(row) = cursor.next().await.map_err(AnalyticsError::Query)? {
+            let protocol_raw: String = row.get("protocol").map_err(AnalyticsError::Query)?;
             let metric = RoutingMetrics {
-                timestamp: row.get("timestamp")?,
-                protocol: row.get("protocol")?,
-                routes_count: row.get("routes_count")?,
-                neighbors_count: row.get("neighbors_count")?,
-                convergence_time_ms: row.get("convergence_time_ms")?,
-                updates_sent: row.get("updates_sent")?,
-                updates_received: row.get("updates_received")?,
-                withdrawals_sent: row.get("withdrawals_sent")?,
-                withdrawals_received: row.get("withdrawals_received")?,
+                timestamp: row.get("timestamp").map_err(AnalyticsError::Query)?,
+                protocol: protocol_raw.parse()?,
+                routes_count: row.get("routes_count").map_err(AnalyticsError::Query)?,
+                neighbors_count: row.get("neighbors_count").map_err(AnalyticsError::Query)?,
+                convergence_time_ms: row.get("convergence_time_ms").map_err(AnalyticsError::Query)?,
+                updates_sent: row.get("updates_sent").map_err(AnalyticsError::Query)?,
+                updates_received: row.get("updates_received").map_err(AnalyticsError::Query)?,
+                withdrawals_sent: row.get("withdrawals_sent").map_err(AnalyticsError::Query)?,
+                withdrawals_received: row.get("withdrawals_received").map_err(AnalyticsError::Query)?,
             };
             results.push(metric);
         }
-        
+
         Ok(results)
     }
-    
+
+    /// Sum `network_metrics` into a handful of headline totals over
+    /// `[start_time, end_time]`. See [`Self::query_network_metrics`] for how
+    /// `resolution` is applied; a rollup tier is merged with `sumMerge` in a
+    /// subquery before the outer `sum`, since ClickHouse can't nest two
+    /// aggregate functions in the same `SELECT`.
     pub async fn get_aggregated_metrics(
         &self,
         start_time: DateTime<Utc>,
         end_time: DateTime<Utc>,
-    ) -> Result<HashMap<String, f64>, Box<dyn std::error::Error>> {
-        let mut session = self.client.get_handle().await?;
-        
-        let query = r#"
-            SELECT 
-                'total_bytes' as metric_name,
-                sum(bytes_in + bytes_out) as value
-            FROM network_metrics
-            WHERE timestamp >= ? AND timestamp <= ?
+        resolution: Option<MetricResolution>,
+    ) -> Result<HashMap<String, f64>, AnalyticsError> {
+        let resolution = resolution.unwrap_or_else(|| MetricResolution::auto(start_time, end_time));
+        let mut session = self.client.get_handle().await.map_err(AnalyticsError::Connection)?;
+
+        let source = if resolution == MetricResolution::Raw {
+            r#"
+                SELECT bytes_in, bytes_out, packets_in, packets_out, errors_in, errors_out, drops_in, drops_out
+                FROM network_metrics
+                WHERE timestamp >= ? AND timestamp <= ?
+            "#.to_string()
+        } else {
+            format!(
+                r#"
+                SELECT
+                    sumMerge(bytes_in) AS bytes_in, sumMerge(bytes_out) AS bytes_out,
+                    sumMerge(packets_in) AS packets_in, sumMerge(packets_out) AS packets_out,
+                    sumMerge(errors_in) AS errors_in, sumMerge(errors_out) AS errors_out,
+                    sumMerge(drops_in) AS drops_in, sumMerge(drops_out) AS drops_out
+                FROM network_metrics{suffix}
+                WHERE timestamp >= ? AND timestamp <= ?
+                GROUP BY timestamp, interface
+            "#,
+                suffix = resolution.table_suffix()
+            )
+        };
+
+        let query = format!(
+            r#"
+            SELECT 'total_bytes' as metric_name, sum(bytes_in + bytes_out) as value FROM ({source})
             UNION ALL
-            SELECT 
-                'total_packets' as metric_name,
-                sum(packets_in + packets_out) as value
-            FROM network_metrics
-            WHERE timestamp >= ? AND timestamp <= ?
+            SELECT 'total_packets' as metric_name, sum(packets_in + packets_out) as value FROM ({source})
             UNION ALL
-            SELECT 
-                'total_errors' as metric_name,
-                sum(errors_in + errors_out) as value
-            FROM network_metrics
-            WHERE timestamp >= ? AND timestamp <= ?
+            SELECT 'total_errors' as metric_name, sum(errors_in + errors_out) as value FROM ({source})
             UNION ALL
-            SELECT 
-                'total_drops' as metric_name,
-                sum(drops_in + drops_out) as value
-            FROM network_metrics
-            WHERE timestamp >= ? AND timestamp <= ?
-        "#;
-        
+            SELECT 'total_drops' as metric_name, sum(drops_in + drops_out) as value FROM ({source})
+            "#,
+            source = source
+        );
+
         let params = vec![start_time, end_time, start_time, end_time, start_time, end_time, start_time, end_time];
-        let mut cursor = session.query(query, &params).await?;
+        let mut cursor = session.query(&query, &params).await.map_err(AnalyticsError::Query)?;
         let mut results = HashMap::new();
-        
-        while let Some(row) = cursor.next().await? {
-            let metric_name: String = row.get("metric_name")?;
-            let value: f64 = row.get("value")?;
+
+        while let Some(row) = cursor.next().await.map_err(AnalyticsError::Query)? {
+            let metric_name: String = row.get("metric_name").map_err(AnalyticsError::Query)?;
+            let value: f64 = row.get("value").map_err(AnalyticsError::Query)?;
             results.insert(metric_name, value);
         }
-        
+
         Ok(results)
     }
 }
 
+/// Rolling rate/quantile snapshot for one key (an interface or a protocol
+/// name), as reported by [`AnalyticsEngine::get_live_stats`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LiveStats {
+    pub avg_rate_bytes_per_sec: f64,
+    pub peak_rate_bytes_per_sec: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+}
+
+/// How many (timestamp, bytes) samples [`RateWindow`] retains per key.
+const RATE_WINDOW_CAPACITY: usize = 64;
+
+/// Bounded ring buffer of recent throughput samples for one key. Average and
+/// peak bytes-per-second are derived by differencing timestamps rather than
+/// tracked incrementally, so the window can't drift out of sync with itself.
+#[derive(Debug, Default)]
+struct RateWindow {
+    samples: VecDeque<(Instant, u64)>,
+}
+
+impl RateWindow {
+    fn record(&mut self, bytes: u64) {
+        if self.samples.len() == RATE_WINDOW_CAPACITY {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((Instant::now(), bytes));
+    }
+
+    /// `(average, peak)` bytes-per-second over the retained window: average
+    /// comes from the total bytes over the oldest-to-newest span, peak from
+    /// the fastest single inter-sample gap.
+    fn rates(&mut self) -> (f64, f64) {
+        if self.samples.len() < 2 {
+            return (0.0, 0.0);
+        }
+
+        let oldest = self.samples.front().unwrap().0;
+        let newest = self.samples.back().unwrap().0;
+        let span = newest.duration_since(oldest).as_secs_f64().max(f64::EPSILON);
+        let total_bytes: u64 = self.samples.iter().skip(1).map(|(_, bytes)| *bytes).sum();
+        let avg_rate = total_bytes as f64 / span;
+
+        let mut peak_rate = 0.0f64;
+        for pair in self.samples.make_contiguous().windows(2) {
+            let (t0, _) = pair[0];
+            let (t1, bytes) = pair[1];
+            let gap = t1.duration_since(t0).as_secs_f64().max(f64::EPSILON);
+            peak_rate = peak_rate.max(bytes as f64 / gap);
+        }
+
+        (avg_rate, peak_rate)
+    }
+}
+
+/// P² (piecewise-parabolic) quantile estimator: tracks a single quantile in
+/// O(1) memory via five markers, after Jain & Chlamtac (1985). Avoids storing
+/// the full sample history a plain sorted reservoir would need.
+#[derive(Debug, Clone)]
+struct P2Quantile {
+    p: f64,
+    /// Raw samples held only until the fifth arrives and the markers can be initialized.
+    initial: Vec<f64>,
+    /// Marker positions (counts).
+    n: [f64; 5],
+    /// Desired (fractional) marker positions.
+    np: [f64; 5],
+    /// Per-sample increment to each desired position.
+    dn: [f64; 5],
+    /// Marker heights: `q[2]` is the running estimate of the `p`-quantile.
+    q: [f64; 5],
+}
+
+impl P2Quantile {
+    fn new(p: f64) -> Self {
+        Self {
+            p,
+            initial: Vec::with_capacity(5),
+            n: [0.0; 5],
+            np: [0.0; 5],
+            dn: [0.0, p / 2.0, p, (1.0 + p) / 2.0, 1.0],
+            q: [0.0; 5],
+        }
+    }
+
+    fn observe(&mut self, x: f64) {
+        if self.initial.len() < 5 {
+            self.initial.push(x);
+            if self.initial.len() == 5 {
+                self.initial.sort_by(|a, b| a.partial_cmp(b).unwrap());
+                for i in 0..5 {
+                    self.q[i] = self.initial[i];
+                    self.n[i] = (i + 1) as f64;
+                }
+                self.np = [1.0, 1.0 + 2.0 * self.p, 1.0 + 4.0 * self.p, 3.0 + 2.0 * self.p, 5.0];
+            }
+            return;
+        }
+
+        let k = if x < self.q[0] {
+            self.q[0] = x;
+            0
+        } else if x >= self.q[4] {
+            self.q[4] = x;
+            3
+        } else {
+            (0..4).find(|&i| self.q[i] <= x && x < self.q[i + 1]).unwrap_or(3)
+        };
+
+        for n in self.n.iter_mut().skip(k + 1) {
+            *n += 1.0;
+        }
+        for i in 0..5 {
+            self.np[i] += self.dn[i];
+        }
+
+        for i in 1..4 {
+            let d = self.np[i] - self.n[i];
+            if (d >= 1.0 && self.n[i + 1] - self.n[i] > 1.0) || (d <= -1.0 && self.n[i - 1] - self.n[i] < -1.0) {
+                let d = if d >= 0.0 { 1.0 } else { -1.0 };
+                let parabolic = self.q[i]
+                    + d / (self.n[i + 1] - self.n[i - 1])
+                        * ((self.n[i] - self.n[i - 1] + d) * (self.q[i + 1] - self.q[i]) / (self.n[i + 1] - self.n[i])
+                            + (self.n[i + 1] - self.n[i] - d) * (self.q[i] - self.q[i - 1]) / (self.n[i] - self.n[i - 1]));
+
+                self.q[i] = if self.q[i - 1] < parabolic && parabolic < self.q[i + 1] {
+                    parabolic
+                } else {
+                    let j = (i as isize + d as isize) as usize;
+                    self.q[i] + d * (self.q[j] - self.q[i]) / (self.n[j] - self.n[i])
+                };
+                self.n[i] += d;
+            }
+        }
+    }
+
+    /// Current estimate of the `p`-quantile; an exact order statistic of
+    /// whatever's been observed so far until the fifth sample initializes the markers.
+    fn value(&self) -> f64 {
+        if self.initial.len() < 5 {
+            if self.initial.is_empty() {
+                return 0.0;
+            }
+            let mut sorted = self.initial.clone();
+            sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let idx = (((sorted.len() - 1) as f64) * self.p).round() as usize;
+            return sorted[idx];
+        }
+        self.q[2]
+    }
+}
+
+#[derive(Debug, Default)]
+struct KeyStats {
+    rate_window: RateWindow,
+    latency_p50: Option<P2Quantile>,
+    latency_p90: Option<P2Quantile>,
+    latency_p99: Option<P2Quantile>,
+}
+
+impl KeyStats {
+    fn observe_latency(&mut self, value_ms: f64) {
+        self.latency_p50.get_or_insert_with(|| P2Quantile::new(0.5)).observe(value_ms);
+        self.latency_p90.get_or_insert_with(|| P2Quantile::new(0.9)).observe(value_ms);
+        self.latency_p99.get_or_insert_with(|| P2Quantile::new(0.99)).observe(value_ms);
+    }
+}
+
+/// Live, in-memory rolling throughput and latency-percentile stats, updated
+/// from every `record_*` call so dashboards can show current rates before
+/// anything reaches ClickHouse. Keyed by whatever dimension the metric
+/// naturally carries (interface for network/impairment metrics, protocol for
+/// routing metrics) — see [`AnalyticsEngine::get_live_stats`].
+#[derive(Debug, Default)]
+struct StatsAccounting {
+    by_key: RwLock<HashMap<String, KeyStats>>,
+}
+
+impl StatsAccounting {
+    async fn record_throughput(&self, key: &str, bytes: u64) {
+        let mut by_key = self.by_key.write().await;
+        by_key.entry(key.to_string()).or_default().rate_window.record(bytes);
+    }
+
+    async fn record_latency(&self, key: &str, value_ms: f64) {
+        let mut by_key = self.by_key.write().await;
+        by_key.entry(key.to_string()).or_default().observe_latency(value_ms);
+    }
+
+    async fn get(&self, key: &str) -> LiveStats {
+        let mut by_key = self.by_key.write().await;
+        let Some(stats) = by_key.get_mut(key) else {
+            return LiveStats::default();
+        };
+        let (avg_rate_bytes_per_sec, peak_rate_bytes_per_sec) = stats.rate_window.rates();
+        LiveStats {
+            avg_rate_bytes_per_sec,
+            peak_rate_bytes_per_sec,
+            p50_ms: stats.latency_p50.as_ref().map_or(0.0, P2Quantile::value),
+            p90_ms: stats.latency_p90.as_ref().map_or(0.0, P2Quantile::value),
+            p99_ms: stats.latency_p99.as_ref().map_or(0.0, P2Quantile::value),
+        }
+    }
+}
+
 pub struct AnalyticsEngine {
     clickhouse: Arc<ClickHouseClient>,
     metrics_buffer: Arc<RwLock<Vec<NetworkMetrics>>>,
@@ -498,180 +1326,587 @@ pub struct AnalyticsEngine {
     traffic_shaping_buffer: Arc<RwLock<Vec<TrafficShapingMetrics>>>,
     impairment_buffer: Arc<RwLock<Vec<ImpairmentMetrics>>>,
     cloud_resource_buffer: Arc<RwLock<Vec<CloudResourceMetrics>>>,
+    stats: StatsAccounting,
+    config: AnalyticsEngineConfig,
+    retry_queue: Arc<RwLock<VecDeque<RetryEntry>>>,
+    batches_retried_total: Arc<AtomicU64>,
+    batches_dropped_total: Arc<AtomicU64>,
+    shutdown: Arc<Notify>,
+    flusher_task: std::sync::Mutex<Option<tokio::task::JoinHandle<()>>>,
+    retry_task: std::sync::Mutex<Option<tokio::task::JoinHandle<()>>>,
+}
+
+/// Tunables for `AnalyticsEngine`'s in-memory buffering, so neither the
+/// eager per-record flush nor the background flush cadence is baked in.
+#[derive(Debug, Clone)]
+pub struct AnalyticsEngineConfig {
+    /// Row count at which a buffer flushes eagerly, independent of `flush_interval`.
+    pub buffer_flush_threshold: usize,
+    /// How often the background flusher drains every buffer, regardless of size.
+    pub flush_interval: Duration,
+    /// Tunables for the failed-insert retry queue.
+    pub retry: RetryConfig,
+}
+
+impl Default for AnalyticsEngineConfig {
+    fn default() -> Self {
+        Self {
+            // The network-metrics insert path now builds one columnar block
+            // per batch (see `ClickHouseClient::network_metrics_columns`), so
+            // a larger batch is cheaper per row than before; raise the
+            // default accordingly.
+            buffer_flush_threshold: 5000,
+            flush_interval: Duration::from_secs(30),
+            retry: RetryConfig::default(),
+        }
+    }
+}
+
+/// Tunables for the queue that holds insert batches that failed to flush.
+#[derive(Debug, Clone)]
+pub struct RetryConfig {
+    /// Delay before the first retry attempt.
+    pub initial_backoff: Duration,
+    /// Backoff doubles on every failed attempt, up to this cap.
+    pub max_backoff: Duration,
+    /// Once the queue holds this many batches, the oldest is dropped (and
+    /// counted in `batches_dropped_total`) to make room for the newest.
+    pub max_queue_size: usize,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(30),
+            max_queue_size: 100,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Exponential backoff for `attempt` (1 = first retry), doubling from
+    /// `initial_backoff` up to `max_backoff`, with up to 20% jitter so a burst
+    /// of failures doesn't retry in lockstep.
+    fn backoff_for(&self, attempt: u32) -> Duration {
+        let exponential = self.initial_backoff.saturating_mul(1u32 << attempt.min(16));
+        let base = exponential.min(self.max_backoff);
+
+        let seed = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().subsec_nanos() as u64;
+        let jitter_cap_ms = (base.as_millis() as u64 / 5).max(1);
+        base + Duration::from_millis(seed % jitter_cap_ms)
+    }
+}
+
+/// Snapshot of retry-queue activity, for callers who want to observe the
+/// data-loss risk from a struggling ClickHouse connection.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RetryStats {
+    pub batches_retried: u64,
+    pub batches_dropped: u64,
+}
+
+/// One insert batch that failed to flush and is awaiting retry, tagged by
+/// which table it belongs to.
+#[derive(Debug, Clone)]
+enum PendingBatch {
+    Network(Vec<NetworkMetrics>),
+    Routing(Vec<RoutingMetrics>),
+    TrafficShaping(Vec<TrafficShapingMetrics>),
+    Impairment(Vec<ImpairmentMetrics>),
+    CloudResource(Vec<CloudResourceMetrics>),
+}
+
+impl PendingBatch {
+    async fn insert(&self, clickhouse: &ClickHouseClient) -> Result<(), AnalyticsError> {
+        match self {
+            PendingBatch::Network(rows) => clickhouse.insert_network_metrics(rows).await,
+            PendingBatch::Routing(rows) => clickhouse.insert_routing_metrics(rows).await,
+            PendingBatch::TrafficShaping(rows) => clickhouse.insert_traffic_shaping_metrics(rows).await,
+            PendingBatch::Impairment(rows) => clickhouse.insert_impairment_metrics(rows).await,
+            PendingBatch::CloudResource(rows) => clickhouse.insert_cloud_resource_metrics(rows).await,
+        }
+    }
+}
+
+struct RetryEntry {
+    batch: PendingBatch,
+    attempt: u32,
+    next_attempt_at: Instant,
 }
 
 impl AnalyticsEngine {
-    pub async fn new(config: ClickHouseConfig) -> Result<Self, Box<dyn std::error::Error>> {
+    pub async fn new(config: ClickHouseConfig, engine_config: AnalyticsEngineConfig) -> Result<Self, AnalyticsError> {
         let clickhouse = Arc::new(ClickHouseClient::new(config).await?);
-        
+        let metrics_buffer = Arc::new(RwLock::new(Vec::new()));
+        let routing_buffer = Arc::new(RwLock::new(Vec::new()));
+        let traffic_shaping_buffer = Arc::new(RwLock::new(Vec::new()));
+        let impairment_buffer = Arc::new(RwLock::new(Vec::new()));
+        let cloud_resource_buffer = Arc::new(RwLock::new(Vec::new()));
+        let retry_queue: Arc<RwLock<VecDeque<RetryEntry>>> = Arc::new(RwLock::new(VecDeque::new()));
+        let batches_retried_total = Arc::new(AtomicU64::new(0));
+        let batches_dropped_total = Arc::new(AtomicU64::new(0));
+        let shutdown = Arc::new(Notify::new());
+
+        // Spawn the background tasks before we have a `Self` to hold their
+        // handles: they only need clones of the shared buffers/client, not the
+        // engine itself, so there's no need to wrap `AnalyticsEngine` in an Arc.
+        let flusher_task = {
+            let clickhouse = Arc::clone(&clickhouse);
+            let metrics_buffer = Arc::clone(&metrics_buffer);
+            let routing_buffer = Arc::clone(&routing_buffer);
+            let traffic_shaping_buffer = Arc::clone(&traffic_shaping_buffer);
+            let impairment_buffer = Arc::clone(&impairment_buffer);
+            let cloud_resource_buffer = Arc::clone(&cloud_resource_buffer);
+            let retry_queue = Arc::clone(&retry_queue);
+            let batches_dropped_total = Arc::clone(&batches_dropped_total);
+            let retry_config = engine_config.retry.clone();
+            let shutdown = Arc::clone(&shutdown);
+            let interval = engine_config.flush_interval;
+
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(interval);
+                // Created once, outside the loop, so a shutdown signalled while
+                // a flush is in progress is still seen on the next iteration.
+                let stop = shutdown.notified();
+                tokio::pin!(stop);
+                loop {
+                    tokio::select! {
+                        _ = ticker.tick() => {
+                            Self::flush_buffers(
+                                &clickhouse,
+                                &metrics_buffer,
+                                &routing_buffer,
+                                &traffic_shaping_buffer,
+                                &impairment_buffer,
+                                &cloud_resource_buffer,
+                                &retry_queue,
+                                &retry_config,
+                                &batches_dropped_total,
+                            ).await;
+                        }
+                        _ = &mut stop => break,
+                    }
+                }
+            })
+        };
+
+        let retry_task = {
+            let clickhouse = Arc::clone(&clickhouse);
+            let retry_queue = Arc::clone(&retry_queue);
+            let batches_retried_total = Arc::clone(&batches_retried_total);
+            let retry_config = engine_config.retry.clone();
+            let shutdown = Arc::clone(&shutdown);
+
+            tokio::spawn(async move {
+                let mut ticker = tokio::time::interval(retry_config.initial_backoff.max(Duration::from_millis(10)));
+                let stop = shutdown.notified();
+                tokio::pin!(stop);
+                loop {
+                    tokio::select! {
+                        _ = ticker.tick() => {
+                            Self::drain_retry_queue(&clickhouse, &retry_queue, &retry_config, &batches_retried_total).await;
+                        }
+                        _ = &mut stop => break,
+                    }
+                }
+            })
+        };
+
         Ok(AnalyticsEngine {
             clickhouse,
-            metrics_buffer: Arc::new(RwLock::new(Vec::new())),
-            routing_buffer: Arc::new(RwLock::new(Vec::new())),
-            traffic_shaping_buffer: Arc::new(RwLock::new(Vec::new())),
-            impairment_buffer: Arc::new(RwLock::new(Vec::new())),
-            cloud_resource_buffer: Arc::new(RwLock::new(Vec::new())),
+            metrics_buffer,
+            routing_buffer,
+            traffic_shaping_buffer,
+            impairment_buffer,
+            cloud_resource_buffer,
+            stats: StatsAccounting::default(),
+            config: engine_config,
+            retry_queue,
+            batches_retried_total,
+            batches_dropped_total,
+            shutdown,
+            flusher_task: std::sync::Mutex::new(Some(flusher_task)),
+            retry_task: std::sync::Mutex::new(Some(retry_task)),
         })
     }
-    
-    pub async fn record_network_metrics(&self, metrics: NetworkMetrics) -> Result<(), Box<dyn std::error::Error>> {
+
+    pub async fn record_network_metrics(&self, metrics: NetworkMetrics) -> Result<(), AnalyticsError> {
+        self.stats.record_throughput(&metrics.interface, metrics.bytes_in + metrics.bytes_out).await;
+
         let mut buffer = self.metrics_buffer.write().await;
         buffer.push(metrics);
-        
+
         // Flush buffer if it's getting large
-        if buffer.len() >= 1000 {
+        if buffer.len() >= self.config.buffer_flush_threshold {
             let metrics_to_flush = buffer.clone();
             buffer.clear();
             drop(buffer); // Release the lock before async operation
-            
-            self.clickhouse.insert_network_metrics(&metrics_to_flush).await?;
+
+            Self::insert_or_queue(
+                &self.clickhouse,
+                &self.retry_queue,
+                &self.config.retry,
+                &self.batches_dropped_total,
+                PendingBatch::Network(metrics_to_flush),
+            ).await;
         }
-        
+
         Ok(())
     }
-    
-    pub async fn record_routing_metrics(&self, metrics: RoutingMetrics) -> Result<(), Box<dyn std::error::Error>> {
+
+    pub async fn record_routing_metrics(&self, metrics: RoutingMetrics) -> Result<(), AnalyticsError> {
+        self.stats.record_latency(&metrics.protocol.to_string(), metrics.convergence_time_ms as f64).await;
+
         let mut buffer = self.routing_buffer.write().await;
         buffer.push(metrics);
-        
-        if buffer.len() >= 1000 {
+
+        if buffer.len() >= self.config.buffer_flush_threshold {
             let metrics_to_flush = buffer.clone();
             buffer.clear();
             drop(buffer);
-            
-            self.clickhouse.insert_routing_metrics(&metrics_to_flush).await?;
+
+            Self::insert_or_queue(
+                &self.clickhouse,
+                &self.retry_queue,
+                &self.config.retry,
+                &self.batches_dropped_total,
+                PendingBatch::Routing(metrics_to_flush),
+            ).await;
         }
-        
+
         Ok(())
     }
-    
-    pub async fn record_traffic_shaping_metrics(&self, metrics: TrafficShapingMetrics) -> Result<(), Box<dyn std::error::Error>> {
+
+    pub async fn record_traffic_shaping_metrics(&self, metrics: TrafficShapingMetrics) -> Result<(), AnalyticsError> {
         let mut buffer = self.traffic_shaping_buffer.write().await;
         buffer.push(metrics);
-        
-        if buffer.len() >= 1000 {
+
+        if buffer.len() >= self.config.buffer_flush_threshold {
             let metrics_to_flush = buffer.clone();
             buffer.clear();
             drop(buffer);
-            
-            self.clickhouse.insert_traffic_shaping_metrics(&metrics_to_flush).await?;
+
+            Self::insert_or_queue(
+                &self.clickhouse,
+                &self.retry_queue,
+                &self.config.retry,
+                &self.batches_dropped_total,
+                PendingBatch::TrafficShaping(metrics_to_flush),
+            ).await;
         }
-        
+
         Ok(())
     }
-    
-    pub async fn record_impairment_metrics(&self, metrics: ImpairmentMetrics) -> Result<(), Box<dyn std::error::Error>> {
+
+    pub async fn record_impairment_metrics(&self, metrics: ImpairmentMetrics) -> Result<(), AnalyticsError> {
+        self.stats.record_latency(&metrics.interface, metrics.delay_ms).await;
+        self.stats.record_latency(&metrics.interface, metrics.jitter_ms).await;
+
         let mut buffer = self.impairment_buffer.write().await;
         buffer.push(metrics);
-        
-        if buffer.len() >= 1000 {
+
+        if buffer.len() >= self.config.buffer_flush_threshold {
             let metrics_to_flush = buffer.clone();
             buffer.clear();
             drop(buffer);
-            
-            self.clickhouse.insert_impairment_metrics(&metrics_to_flush).await?;
+
+            Self::insert_or_queue(
+                &self.clickhouse,
+                &self.retry_queue,
+                &self.config.retry,
+                &self.batches_dropped_total,
+                PendingBatch::Impairment(metrics_to_flush),
+            ).await;
         }
-        
+
         Ok(())
     }
-    
-    pub async fn record_cloud_resource_metrics(&self, metrics: CloudResourceMetrics) -> Result<(), Box<dyn std::error::Error>> {
+
+    pub async fn record_cloud_resource_metrics(&self, metrics: CloudResourceMetrics) -> Result<(), AnalyticsError> {
         let mut buffer = self.cloud_resource_buffer.write().await;
         buffer.push(metrics);
-        
-        if buffer.len() >= 1000 {
+
+        if buffer.len() >= self.config.buffer_flush_threshold {
             let metrics_to_flush = buffer.clone();
             buffer.clear();
             drop(buffer);
-            
-            self.clickhouse.insert_cloud_resource_metrics(&metrics_to_flush).await?;
+
+            Self::insert_or_queue(
+                &self.clickhouse,
+                &self.retry_queue,
+                &self.config.retry,
+                &self.batches_dropped_total,
+                PendingBatch::CloudResource(metrics_to_flush),
+            ).await;
         }
-        
+
         Ok(())
     }
-    
-    pub async fn flush_all_buffers(&self) -> Result<(), Box<dyn std::error::Error>> {
+
+    pub async fn flush_all_buffers(&self) -> Result<(), AnalyticsError> {
+        Self::flush_buffers(
+            &self.clickhouse,
+            &self.metrics_buffer,
+            &self.routing_buffer,
+            &self.traffic_shaping_buffer,
+            &self.impairment_buffer,
+            &self.cloud_resource_buffer,
+            &self.retry_queue,
+            &self.config.retry,
+            &self.batches_dropped_total,
+        ).await;
+        Ok(())
+    }
+
+    /// Drain every non-empty buffer into `clickhouse`. Takes plain references
+    /// so it can run both from `&self` and from the background flusher task,
+    /// which only holds clones of the underlying `Arc`s. A batch that fails to
+    /// insert is moved onto the retry queue instead of being dropped.
+    #[allow(clippy::too_many_arguments)]
+    async fn flush_buffers(
+        clickhouse: &ClickHouseClient,
+        metrics_buffer: &RwLock<Vec<NetworkMetrics>>,
+        routing_buffer: &RwLock<Vec<RoutingMetrics>>,
+        traffic_shaping_buffer: &RwLock<Vec<TrafficShapingMetrics>>,
+        impairment_buffer: &RwLock<Vec<ImpairmentMetrics>>,
+        cloud_resource_buffer: &RwLock<Vec<CloudResourceMetrics>>,
+        retry_queue: &RwLock<VecDeque<RetryEntry>>,
+        retry_config: &RetryConfig,
+        batches_dropped_total: &AtomicU64,
+    ) {
         // Flush network metrics
         {
-            let mut buffer = self.metrics_buffer.write().await;
+            let mut buffer = metrics_buffer.write().await;
             if !buffer.is_empty() {
                 let metrics_to_flush = buffer.clone();
                 buffer.clear();
                 drop(buffer);
-                self.clickhouse.insert_network_metrics(&metrics_to_flush).await?;
+                Self::insert_or_queue(clickhouse, retry_queue, retry_config, batches_dropped_total, PendingBatch::Network(metrics_to_flush)).await;
             }
         }
-        
+
         // Flush routing metrics
         {
-            let mut buffer = self.routing_buffer.write().await;
+            let mut buffer = routing_buffer.write().await;
             if !buffer.is_empty() {
                 let metrics_to_flush = buffer.clone();
                 buffer.clear();
                 drop(buffer);
-                self.clickhouse.insert_routing_metrics(&metrics_to_flush).await?;
+                Self::insert_or_queue(clickhouse, retry_queue, retry_config, batches_dropped_total, PendingBatch::Routing(metrics_to_flush)).await;
             }
         }
-        
+
         // Flush traffic shaping metrics
         {
-            let mut buffer = self.traffic_shaping_buffer.write().await;
+            let mut buffer = traffic_shaping_buffer.write().await;
             if !buffer.is_empty() {
                 let metrics_to_flush = buffer.clone();
                 buffer.clear();
                 drop(buffer);
-                self.clickhouse.insert_traffic_shaping_metrics(&metrics_to_flush).await?;
+                Self::insert_or_queue(clickhouse, retry_queue, retry_config, batches_dropped_total, PendingBatch::TrafficShaping(metrics_to_flush)).await;
             }
         }
-        
+
         // Flush impairment metrics
         {
-            let mut buffer = self.impairment_buffer.write().await;
+            let mut buffer = impairment_buffer.write().await;
             if !buffer.is_empty() {
                 let metrics_to_flush = buffer.clone();
                 buffer.clear();
                 drop(buffer);
-                self.clickhouse.insert_impairment_metrics(&metrics_to_flush).await?;
+                Self::insert_or_queue(clickhouse, retry_queue, retry_config, batches_dropped_total, PendingBatch::Impairment(metrics_to_flush)).await;
             }
         }
-        
+
         // Flush cloud resource metrics
         {
-            let mut buffer = self.cloud_resource_buffer.write().await;
+            let mut buffer = cloud_resource_buffer.write().await;
             if !buffer.is_empty() {
                 let metrics_to_flush = buffer.clone();
                 buffer.clear();
                 drop(buffer);
-                self.clickhouse.insert_cloud_resource_metrics(&metrics_to_flush).await?;
+                Self::insert_or_queue(clickhouse, retry_queue, retry_config, batches_dropped_total, PendingBatch::CloudResource(metrics_to_flush)).await;
             }
         }
-        
-        Ok(())
     }
-    
+
+    /// Attempt one insert; on failure, queue the batch for retry instead of
+    /// dropping it.
+    async fn insert_or_queue(
+        clickhouse: &ClickHouseClient,
+        retry_queue: &RwLock<VecDeque<RetryEntry>>,
+        retry_config: &RetryConfig,
+        batches_dropped_total: &AtomicU64,
+        batch: PendingBatch,
+    ) {
+        if let Err(err) = batch.insert(clickhouse).await {
+            tracing::warn!("analytics insert failed, queued for retry: {}", err);
+            let mut queue = retry_queue.write().await;
+            if queue.len() >= retry_config.max_queue_size {
+                queue.pop_front();
+                batches_dropped_total.fetch_add(1, Ordering::Relaxed);
+            }
+            queue.push_back(RetryEntry {
+                batch,
+                attempt: 0,
+                next_attempt_at: Instant::now() + retry_config.initial_backoff,
+            });
+        }
+    }
+
+    /// Retry every queued batch whose backoff has elapsed. Successes are
+    /// dropped from the queue; failures are re-enqueued with a doubled
+    /// backoff (see [`RetryConfig::backoff_for`]).
+    async fn drain_retry_queue(
+        clickhouse: &ClickHouseClient,
+        retry_queue: &RwLock<VecDeque<RetryEntry>>,
+        retry_config: &RetryConfig,
+        batches_retried_total: &AtomicU64,
+    ) {
+        let due = {
+            let mut queue = retry_queue.write().await;
+            let now = Instant::now();
+            let (due, remaining): (VecDeque<_>, VecDeque<_>) =
+                queue.drain(..).partition(|entry| entry.next_attempt_at <= now);
+            *queue = remaining;
+            due
+        };
+
+        for mut entry in due {
+            batches_retried_total.fetch_add(1, Ordering::Relaxed);
+            if let Err(err) = entry.batch.insert(clickhouse).await {
+                entry.attempt += 1;
+                tracing::warn!("analytics retry attempt {} failed: {}", entry.attempt, err);
+                entry.next_attempt_at = Instant::now() + retry_config.backoff_for(entry.attempt);
+                retry_queue.write().await.push_back(entry);
+            }
+        }
+    }
+
+    /// Live rolling throughput/latency stats for `key` (an interface name for
+    /// network/impairment metrics, a protocol name for routing metrics),
+    /// computed entirely in memory so it reflects data not yet flushed to
+    /// ClickHouse. Returns a zeroed snapshot if `key` hasn't been observed yet.
+    pub async fn get_live_stats(&self, key: &str) -> LiveStats {
+        self.stats.get(key).await
+    }
+
+    /// Current retry-queue activity: how many batches have been retried and
+    /// how many were dropped because the queue was full.
+    pub fn retry_stats(&self) -> RetryStats {
+        RetryStats {
+            batches_retried: self.batches_retried_total.load(Ordering::Relaxed),
+            batches_dropped: self.batches_dropped_total.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Stop the background flusher and retry tasks and flush every buffer one
+    /// last time, deterministically. A batch whose final insert fails is left
+    /// on the retry queue, which is no longer drained once this returns.
+    pub async fn shutdown(&self) -> Result<(), AnalyticsError> {
+        self.shutdown.notify_waiters();
+        if let Some(task) = self.flusher_task.lock().unwrap().take() {
+            let _ = task.await;
+        }
+        if let Some(task) = self.retry_task.lock().unwrap().take() {
+            let _ = task.await;
+        }
+        self.flush_all_buffers().await
+    }
+
     pub async fn get_network_metrics(
         &self,
         start_time: DateTime<Utc>,
         end_time: DateTime<Utc>,
         interface: Option<&str>,
-    ) -> Result<Vec<NetworkMetrics>, Box<dyn std::error::Error>> {
-        self.clickhouse.query_network_metrics(start_time, end_time, interface).await
+        resolution: Option<MetricResolution>,
+    ) -> Result<Vec<NetworkMetrics>, AnalyticsError> {
+        self.clickhouse.query_network_metrics(start_time, end_time, interface, resolution).await
     }
-    
+
     pub async fn get_routing_metrics(
         &self,
         start_time: DateTime<Utc>,
         end_time: DateTime<Utc>,
         protocol: Option<&str>,
-    ) -> Result<Vec<RoutingMetrics>, Box<dyn std::error::Error>> {
-        self.clickhouse.query_routing_metrics(start_time, end_time, protocol).await
+        resolution: Option<MetricResolution>,
+    ) -> Result<Vec<RoutingMetrics>, AnalyticsError> {
+        self.clickhouse.query_routing_metrics(start_time, end_time, protocol, resolution).await
     }
-    
+
     pub async fn get_aggregated_metrics(
         &self,
         start_time: DateTime<Utc>,
         end_time: DateTime<Utc>,
-    ) -> Result<HashMap<String, f64>, Box<dyn std::error::Error>> {
-        self.clickhouse.get_aggregated_metrics(start_time, end_time).await
+        resolution: Option<MetricResolution>,
+    ) -> Result<HashMap<String, f64>, AnalyticsError> {
+        self.clickhouse.get_aggregated_metrics(start_time, end_time, resolution).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn network_metrics_columns_transposes_a_10k_row_batch_without_awaits() {
+        let metrics: Vec<NetworkMetrics> = (0..10_000u64)
+            .map(|i| NetworkMetrics {
+                timestamp: Utc::now(),
+                interface: format!("eth{}", i % 4),
+                bytes_in: i,
+                bytes_out: i * 2,
+                packets_in: i,
+                packets_out: i,
+                errors_in: 0,
+                errors_out: 0,
+                drops_in: 0,
+                drops_out: 0,
+            })
+            .collect();
+
+        // `network_metrics_columns` is a plain synchronous function: building
+        // all ten columns for a 10k-row batch takes zero awaited calls, versus
+        // the row-at-a-time `insert.write(...).await` path it replaces.
+        let (timestamp, interface, bytes_in, bytes_out, packets_in, packets_out, errors_in, errors_out, drops_in, drops_out) =
+            ClickHouseClient::network_metrics_columns(&metrics);
+
+        assert_eq!(timestamp.len(), 10_000);
+        assert_eq!(interface.len(), 10_000);
+        assert_eq!(bytes_in.len(), 10_000);
+        assert_eq!(bytes_out.len(), 10_000);
+        assert_eq!(packets_in.len(), 10_000);
+        assert_eq!(packets_out.len(), 10_000);
+        assert_eq!(errors_in.len(), 10_000);
+        assert_eq!(errors_out.len(), 10_000);
+        assert_eq!(drops_in.len(), 10_000);
+        assert_eq!(drops_out.len(), 10_000);
+
+        assert_eq!(interface[5], "eth1");
+        assert_eq!(bytes_out[5], 10);
+    }
+
+    #[test]
+    fn metric_resolution_auto_selects_the_coarsest_covering_rollup() {
+        let end = Utc::now();
+
+        assert_eq!(MetricResolution::auto(end - chrono::Duration::minutes(5), end), MetricResolution::Raw);
+        assert_eq!(MetricResolution::auto(end - chrono::Duration::hours(12), end), MetricResolution::OneMinute);
+        // A query spanning more than the raw table's 30-day TTL must land on
+        // the hourly rollup rather than scanning (now-expired) raw rows.
+        assert_eq!(MetricResolution::auto(end - chrono::Duration::days(45), end), MetricResolution::OneHour);
+    }
+
+    #[test]
+    fn routing_protocol_round_trips_through_display_and_rejects_unknown_values() {
+        for protocol in [RoutingProtocol::Bgp, RoutingProtocol::Ospf, RoutingProtocol::Isis, RoutingProtocol::Static] {
+            assert_eq!(protocol.to_string().parse::<RoutingProtocol>().unwrap(), protocol);
+        }
+
+        assert!(matches!(
+            "eigrp".parse::<RoutingProtocol>(),
+            Err(AnalyticsError::Serialize { field: "protocol", .. })
+        ));
     }
 }
@@ -1,8 +1,13 @@
 use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{broadcast, Notify, RwLock};
 use serde::{Deserialize, Serialize};
+use async_trait::async_trait;
 use clickhouse_rs::{Pool, Block, types::Complex};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -99,25 +104,247 @@ pub struct NetemImpairment {
     pub active: bool,
 }
 
-pub struct ClickHouseAnalytics {
+/// A single column value in the backend-agnostic row representation used by
+/// `AnalyticsBackend::insert_block`.
+#[derive(Debug, Clone)]
+pub enum AnalyticsValue {
+    Text(String),
+    UInt(u64),
+    Float(f64),
+    Bool(bool),
+    TextArray(Vec<String>),
+    U32Array(Vec<u32>),
+    Tags(HashMap<String, String>),
+}
+
+impl NetworkMetric {
+    fn to_row(&self) -> HashMap<String, AnalyticsValue> {
+        HashMap::from([
+            ("timestamp".to_string(), AnalyticsValue::UInt(self.timestamp)),
+            ("router_id".to_string(), AnalyticsValue::Text(self.router_id.clone())),
+            ("interface".to_string(), AnalyticsValue::Text(self.interface.clone())),
+            ("metric_type".to_string(), AnalyticsValue::Text(self.metric_type.clone())),
+            ("value".to_string(), AnalyticsValue::Float(self.value)),
+            ("tags".to_string(), AnalyticsValue::Tags(self.tags.clone())),
+        ])
+    }
+}
+
+impl PacketFlow {
+    fn to_row(&self) -> HashMap<String, AnalyticsValue> {
+        HashMap::from([
+            ("timestamp".to_string(), AnalyticsValue::UInt(self.timestamp)),
+            ("src_ip".to_string(), AnalyticsValue::Text(self.src_ip.clone())),
+            ("dst_ip".to_string(), AnalyticsValue::Text(self.dst_ip.clone())),
+            ("src_port".to_string(), AnalyticsValue::UInt(self.src_port as u64)),
+            ("dst_port".to_string(), AnalyticsValue::UInt(self.dst_port as u64)),
+            ("protocol".to_string(), AnalyticsValue::UInt(self.protocol as u64)),
+            ("bytes".to_string(), AnalyticsValue::UInt(self.bytes)),
+            ("packets".to_string(), AnalyticsValue::UInt(self.packets)),
+            ("duration_ms".to_string(), AnalyticsValue::UInt(self.duration_ms)),
+            ("router_id".to_string(), AnalyticsValue::Text(self.router_id.clone())),
+        ])
+    }
+}
+
+impl BGPUpdate {
+    fn to_row(&self) -> HashMap<String, AnalyticsValue> {
+        HashMap::from([
+            ("timestamp".to_string(), AnalyticsValue::UInt(self.timestamp)),
+            ("router_id".to_string(), AnalyticsValue::Text(self.router_id.clone())),
+            ("neighbor_ip".to_string(), AnalyticsValue::Text(self.neighbor_ip.clone())),
+            ("prefix".to_string(), AnalyticsValue::Text(self.prefix.clone())),
+            ("prefix_length".to_string(), AnalyticsValue::UInt(self.prefix_length as u64)),
+            ("as_path".to_string(), AnalyticsValue::U32Array(self.as_path.clone())),
+            ("next_hop".to_string(), AnalyticsValue::Text(self.next_hop.clone())),
+            ("origin".to_string(), AnalyticsValue::Text(self.origin.clone())),
+            ("local_pref".to_string(), AnalyticsValue::UInt(self.local_pref as u64)),
+            ("med".to_string(), AnalyticsValue::UInt(self.med as u64)),
+            ("communities".to_string(), AnalyticsValue::TextArray(self.communities.clone())),
+            ("action".to_string(), AnalyticsValue::Text(self.action.clone())),
+        ])
+    }
+}
+
+impl OSPFUpdate {
+    fn to_row(&self) -> HashMap<String, AnalyticsValue> {
+        HashMap::from([
+            ("timestamp".to_string(), AnalyticsValue::UInt(self.timestamp)),
+            ("router_id".to_string(), AnalyticsValue::Text(self.router_id.clone())),
+            ("area_id".to_string(), AnalyticsValue::Text(self.area_id.clone())),
+            ("lsa_type".to_string(), AnalyticsValue::UInt(self.lsa_type as u64)),
+            ("lsa_id".to_string(), AnalyticsValue::Text(self.lsa_id.clone())),
+            ("advertising_router".to_string(), AnalyticsValue::Text(self.advertising_router.clone())),
+            ("sequence_number".to_string(), AnalyticsValue::UInt(self.sequence_number as u64)),
+            ("age".to_string(), AnalyticsValue::UInt(self.age as u64)),
+            ("checksum".to_string(), AnalyticsValue::UInt(self.checksum as u64)),
+            ("length".to_string(), AnalyticsValue::UInt(self.length as u64)),
+            ("action".to_string(), AnalyticsValue::Text(self.action.clone())),
+        ])
+    }
+}
+
+impl ISISUpdate {
+    fn to_row(&self) -> HashMap<String, AnalyticsValue> {
+        HashMap::from([
+            ("timestamp".to_string(), AnalyticsValue::UInt(self.timestamp)),
+            ("system_id".to_string(), AnalyticsValue::Text(self.system_id.clone())),
+            ("area_id".to_string(), AnalyticsValue::Text(self.area_id.clone())),
+            ("level".to_string(), AnalyticsValue::UInt(self.level as u64)),
+            ("lsp_id".to_string(), AnalyticsValue::Text(self.lsp_id.clone())),
+            ("sequence_number".to_string(), AnalyticsValue::UInt(self.sequence_number as u64)),
+            ("remaining_lifetime".to_string(), AnalyticsValue::UInt(self.remaining_lifetime as u64)),
+            ("checksum".to_string(), AnalyticsValue::UInt(self.checksum as u64)),
+            ("pdu_length".to_string(), AnalyticsValue::UInt(self.pdu_length as u64)),
+            ("action".to_string(), AnalyticsValue::Text(self.action.clone())),
+        ])
+    }
+}
+
+impl TrafficShapingMetric {
+    fn to_row(&self) -> HashMap<String, AnalyticsValue> {
+        HashMap::from([
+            ("timestamp".to_string(), AnalyticsValue::UInt(self.timestamp)),
+            ("router_id".to_string(), AnalyticsValue::Text(self.router_id.clone())),
+            ("interface".to_string(), AnalyticsValue::Text(self.interface.clone())),
+            ("algorithm".to_string(), AnalyticsValue::Text(self.algorithm.clone())),
+            ("class_id".to_string(), AnalyticsValue::UInt(self.class_id as u64)),
+            ("packets_processed".to_string(), AnalyticsValue::UInt(self.packets_processed)),
+            ("packets_dropped".to_string(), AnalyticsValue::UInt(self.packets_dropped)),
+            ("bytes_processed".to_string(), AnalyticsValue::UInt(self.bytes_processed)),
+            ("bytes_dropped".to_string(), AnalyticsValue::UInt(self.bytes_dropped)),
+            ("queue_length".to_string(), AnalyticsValue::UInt(self.queue_length as u64)),
+            ("throughput_bps".to_string(), AnalyticsValue::Float(self.throughput_bps)),
+        ])
+    }
+}
+
+impl NetemImpairment {
+    fn to_row(&self) -> HashMap<String, AnalyticsValue> {
+        HashMap::from([
+            ("timestamp".to_string(), AnalyticsValue::UInt(self.timestamp)),
+            ("router_id".to_string(), AnalyticsValue::Text(self.router_id.clone())),
+            ("interface".to_string(), AnalyticsValue::Text(self.interface.clone())),
+            ("impairment_type".to_string(), AnalyticsValue::Text(self.impairment_type.clone())),
+            ("parameters".to_string(), AnalyticsValue::Tags(self.parameters.clone())),
+            ("active".to_string(), AnalyticsValue::Bool(self.active)),
+        ])
+    }
+}
+
+/// Column order for each analytics table, shared by every backend so a row
+/// map converts to a positional insert the same way everywhere.
+fn table_columns(table: &str) -> &'static [&'static str] {
+    match table {
+        "network_metrics" => &["timestamp", "router_id", "interface", "metric_type", "value", "tags"],
+        "packet_flows" => &[
+            "timestamp", "src_ip", "dst_ip", "src_port", "dst_port", "protocol", "bytes", "packets",
+            "duration_ms", "router_id",
+        ],
+        "bgp_updates" => &[
+            "timestamp", "router_id", "neighbor_ip", "prefix", "prefix_length", "as_path", "next_hop",
+            "origin", "local_pref", "med", "communities", "action",
+        ],
+        "ospf_updates" => &[
+            "timestamp", "router_id", "area_id", "lsa_type", "lsa_id", "advertising_router",
+            "sequence_number", "age", "checksum", "length", "action",
+        ],
+        "isis_updates" => &[
+            "timestamp", "system_id", "area_id", "level", "lsp_id", "sequence_number",
+            "remaining_lifetime", "checksum", "pdu_length", "action",
+        ],
+        "traffic_shaping_metrics" => &[
+            "timestamp", "router_id", "interface", "algorithm", "class_id", "packets_processed",
+            "packets_dropped", "bytes_processed", "bytes_dropped", "queue_length", "throughput_bps",
+        ],
+        "netem_impairments" => &[
+            "timestamp", "router_id", "interface", "impairment_type", "parameters", "active",
+        ],
+        _ => &[],
+    }
+}
+
+fn row_text(row: &HashMap<String, AnalyticsValue>, column: &str) -> String {
+    match row.get(column) {
+        Some(AnalyticsValue::Text(v)) => v.clone(),
+        _ => String::new(),
+    }
+}
+
+fn row_uint(row: &HashMap<String, AnalyticsValue>, column: &str) -> u64 {
+    match row.get(column) {
+        Some(AnalyticsValue::UInt(v)) => *v,
+        _ => 0,
+    }
+}
+
+fn row_float(row: &HashMap<String, AnalyticsValue>, column: &str) -> f64 {
+    match row.get(column) {
+        Some(AnalyticsValue::Float(v)) => *v,
+        _ => 0.0,
+    }
+}
+
+fn row_bool(row: &HashMap<String, AnalyticsValue>, column: &str) -> bool {
+    matches!(row.get(column), Some(AnalyticsValue::Bool(true)))
+}
+
+fn row_text_array(row: &HashMap<String, AnalyticsValue>, column: &str) -> Vec<String> {
+    match row.get(column) {
+        Some(AnalyticsValue::TextArray(v)) => v.clone(),
+        _ => Vec::new(),
+    }
+}
+
+fn row_u32_array(row: &HashMap<String, AnalyticsValue>, column: &str) -> Vec<u32> {
+    match row.get(column) {
+        Some(AnalyticsValue::U32Array(v)) => v.clone(),
+        _ => Vec::new(),
+    }
+}
+
+fn row_tags(row: &HashMap<String, AnalyticsValue>, column: &str) -> HashMap<String, String> {
+    match row.get(column) {
+        Some(AnalyticsValue::Tags(v)) => v.clone(),
+        _ => HashMap::new(),
+    }
+}
+
+/// Storage interface for analytics ingestion/query, so the pipeline can run
+/// against anything that can hold rows and answer SQL -- not only a live
+/// ClickHouse server.
+#[async_trait]
+pub trait AnalyticsBackend: Send + Sync {
+    /// Create every analytics table if it doesn't already exist.
+    async fn create_schema(&self) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Insert a batch of rows into `table`. `rows` is empty-safe (a no-op).
+    async fn insert_block(
+        &self,
+        table: &str,
+        rows: Vec<HashMap<String, AnalyticsValue>>,
+    ) -> Result<(), Box<dyn std::error::Error>>;
+
+    /// Run a raw query and return rows as column-name -> stringified-value maps.
+    async fn query(&self, sql: &str) -> Result<Vec<HashMap<String, String>>, Box<dyn std::error::Error>>;
+}
+
+/// `AnalyticsBackend` backed by a live ClickHouse server.
+pub struct ClickHouseBackend {
     pool: Pool,
-    metrics_buffer: Arc<RwLock<Vec<NetworkMetric>>>,
-    packet_flows_buffer: Arc<RwLock<Vec<PacketFlow>>>,
-    bgp_updates_buffer: Arc<RwLock<Vec<BGPUpdate>>>,
-    ospf_updates_buffer: Arc<RwLock<Vec<OSPFUpdate>>>,
-    isis_updates_buffer: Arc<RwLock<Vec<ISISUpdate>>>,
-    traffic_shaping_buffer: Arc<RwLock<Vec<TrafficShapingMetric>>>,
-    netem_impairments_buffer: Arc<RwLock<Vec<NetemImpairment>>>,
 }
 
-impl ClickHouseAnalytics {
-    pub async fn new(connection_string: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let pool = Pool::new(connection_string)?;
-        
-        // Create tables if they don't exist
-        let mut client = pool.get_handle().await?;
-        
-        // Create metrics table
+impl ClickHouseBackend {
+    pub fn new(connection_string: &str) -> Result<Self, Box<dyn std::error::Error>> {
+        Ok(Self { pool: Pool::new(connection_string)? })
+    }
+}
+
+#[async_trait]
+impl AnalyticsBackend for ClickHouseBackend {
+    async fn create_schema(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut client = self.pool.get_handle().await?;
+
         client.execute("
             CREATE TABLE IF NOT EXISTS network_metrics (
                 timestamp UInt64,
@@ -132,7 +359,6 @@ impl ClickHouseAnalytics {
             ORDER BY (router_id, interface, metric_type, timestamp)
         ").await?;
 
-        // Create packet flows table
         client.execute("
             CREATE TABLE IF NOT EXISTS packet_flows (
                 timestamp UInt64,
@@ -151,7 +377,6 @@ impl ClickHouseAnalytics {
             ORDER BY (router_id, src_ip, dst_ip, timestamp)
         ").await?;
 
-        // Create BGP updates table
         client.execute("
             CREATE TABLE IF NOT EXISTS bgp_updates (
                 timestamp UInt64,
@@ -172,7 +397,6 @@ impl ClickHouseAnalytics {
             ORDER BY (router_id, neighbor_ip, prefix, timestamp)
         ").await?;
 
-        // Create OSPF updates table
         client.execute("
             CREATE TABLE IF NOT EXISTS ospf_updates (
                 timestamp UInt64,
@@ -192,7 +416,6 @@ impl ClickHouseAnalytics {
             ORDER BY (router_id, area_id, lsa_type, timestamp)
         ").await?;
 
-        // Create IS-IS updates table
         client.execute("
             CREATE TABLE IF NOT EXISTS isis_updates (
                 timestamp UInt64,
@@ -211,7 +434,6 @@ impl ClickHouseAnalytics {
             ORDER BY (system_id, area_id, level, timestamp)
         ").await?;
 
-        // Create traffic shaping metrics table
         client.execute("
             CREATE TABLE IF NOT EXISTS traffic_shaping_metrics (
                 timestamp UInt64,
@@ -231,7 +453,6 @@ impl ClickHouseAnalytics {
             ORDER BY (router_id, interface, algorithm, timestamp)
         ").await?;
 
-        // Create netem impairments table
         client.execute("
             CREATE TABLE IF NOT EXISTS netem_impairments (
                 timestamp UInt64,
@@ -246,8 +467,836 @@ impl ClickHouseAnalytics {
             ORDER BY (router_id, interface, impairment_type, timestamp)
         ").await?;
 
+        Ok(())
+    }
+
+    async fn insert_block(
+        &self,
+        table: &str,
+        rows: Vec<HashMap<String, AnalyticsValue>>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        let mut client = self.pool.get_handle().await?;
+        let mut block = Block::new();
+
+        for row in &rows {
+            match table {
+                "network_metrics" => block.push((
+                    row_uint(row, "timestamp"),
+                    row_text(row, "router_id"),
+                    row_text(row, "interface"),
+                    row_text(row, "metric_type"),
+                    row_float(row, "value"),
+                    row_tags(row, "tags"),
+                ))?,
+                "packet_flows" => block.push((
+                    row_uint(row, "timestamp"),
+                    row_text(row, "src_ip"),
+                    row_text(row, "dst_ip"),
+                    row_uint(row, "src_port") as u16,
+                    row_uint(row, "dst_port") as u16,
+                    row_uint(row, "protocol") as u8,
+                    row_uint(row, "bytes"),
+                    row_uint(row, "packets"),
+                    row_uint(row, "duration_ms"),
+                    row_text(row, "router_id"),
+                ))?,
+                "bgp_updates" => block.push((
+                    row_uint(row, "timestamp"),
+                    row_text(row, "router_id"),
+                    row_text(row, "neighbor_ip"),
+                    row_text(row, "prefix"),
+                    row_uint(row, "prefix_length") as u8,
+                    row_u32_array(row, "as_path"),
+                    row_text(row, "next_hop"),
+                    row_text(row, "origin"),
+                    row_uint(row, "local_pref") as u32,
+                    row_uint(row, "med") as u32,
+                    row_text_array(row, "communities"),
+                    row_text(row, "action"),
+                ))?,
+                "ospf_updates" => block.push((
+                    row_uint(row, "timestamp"),
+                    row_text(row, "router_id"),
+                    row_text(row, "area_id"),
+                    row_uint(row, "lsa_type") as u8,
+                    row_text(row, "lsa_id"),
+                    row_text(row, "advertising_router"),
+                    row_uint(row, "sequence_number") as u32,
+                    row_uint(row, "age") as u16,
+                    row_uint(row, "checksum") as u16,
+                    row_uint(row, "length") as u16,
+                    row_text(row, "action"),
+                ))?,
+                "isis_updates" => block.push((
+                    row_uint(row, "timestamp"),
+                    row_text(row, "system_id"),
+                    row_text(row, "area_id"),
+                    row_uint(row, "level") as u8,
+                    row_text(row, "lsp_id"),
+                    row_uint(row, "sequence_number") as u32,
+                    row_uint(row, "remaining_lifetime") as u16,
+                    row_uint(row, "checksum") as u16,
+                    row_uint(row, "pdu_length") as u16,
+                    row_text(row, "action"),
+                ))?,
+                "traffic_shaping_metrics" => block.push((
+                    row_uint(row, "timestamp"),
+                    row_text(row, "router_id"),
+                    row_text(row, "interface"),
+                    row_text(row, "algorithm"),
+                    row_uint(row, "class_id") as u8,
+                    row_uint(row, "packets_processed"),
+                    row_uint(row, "packets_dropped"),
+                    row_uint(row, "bytes_processed"),
+                    row_uint(row, "bytes_dropped"),
+                    row_uint(row, "queue_length") as u32,
+                    row_float(row, "throughput_bps"),
+                ))?,
+                "netem_impairments" => block.push((
+                    row_uint(row, "timestamp"),
+                    row_text(row, "router_id"),
+                    row_text(row, "interface"),
+                    row_text(row, "impairment_type"),
+                    row_tags(row, "parameters"),
+                    if row_bool(row, "active") { 1u8 } else { 0u8 },
+                ))?,
+                _ => return Err(format!("unknown analytics table: {}", table).into()),
+            }
+        }
+
+        client.insert(table, block).await?;
+        Ok(())
+    }
+
+    async fn query(&self, sql: &str) -> Result<Vec<HashMap<String, String>>, Box<dyn std::error::Error>> {
+        let mut client = self.pool.get_handle().await?;
+        let mut cursor = client.query(sql).fetch_all().await?;
+
+        let mut results = Vec::new();
+        while let Some(row) = cursor.next().await? {
+            let mut row_data = HashMap::new();
+            for (i, column) in row.columns().iter().enumerate() {
+                let value = row.get::<String, _>(i)?;
+                row_data.insert(column.name().to_string(), value);
+            }
+            results.push(row_data);
+        }
+
+        Ok(results)
+    }
+}
+
+fn sqlite_schema_sql() -> &'static str {
+    "
+    CREATE TABLE IF NOT EXISTS network_metrics (
+        timestamp INTEGER, router_id TEXT, interface TEXT, metric_type TEXT, value REAL, tags TEXT
+    );
+    CREATE TABLE IF NOT EXISTS packet_flows (
+        timestamp INTEGER, src_ip TEXT, dst_ip TEXT, src_port INTEGER, dst_port INTEGER,
+        protocol INTEGER, bytes INTEGER, packets INTEGER, duration_ms INTEGER, router_id TEXT
+    );
+    CREATE TABLE IF NOT EXISTS bgp_updates (
+        timestamp INTEGER, router_id TEXT, neighbor_ip TEXT, prefix TEXT, prefix_length INTEGER,
+        as_path TEXT, next_hop TEXT, origin TEXT, local_pref INTEGER, med INTEGER,
+        communities TEXT, action TEXT
+    );
+    CREATE TABLE IF NOT EXISTS ospf_updates (
+        timestamp INTEGER, router_id TEXT, area_id TEXT, lsa_type INTEGER, lsa_id TEXT,
+        advertising_router TEXT, sequence_number INTEGER, age INTEGER, checksum INTEGER,
+        length INTEGER, action TEXT
+    );
+    CREATE TABLE IF NOT EXISTS isis_updates (
+        timestamp INTEGER, system_id TEXT, area_id TEXT, level INTEGER, lsp_id TEXT,
+        sequence_number INTEGER, remaining_lifetime INTEGER, checksum INTEGER,
+        pdu_length INTEGER, action TEXT
+    );
+    CREATE TABLE IF NOT EXISTS traffic_shaping_metrics (
+        timestamp INTEGER, router_id TEXT, interface TEXT, algorithm TEXT, class_id INTEGER,
+        packets_processed INTEGER, packets_dropped INTEGER, bytes_processed INTEGER,
+        bytes_dropped INTEGER, queue_length INTEGER, throughput_bps REAL
+    );
+    CREATE TABLE IF NOT EXISTS netem_impairments (
+        timestamp INTEGER, router_id TEXT, interface TEXT, impairment_type TEXT,
+        parameters TEXT, active INTEGER
+    );
+    "
+}
+
+fn sqlite_param(row: &HashMap<String, AnalyticsValue>, column: &str) -> Box<dyn rusqlite::ToSql> {
+    match row.get(column) {
+        Some(AnalyticsValue::Text(v)) => Box::new(v.clone()),
+        Some(AnalyticsValue::UInt(v)) => Box::new(*v as i64),
+        Some(AnalyticsValue::Float(v)) => Box::new(*v),
+        Some(AnalyticsValue::Bool(v)) => Box::new(if *v { 1i64 } else { 0i64 }),
+        Some(AnalyticsValue::TextArray(items)) => Box::new(items.join(",")),
+        Some(AnalyticsValue::U32Array(items)) => {
+            Box::new(items.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(","))
+        }
+        Some(AnalyticsValue::Tags(map)) => {
+            Box::new(map.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join(","))
+        }
+        None => Box::new(Option::<String>::None),
+    }
+}
+
+fn sqlite_value_to_string(value: rusqlite::types::Value) -> String {
+    use rusqlite::types::Value;
+    match value {
+        Value::Null => String::new(),
+        Value::Integer(v) => v.to_string(),
+        Value::Real(v) => v.to_string(),
+        Value::Text(v) => v,
+        Value::Blob(v) => String::from_utf8_lossy(&v).to_string(),
+    }
+}
+
+/// `AnalyticsBackend` backed by an embedded SQLite file, for single-node lab
+/// setups or offline replay where standing up a ClickHouse server is
+/// impractical. Blocking SQLite calls run on the blocking thread pool.
+pub struct SqliteBackend {
+    conn: Arc<std::sync::Mutex<rusqlite::Connection>>,
+}
+
+impl SqliteBackend {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Box<dyn std::error::Error>> {
+        let conn = rusqlite::Connection::open(path)?;
+        Ok(Self { conn: Arc::new(std::sync::Mutex::new(conn)) })
+    }
+
+    pub fn open_in_memory() -> Result<Self, Box<dyn std::error::Error>> {
+        let conn = rusqlite::Connection::open_in_memory()?;
+        Ok(Self { conn: Arc::new(std::sync::Mutex::new(conn)) })
+    }
+}
+
+#[async_trait]
+impl AnalyticsBackend for SqliteBackend {
+    async fn create_schema(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || conn.lock().unwrap().execute_batch(sqlite_schema_sql())).await??;
+        Ok(())
+    }
+
+    async fn insert_block(
+        &self,
+        table: &str,
+        rows: Vec<HashMap<String, AnalyticsValue>>,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+
+        let table = table.to_string();
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+            let columns = table_columns(&table);
+            if columns.is_empty() {
+                return Err(format!("unknown analytics table: {}", table).into());
+            }
+
+            let placeholders: Vec<String> = (1..=columns.len()).map(|i| format!("?{}", i)).collect();
+            let sql = format!("INSERT INTO {} ({}) VALUES ({})", table, columns.join(", "), placeholders.join(", "));
+
+            let mut conn = conn.lock().unwrap();
+            let tx = conn.transaction()?;
+            {
+                let mut stmt = tx.prepare(&sql)?;
+                for row in &rows {
+                    let params: Vec<Box<dyn rusqlite::ToSql>> =
+                        columns.iter().map(|column| sqlite_param(row, column)).collect();
+                    let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+                    stmt.execute(rusqlite::params_from_iter(param_refs))?;
+                }
+            }
+            tx.commit()?;
+            Ok(())
+        })
+        .await?
+        .map_err(|err| -> Box<dyn std::error::Error> { err.to_string().into() })
+    }
+
+    async fn query(&self, sql: &str) -> Result<Vec<HashMap<String, String>>, Box<dyn std::error::Error>> {
+        let sql = sql.to_string();
+        let conn = self.conn.clone();
+        tokio::task::spawn_blocking(move || -> rusqlite::Result<Vec<HashMap<String, String>>> {
+            let conn = conn.lock().unwrap();
+            let mut stmt = conn.prepare(&sql)?;
+            let column_names: Vec<String> = stmt.column_names().iter().map(|name| name.to_string()).collect();
+
+            let mut rows = stmt.query([])?;
+            let mut results = Vec::new();
+            while let Some(row) = rows.next()? {
+                let mut row_data = HashMap::new();
+                for (i, name) in column_names.iter().enumerate() {
+                    let value: rusqlite::types::Value = row.get(i)?;
+                    row_data.insert(name.clone(), sqlite_value_to_string(value));
+                }
+                results.push(row_data);
+            }
+            Ok(results)
+        })
+        .await?
+        .map_err(|err| -> Box<dyn std::error::Error> { Box::new(err) })
+    }
+}
+
+/// A value bound into a [`QueryBuilder`] predicate. Text values are quoted and
+/// escaped; unsigned integers are rendered bare.
+#[derive(Debug, Clone)]
+pub enum QueryValue {
+    Text(String),
+    UInt(u64),
+}
+
+impl QueryValue {
+    fn render(&self) -> String {
+        match self {
+            QueryValue::Text(s) => format!("'{}'", s.replace('\\', "\\\\").replace('\'', "\\'")),
+            QueryValue::UInt(n) => n.to_string(),
+        }
+    }
+}
+
+impl From<&str> for QueryValue {
+    fn from(value: &str) -> Self {
+        QueryValue::Text(value.to_string())
+    }
+}
+
+impl From<String> for QueryValue {
+    fn from(value: String) -> Self {
+        QueryValue::Text(value)
+    }
+}
+
+impl From<u64> for QueryValue {
+    fn from(value: u64) -> Self {
+        QueryValue::UInt(value)
+    }
+}
+
+/// Builds `SELECT` statements so callers compose safe ad-hoc aggregations
+/// instead of hand-formatting SQL into [`ClickHouseAnalytics::query_metrics`].
+/// Values are bound through [`QueryValue`] (quoted/escaped as needed); table
+/// and column names passed to [`Self::eq`]/[`Self::between`]/[`Self::group_by`]/
+/// [`Self::order_by`] are reduced to bare identifier characters so they can't
+/// break out of their position in the generated SQL.
+#[derive(Debug, Clone)]
+pub struct QueryBuilder {
+    columns: Vec<String>,
+    table: String,
+    conditions: Vec<String>,
+    group_by: Vec<String>,
+    order_by: Option<(String, bool)>,
+    limit: Option<u64>,
+}
+
+impl QueryBuilder {
+    pub fn select(table: &str) -> Self {
+        Self {
+            columns: vec!["*".to_string()],
+            table: Self::identifier(table),
+            conditions: Vec::new(),
+            group_by: Vec::new(),
+            order_by: None,
+            limit: None,
+        }
+    }
+
+    /// Override the default `SELECT *` with an explicit column/expression
+    /// list (e.g. aggregates like `COUNT(*) AS n`). Passed through verbatim,
+    /// since these come from trusted call sites rather than external input.
+    pub fn columns(mut self, columns: &[&str]) -> Self {
+        self.columns = columns.iter().map(|c| c.to_string()).collect();
+        self
+    }
+
+    pub fn eq(mut self, column: &str, value: impl Into<QueryValue>) -> Self {
+        self.conditions.push(format!("{} = {}", Self::identifier(column), value.into().render()));
+        self
+    }
+
+    pub fn between(mut self, column: &str, low: u64, high: u64) -> Self {
+        let column = Self::identifier(column);
+        self.conditions.push(format!("{} >= {} AND {} <= {}", column, low, column, high));
+        self
+    }
+
+    pub fn group_by(mut self, columns: &[&str]) -> Self {
+        self.group_by = columns.iter().map(|c| Self::identifier(c)).collect();
+        self
+    }
+
+    pub fn order_by(mut self, column: &str, descending: bool) -> Self {
+        self.order_by = Some((Self::identifier(column), descending));
+        self
+    }
+
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn build(&self) -> String {
+        let mut query = format!("SELECT {} FROM {}", self.columns.join(", "), self.table);
+        if !self.conditions.is_empty() {
+            query.push_str(" WHERE ");
+            query.push_str(&self.conditions.join(" AND "));
+        }
+        if !self.group_by.is_empty() {
+            query.push_str(" GROUP BY ");
+            query.push_str(&self.group_by.join(", "));
+        }
+        if let Some((column, descending)) = &self.order_by {
+            query.push_str(" ORDER BY ");
+            query.push_str(column);
+            query.push_str(if *descending { " DESC" } else { " ASC" });
+        }
+        if let Some(limit) = self.limit {
+            query.push_str(&format!(" LIMIT {}", limit));
+        }
+        query
+    }
+
+    /// Keep only bare-identifier characters, so a table/column name can't
+    /// break out of its position in the generated SQL.
+    fn identifier(raw: &str) -> String {
+        raw.chars().filter(|c| c.is_ascii_alphanumeric() || *c == '_').collect()
+    }
+}
+
+/// Tunables for buffering and background flushing, passed to
+/// [`ClickHouseAnalytics::new`]/[`ClickHouseAnalytics::with_backend`] so
+/// operators can trade ingest latency against batch efficiency per event type.
+#[derive(Debug, Clone)]
+pub struct ClickHouseAnalyticsConfig {
+    /// Row count at which a buffer flushes eagerly, independent of `flush_interval`.
+    pub metrics_buffer_cap: usize,
+    pub packet_flows_buffer_cap: usize,
+    pub bgp_updates_buffer_cap: usize,
+    pub ospf_updates_buffer_cap: usize,
+    pub isis_updates_buffer_cap: usize,
+    pub traffic_shaping_buffer_cap: usize,
+    pub netem_impairments_buffer_cap: usize,
+    /// How often the background flusher (see [`ClickHouseAnalytics::start_flusher`])
+    /// drains every buffer, regardless of size.
+    pub flush_interval: Duration,
+    /// Crash-safe write-ahead log for rows not yet flushed. `None` disables it
+    /// (buffers are then purely in-memory, as before).
+    pub wal: Option<WalConfig>,
+}
+
+impl Default for ClickHouseAnalyticsConfig {
+    fn default() -> Self {
+        Self {
+            metrics_buffer_cap: 1000,
+            packet_flows_buffer_cap: 1000,
+            bgp_updates_buffer_cap: 100,
+            ospf_updates_buffer_cap: 100,
+            isis_updates_buffer_cap: 100,
+            traffic_shaping_buffer_cap: 1000,
+            netem_impairments_buffer_cap: 100,
+            flush_interval: Duration::from_secs(5),
+            wal: None,
+        }
+    }
+}
+
+/// Configuration for the optional write-ahead log: where segments live and
+/// how aggressively they're synced to disk.
+#[derive(Debug, Clone)]
+pub struct WalConfig {
+    pub dir: PathBuf,
+    /// `fsync` after every append. Off trades durability for throughput.
+    pub sync_on_write: bool,
+}
+
+impl Default for WalConfig {
+    fn default() -> Self {
+        Self { dir: PathBuf::from("analytics_wal"), sync_on_write: false }
+    }
+}
+
+/// One append-only segment file, holding newline-delimited JSON rows for a
+/// single buffer. Truncated once its rows are durably flushed to the backend.
+struct WalSegment {
+    path: PathBuf,
+    file: std::sync::Mutex<File>,
+    sync_on_write: bool,
+}
+
+impl WalSegment {
+    fn open(dir: &Path, table: &str, sync_on_write: bool) -> io::Result<Self> {
+        let path = dir.join(format!("{table}.wal"));
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        Ok(Self { path, file: std::sync::Mutex::new(file), sync_on_write })
+    }
+
+    fn append(&self, line: &str) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap();
+        file.write_all(line.as_bytes())?;
+        file.write_all(b"\n")?;
+        if self.sync_on_write {
+            file.sync_data()?;
+        }
+        Ok(())
+    }
+
+    /// Current segment length, in bytes. Snapshotted by a flush right after
+    /// it drains the in-memory buffer so it can later truncate exactly the
+    /// rows it flushed, even if more rows are appended in the meantime.
+    fn len_bytes(&self) -> io::Result<u64> {
+        let file = self.file.lock().unwrap();
+        Ok(file.metadata()?.len())
+    }
+
+    /// Drop the first `up_to` bytes -- the rows a flush just durably wrote to
+    /// the backend -- while preserving anything appended after that point by
+    /// a concurrent insert. Truncating the whole file here would discard
+    /// WAL records for rows that only exist in memory so far.
+    fn truncate_prefix(&self, up_to: u64) -> io::Result<()> {
+        let mut file = self.file.lock().unwrap();
+        let len = file.metadata()?.len();
+        if up_to >= len {
+            file.set_len(0)?;
+            file.seek(SeekFrom::Start(0))?;
+            return Ok(());
+        }
+
+        file.seek(SeekFrom::Start(up_to))?;
+        let mut remainder = Vec::with_capacity((len - up_to) as usize);
+        file.read_to_end(&mut remainder)?;
+
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        file.write_all(&remainder)?;
+        Ok(())
+    }
+
+    fn replay(path: &Path) -> io::Result<Vec<String>> {
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let content = std::fs::read_to_string(path)?;
+        Ok(content.lines().filter(|line| !line.is_empty()).map(str::to_string).collect())
+    }
+}
+
+/// The set of per-table WAL segments backing a [`ClickHouseAnalytics`] instance.
+struct WriteAheadLog {
+    metrics: WalSegment,
+    packet_flows: WalSegment,
+    bgp_updates: WalSegment,
+    ospf_updates: WalSegment,
+    isis_updates: WalSegment,
+    traffic_shaping: WalSegment,
+    netem_impairments: WalSegment,
+}
+
+impl WriteAheadLog {
+    fn open(config: &WalConfig) -> io::Result<Self> {
+        std::fs::create_dir_all(&config.dir)?;
         Ok(Self {
-            pool,
+            metrics: WalSegment::open(&config.dir, "network_metrics", config.sync_on_write)?,
+            packet_flows: WalSegment::open(&config.dir, "packet_flows", config.sync_on_write)?,
+            bgp_updates: WalSegment::open(&config.dir, "bgp_updates", config.sync_on_write)?,
+            ospf_updates: WalSegment::open(&config.dir, "ospf_updates", config.sync_on_write)?,
+            isis_updates: WalSegment::open(&config.dir, "isis_updates", config.sync_on_write)?,
+            traffic_shaping: WalSegment::open(&config.dir, "traffic_shaping_metrics", config.sync_on_write)?,
+            netem_impairments: WalSegment::open(&config.dir, "netem_impairments", config.sync_on_write)?,
+        })
+    }
+}
+
+/// The running background flusher started by [`ClickHouseAnalytics::start_flusher`].
+struct FlusherHandle {
+    shutdown: Arc<Notify>,
+    task: tokio::task::JoinHandle<()>,
+}
+
+/// Upper bound (in nanoseconds) of each flush-latency histogram bucket. The
+/// final bucket is implicitly "+Inf".
+const FLUSH_LATENCY_BUCKET_BOUNDS_NS: [u64; 6] =
+    [1_000_000, 10_000_000, 100_000_000, 500_000_000, 1_000_000_000, u64::MAX];
+
+/// Counters/gauges/histogram for one table's ingestion pipeline, updated from
+/// `insert_*`/`flush_*` and rendered by [`AnalyticsMetrics::render_prometheus`].
+struct TableMetrics {
+    inserted_total: AtomicU64,
+    flushed_total: AtomicU64,
+    flush_total: AtomicU64,
+    flush_errors_total: AtomicU64,
+    buffer_depth: AtomicU64,
+    flush_latency_buckets: [AtomicU64; FLUSH_LATENCY_BUCKET_BOUNDS_NS.len()],
+}
+
+impl TableMetrics {
+    fn new() -> Self {
+        Self {
+            inserted_total: AtomicU64::new(0),
+            flushed_total: AtomicU64::new(0),
+            flush_total: AtomicU64::new(0),
+            flush_errors_total: AtomicU64::new(0),
+            buffer_depth: AtomicU64::new(0),
+            flush_latency_buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+
+    fn record_insert(&self) {
+        self.inserted_total.fetch_add(1, Ordering::Relaxed);
+        self.buffer_depth.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_flush_success(&self, rows: u64, latency_ns: u64) {
+        self.flushed_total.fetch_add(rows, Ordering::Relaxed);
+        self.flush_total.fetch_add(1, Ordering::Relaxed);
+        self.buffer_depth.fetch_sub(rows, Ordering::Relaxed);
+        for (bound, bucket) in FLUSH_LATENCY_BUCKET_BOUNDS_NS.iter().zip(&self.flush_latency_buckets) {
+            if latency_ns <= *bound {
+                bucket.fetch_add(1, Ordering::Relaxed);
+                break;
+            }
+        }
+    }
+
+    fn record_flush_error(&self, rows: u64) {
+        self.flush_total.fetch_add(1, Ordering::Relaxed);
+        self.flush_errors_total.fetch_add(1, Ordering::Relaxed);
+        // The rows were already drained from the buffer before the insert
+        // was attempted, so the gauge must drop them here too, not just on
+        // the success path.
+        self.buffer_depth.fetch_sub(rows, Ordering::Relaxed);
+    }
+}
+
+/// Ingestion-pipeline instrumentation, exposed through
+/// [`ClickHouseAnalytics::metrics_handle`] so operators can alert on buffer
+/// growth or backend insert failures.
+struct AnalyticsMetrics {
+    network_metrics: TableMetrics,
+    packet_flows: TableMetrics,
+    bgp_updates: TableMetrics,
+    ospf_updates: TableMetrics,
+    isis_updates: TableMetrics,
+    traffic_shaping_metrics: TableMetrics,
+    netem_impairments: TableMetrics,
+}
+
+impl AnalyticsMetrics {
+    fn new() -> Self {
+        Self {
+            network_metrics: TableMetrics::new(),
+            packet_flows: TableMetrics::new(),
+            bgp_updates: TableMetrics::new(),
+            ospf_updates: TableMetrics::new(),
+            isis_updates: TableMetrics::new(),
+            traffic_shaping_metrics: TableMetrics::new(),
+            netem_impairments: TableMetrics::new(),
+        }
+    }
+
+    fn tables(&self) -> [(&'static str, &TableMetrics); 7] {
+        [
+            ("network_metrics", &self.network_metrics),
+            ("packet_flows", &self.packet_flows),
+            ("bgp_updates", &self.bgp_updates),
+            ("ospf_updates", &self.ospf_updates),
+            ("isis_updates", &self.isis_updates),
+            ("traffic_shaping_metrics", &self.traffic_shaping_metrics),
+            ("netem_impairments", &self.netem_impairments),
+        ]
+    }
+
+    /// Render every table's counters/gauges/histogram in Prometheus text
+    /// exposition format (see https://prometheus.io/docs/instrumenting/exposition_formats/).
+    fn render_prometheus(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP analytics_inserted_rows_total Rows pushed into a buffer via insert_*\n");
+        out.push_str("# TYPE analytics_inserted_rows_total counter\n");
+        for (table, metrics) in self.tables() {
+            out.push_str(&format!(
+                "analytics_inserted_rows_total{{table=\"{table}\"}} {}\n",
+                metrics.inserted_total.load(Ordering::Relaxed)
+            ));
+        }
+
+        out.push_str("# HELP analytics_flushed_rows_total Rows successfully written to the backend\n");
+        out.push_str("# TYPE analytics_flushed_rows_total counter\n");
+        for (table, metrics) in self.tables() {
+            out.push_str(&format!(
+                "analytics_flushed_rows_total{{table=\"{table}\"}} {}\n",
+                metrics.flushed_total.load(Ordering::Relaxed)
+            ));
+        }
+
+        out.push_str("# HELP analytics_flush_total Flush attempts\n");
+        out.push_str("# TYPE analytics_flush_total counter\n");
+        for (table, metrics) in self.tables() {
+            out.push_str(&format!(
+                "analytics_flush_total{{table=\"{table}\"}} {}\n",
+                metrics.flush_total.load(Ordering::Relaxed)
+            ));
+        }
+
+        out.push_str("# HELP analytics_flush_errors_total Failed flush attempts\n");
+        out.push_str("# TYPE analytics_flush_errors_total counter\n");
+        for (table, metrics) in self.tables() {
+            out.push_str(&format!(
+                "analytics_flush_errors_total{{table=\"{table}\"}} {}\n",
+                metrics.flush_errors_total.load(Ordering::Relaxed)
+            ));
+        }
+
+        out.push_str("# HELP analytics_buffer_depth Rows currently buffered, not yet flushed\n");
+        out.push_str("# TYPE analytics_buffer_depth gauge\n");
+        for (table, metrics) in self.tables() {
+            out.push_str(&format!(
+                "analytics_buffer_depth{{table=\"{table}\"}} {}\n",
+                metrics.buffer_depth.load(Ordering::Relaxed)
+            ));
+        }
+
+        out.push_str("# HELP analytics_flush_latency_seconds Time spent inserting a flushed block into the backend\n");
+        out.push_str("# TYPE analytics_flush_latency_seconds histogram\n");
+        for (table, metrics) in self.tables() {
+            let mut running = 0u64;
+            for (bound_ns, bucket) in FLUSH_LATENCY_BUCKET_BOUNDS_NS.iter().zip(&metrics.flush_latency_buckets) {
+                running += bucket.load(Ordering::Relaxed);
+                let le = if *bound_ns == u64::MAX {
+                    "+Inf".to_string()
+                } else {
+                    format!("{}", *bound_ns as f64 / 1_000_000_000.0)
+                };
+                out.push_str(&format!(
+                    "analytics_flush_latency_seconds_bucket{{table=\"{table}\",le=\"{le}\"}} {running}\n"
+                ));
+            }
+        }
+
+        out
+    }
+}
+
+/// Broadcast capacity for [`ClickHouseAnalytics::subscribe`]: how many recent
+/// events a slow subscriber can fall behind by before it starts missing them.
+const EVENT_BROADCAST_CAPACITY: usize = 1024;
+
+/// A newly-ingested routing-protocol record, broadcast to subscribers as
+/// `insert_bgp_update`/`insert_ospf_update`/`insert_isis_update` are called.
+#[derive(Debug, Clone)]
+pub enum RoutingEvent {
+    Bgp(BGPUpdate),
+    Ospf(OSPFUpdate),
+    Isis(ISISUpdate),
+}
+
+/// Subscription predicate for [`ClickHouseAnalytics::subscribe`]. `None`
+/// fields match any value of that dimension. `router_id` matches
+/// [`BGPUpdate::router_id`]/[`OSPFUpdate::router_id`] and
+/// [`ISISUpdate::system_id`]; `neighbor_ip` only applies to BGP updates;
+/// `area_id` only applies to OSPF/IS-IS updates.
+#[derive(Debug, Clone, Default)]
+pub struct EventFilter {
+    pub router_id: Option<String>,
+    pub neighbor_ip: Option<String>,
+    pub area_id: Option<String>,
+}
+
+impl EventFilter {
+    fn matches(&self, event: &RoutingEvent) -> bool {
+        match event {
+            RoutingEvent::Bgp(update) => {
+                self.router_id.as_deref().map_or(true, |id| id == update.router_id)
+                    && self.neighbor_ip.as_deref().map_or(true, |ip| ip == update.neighbor_ip)
+                    && self.area_id.is_none()
+            }
+            RoutingEvent::Ospf(update) => {
+                self.router_id.as_deref().map_or(true, |id| id == update.router_id)
+                    && self.neighbor_ip.is_none()
+                    && self.area_id.as_deref().map_or(true, |area| area == update.area_id)
+            }
+            RoutingEvent::Isis(update) => {
+                self.router_id.as_deref().map_or(true, |id| id == update.system_id)
+                    && self.neighbor_ip.is_none()
+                    && self.area_id.as_deref().map_or(true, |area| area == update.area_id)
+            }
+        }
+    }
+}
+
+/// A live subscription returned by [`ClickHouseAnalytics::subscribe`]. Beyond
+/// plain `recv`, [`Self::poll`] offers a long-poll variant that blocks until a
+/// matching event arrives or a timeout elapses.
+pub struct EventSubscription {
+    filter: EventFilter,
+    receiver: broadcast::Receiver<RoutingEvent>,
+}
+
+impl EventSubscription {
+    /// Wait indefinitely for the next event matching this subscription's filter.
+    pub async fn recv(&mut self) -> Option<RoutingEvent> {
+        loop {
+            match self.receiver.recv().await {
+                Ok(event) if self.filter.matches(&event) => return Some(event),
+                Ok(_) => continue,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Wait up to `timeout` for the next matching event. Returns `None` on
+    /// timeout or once the publisher side has gone away.
+    pub async fn poll(&mut self, timeout: Duration) -> Option<RoutingEvent> {
+        tokio::time::timeout(timeout, self.recv()).await.ok().flatten()
+    }
+}
+
+pub struct ClickHouseAnalytics {
+    backend: Arc<dyn AnalyticsBackend>,
+    config: ClickHouseAnalyticsConfig,
+    metrics_buffer: Arc<RwLock<Vec<NetworkMetric>>>,
+    packet_flows_buffer: Arc<RwLock<Vec<PacketFlow>>>,
+    bgp_updates_buffer: Arc<RwLock<Vec<BGPUpdate>>>,
+    ospf_updates_buffer: Arc<RwLock<Vec<OSPFUpdate>>>,
+    isis_updates_buffer: Arc<RwLock<Vec<ISISUpdate>>>,
+    traffic_shaping_buffer: Arc<RwLock<Vec<TrafficShapingMetric>>>,
+    netem_impairments_buffer: Arc<RwLock<Vec<NetemImpairment>>>,
+    flusher: std::sync::Mutex<Option<FlusherHandle>>,
+    wal: Option<Arc<WriteAheadLog>>,
+    metrics: Arc<AnalyticsMetrics>,
+    events: broadcast::Sender<RoutingEvent>,
+}
+
+impl ClickHouseAnalytics {
+    /// Connect to a live ClickHouse server. For a backend that doesn't require
+    /// one, use [`Self::with_backend`] with a [`SqliteBackend`].
+    pub async fn new(connection_string: &str, config: ClickHouseAnalyticsConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        Self::with_backend(Arc::new(ClickHouseBackend::new(connection_string)?), config).await
+    }
+
+    /// Build analytics ingestion/query on top of any [`AnalyticsBackend`].
+    pub async fn with_backend(backend: Arc<dyn AnalyticsBackend>, config: ClickHouseAnalyticsConfig) -> Result<Self, Box<dyn std::error::Error>> {
+        backend.create_schema().await?;
+
+        let wal = match &config.wal {
+            Some(wal_config) => {
+                let wal_config = wal_config.clone();
+                Some(Arc::new(tokio::task::spawn_blocking(move || WriteAheadLog::open(&wal_config)).await??))
+            }
+            None => None,
+        };
+
+        let (events, _) = broadcast::channel(EVENT_BROADCAST_CAPACITY);
+
+        let analytics = Self {
+            backend,
+            config,
             metrics_buffer: Arc::new(RwLock::new(Vec::new())),
             packet_flows_buffer: Arc::new(RwLock::new(Vec::new())),
             bgp_updates_buffer: Arc::new(RwLock::new(Vec::new())),
@@ -255,115 +1304,287 @@ impl ClickHouseAnalytics {
             isis_updates_buffer: Arc::new(RwLock::new(Vec::new())),
             traffic_shaping_buffer: Arc::new(RwLock::new(Vec::new())),
             netem_impairments_buffer: Arc::new(RwLock::new(Vec::new())),
-        })
+            flusher: std::sync::Mutex::new(None),
+            wal,
+            metrics: Arc::new(AnalyticsMetrics::new()),
+            events,
+        };
+        analytics.recover().await?;
+        Ok(analytics)
+    }
+
+    /// Render ingestion-pipeline instrumentation (buffer depths, flush counts,
+    /// rows inserted per table, flush latency, insert errors) in Prometheus
+    /// text exposition format.
+    pub fn metrics_handle(&self) -> String {
+        self.metrics.render_prometheus()
+    }
+
+    /// Subscribe to newly ingested BGP/OSPF/IS-IS records matching `filter`.
+    /// Events are delivered over a [`tokio::sync::broadcast`] channel, so a
+    /// subscriber that falls more than [`EVENT_BROADCAST_CAPACITY`] events
+    /// behind will silently miss the oldest ones rather than block ingestion.
+    pub fn subscribe(&self, filter: EventFilter) -> EventSubscription {
+        EventSubscription { filter, receiver: self.events.subscribe() }
+    }
+
+    /// Replay any surviving WAL segments into their buffers. Called
+    /// automatically on construction; exposed so callers can re-run recovery
+    /// after restoring a WAL directory from a backup.
+    pub async fn recover(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(wal) = self.wal.clone() else {
+            return Ok(());
+        };
+
+        let (metrics, packet_flows, bgp_updates, ospf_updates, isis_updates, traffic_shaping, netem_impairments) =
+            tokio::task::spawn_blocking(move || -> io::Result<_> {
+                Ok((
+                    WalSegment::replay(&wal.metrics.path)?,
+                    WalSegment::replay(&wal.packet_flows.path)?,
+                    WalSegment::replay(&wal.bgp_updates.path)?,
+                    WalSegment::replay(&wal.ospf_updates.path)?,
+                    WalSegment::replay(&wal.isis_updates.path)?,
+                    WalSegment::replay(&wal.traffic_shaping.path)?,
+                    WalSegment::replay(&wal.netem_impairments.path)?,
+                ))
+            })
+            .await??;
+
+        self.metrics_buffer.write().await.extend(metrics.iter().filter_map(|line| serde_json::from_str(line).ok()));
+        self.packet_flows_buffer.write().await.extend(packet_flows.iter().filter_map(|line| serde_json::from_str(line).ok()));
+        self.bgp_updates_buffer.write().await.extend(bgp_updates.iter().filter_map(|line| serde_json::from_str(line).ok()));
+        self.ospf_updates_buffer.write().await.extend(ospf_updates.iter().filter_map(|line| serde_json::from_str(line).ok()));
+        self.isis_updates_buffer.write().await.extend(isis_updates.iter().filter_map(|line| serde_json::from_str(line).ok()));
+        self.traffic_shaping_buffer.write().await.extend(traffic_shaping.iter().filter_map(|line| serde_json::from_str(line).ok()));
+        self.netem_impairments_buffer.write().await.extend(netem_impairments.iter().filter_map(|line| serde_json::from_str(line).ok()));
+
+        Ok(())
+    }
+
+    /// Spawn a background task that flushes every buffer on `config.flush_interval`,
+    /// independent of the per-table size caps enforced by `insert_*`. A no-op if
+    /// already running. Call [`Self::shutdown`] to stop it and flush deterministically.
+    pub fn start_flusher(self: &Arc<Self>) {
+        let mut guard = self.flusher.lock().unwrap();
+        if guard.is_some() {
+            return;
+        }
+
+        let shutdown = Arc::new(Notify::new());
+        let task_shutdown = shutdown.clone();
+        let this = Arc::clone(self);
+        let interval = this.config.flush_interval;
+
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                tokio::select! {
+                    _ = ticker.tick() => {
+                        if let Err(err) = this.flush_all().await {
+                            tracing::warn!("background analytics flush failed: {}", err);
+                        }
+                    }
+                    _ = task_shutdown.notified() => break,
+                }
+            }
+        });
+
+        *guard = Some(FlusherHandle { shutdown, task });
+    }
+
+    /// Stop the background flusher (if running) and flush every buffer one
+    /// last time, deterministically. Replaces the old `Drop`-based flush,
+    /// which could panic inside an existing runtime and silently lose data
+    /// on error.
+    pub async fn shutdown(self) -> Result<(), Box<dyn std::error::Error>> {
+        let handle = self.flusher.lock().unwrap().take();
+        if let Some(handle) = handle {
+            handle.shutdown.notify_one();
+            let _ = handle.task.await;
+        }
+        self.flush_all().await
+    }
+
+    /// Append `line` to the WAL segment selected by `segment`, if a WAL is configured.
+    async fn wal_append(
+        &self,
+        segment: impl FnOnce(&WriteAheadLog) -> &WalSegment + Send + 'static,
+        line: String,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(wal) = self.wal.clone() else {
+            return Ok(());
+        };
+        tokio::task::spawn_blocking(move || segment(&wal).append(&line)).await??;
+        Ok(())
+    }
+
+    /// Byte length of the WAL segment selected by `segment`, if a WAL is
+    /// configured. A flush snapshots this right after draining its buffer so
+    /// it later truncates only the rows it actually flushed.
+    async fn wal_len(
+        &self,
+        segment: impl FnOnce(&WriteAheadLog) -> &WalSegment + Send + 'static,
+    ) -> Result<u64, Box<dyn std::error::Error>> {
+        let Some(wal) = self.wal.clone() else {
+            return Ok(0);
+        };
+        Ok(tokio::task::spawn_blocking(move || segment(&wal).len_bytes()).await??)
+    }
+
+    /// Truncate the first `up_to` bytes of the WAL segment selected by
+    /// `segment`, if a WAL is configured. Called once a buffer's rows are
+    /// durably in the backend; `up_to` is the segment's length at the moment
+    /// those rows were drained, so rows appended afterward survive.
+    async fn wal_truncate(
+        &self,
+        segment: impl FnOnce(&WriteAheadLog) -> &WalSegment + Send + 'static,
+        up_to: u64,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(wal) = self.wal.clone() else {
+            return Ok(());
+        };
+        tokio::task::spawn_blocking(move || segment(&wal).truncate_prefix(up_to)).await??;
+        Ok(())
     }
 
     pub async fn insert_metric(&self, metric: NetworkMetric) -> Result<(), Box<dyn std::error::Error>> {
+        // Held across the WAL append so a concurrent flush can never observe
+        // a WAL record whose row hasn't made it into the buffer yet -- see
+        // wal_len/wal_truncate.
         let mut buffer = self.metrics_buffer.write().await;
+        self.wal_append(|wal| &wal.metrics, serde_json::to_string(&metric)?).await?;
+        self.metrics.network_metrics.record_insert();
         buffer.push(metric);
-        
+
         // Flush if buffer is full
-        if buffer.len() >= 1000 {
+        if buffer.len() >= self.config.metrics_buffer_cap {
+            drop(buffer);
             self.flush_metrics().await?;
         }
-        
+
         Ok(())
     }
 
     pub async fn insert_packet_flow(&self, flow: PacketFlow) -> Result<(), Box<dyn std::error::Error>> {
         let mut buffer = self.packet_flows_buffer.write().await;
+        self.wal_append(|wal| &wal.packet_flows, serde_json::to_string(&flow)?).await?;
+        self.metrics.packet_flows.record_insert();
         buffer.push(flow);
-        
+
         // Flush if buffer is full
-        if buffer.len() >= 1000 {
+        if buffer.len() >= self.config.packet_flows_buffer_cap {
+            drop(buffer);
             self.flush_packet_flows().await?;
         }
-        
+
         Ok(())
     }
 
     pub async fn insert_bgp_update(&self, update: BGPUpdate) -> Result<(), Box<dyn std::error::Error>> {
         let mut buffer = self.bgp_updates_buffer.write().await;
+        self.wal_append(|wal| &wal.bgp_updates, serde_json::to_string(&update)?).await?;
+        self.metrics.bgp_updates.record_insert();
+        let _ = self.events.send(RoutingEvent::Bgp(update.clone()));
         buffer.push(update);
-        
+
         // Flush if buffer is full
-        if buffer.len() >= 100 {
+        if buffer.len() >= self.config.bgp_updates_buffer_cap {
+            drop(buffer);
             self.flush_bgp_updates().await?;
         }
-        
+
         Ok(())
     }
 
     pub async fn insert_ospf_update(&self, update: OSPFUpdate) -> Result<(), Box<dyn std::error::Error>> {
         let mut buffer = self.ospf_updates_buffer.write().await;
+        self.wal_append(|wal| &wal.ospf_updates, serde_json::to_string(&update)?).await?;
+        self.metrics.ospf_updates.record_insert();
+        let _ = self.events.send(RoutingEvent::Ospf(update.clone()));
         buffer.push(update);
-        
+
         // Flush if buffer is full
-        if buffer.len() >= 100 {
+        if buffer.len() >= self.config.ospf_updates_buffer_cap {
+            drop(buffer);
             self.flush_ospf_updates().await?;
         }
-        
+
         Ok(())
     }
 
     pub async fn insert_isis_update(&self, update: ISISUpdate) -> Result<(), Box<dyn std::error::Error>> {
         let mut buffer = self.isis_updates_buffer.write().await;
+        self.wal_append(|wal| &wal.isis_updates, serde_json::to_string(&update)?).await?;
+        self.metrics.isis_updates.record_insert();
+        let _ = self.events.send(RoutingEvent::Isis(update.clone()));
         buffer.push(update);
-        
+
         // Flush if buffer is full
-        if buffer.len() >= 100 {
+        if buffer.len() >= self.config.isis_updates_buffer_cap {
+            drop(buffer);
             self.flush_isis_updates().await?;
         }
-        
+
         Ok(())
     }
 
     pub async fn insert_traffic_shaping_metric(&self, metric: TrafficShapingMetric) -> Result<(), Box<dyn std::error::Error>> {
         let mut buffer = self.traffic_shaping_buffer.write().await;
+        self.wal_append(|wal| &wal.traffic_shaping, serde_json::to_string(&metric)?).await?;
+        self.metrics.traffic_shaping_metrics.record_insert();
         buffer.push(metric);
-        
+
         // Flush if buffer is full
-        if buffer.len() >= 1000 {
+        if buffer.len() >= self.config.traffic_shaping_buffer_cap {
+            drop(buffer);
             self.flush_traffic_shaping_metrics().await?;
         }
-        
+
         Ok(())
     }
 
     pub async fn insert_netem_impairment(&self, impairment: NetemImpairment) -> Result<(), Box<dyn std::error::Error>> {
         let mut buffer = self.netem_impairments_buffer.write().await;
+        self.wal_append(|wal| &wal.netem_impairments, serde_json::to_string(&impairment)?).await?;
+        self.metrics.netem_impairments.record_insert();
         buffer.push(impairment);
-        
+
         // Flush if buffer is full
-        if buffer.len() >= 100 {
+        if buffer.len() >= self.config.netem_impairments_buffer_cap {
+            drop(buffer);
             self.flush_netem_impairments().await?;
         }
-        
+
         Ok(())
     }
 
+    /// Run `insert_block`, recording its outcome and latency against `table_metrics`.
+    async fn timed_insert_block(
+        &self,
+        table: &str,
+        rows: Vec<HashMap<String, AnalyticsValue>>,
+        table_metrics: &TableMetrics,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let row_count = rows.len() as u64;
+        let started = Instant::now();
+        let result = self.backend.insert_block(table, rows).await;
+        match &result {
+            Ok(()) => table_metrics.record_flush_success(row_count, started.elapsed().as_nanos() as u64),
+            Err(_) => table_metrics.record_flush_error(row_count),
+        }
+        result
+    }
+
     async fn flush_metrics(&self) -> Result<(), Box<dyn std::error::Error>> {
         let mut buffer = self.metrics_buffer.write().await;
         if buffer.is_empty() {
             return Ok(());
         }
-
-        let mut client = self.pool.get_handle().await?;
-        let mut block = Block::new();
-        
-        for metric in buffer.drain(..) {
-            block.push((
-                metric.timestamp,
-                metric.router_id,
-                metric.interface,
-                metric.metric_type,
-                metric.value,
-                metric.tags,
-            ));
-        }
-
-        client.insert("network_metrics", block).await?;
-        Ok(())
+        let rows = buffer.drain(..).map(|metric| metric.to_row()).collect();
+        let wal_len = self.wal_len(|wal| &wal.metrics).await?;
+        drop(buffer);
+        self.timed_insert_block("network_metrics", rows, &self.metrics.network_metrics).await?;
+        self.wal_truncate(|wal| &wal.metrics, wal_len).await
     }
 
     async fn flush_packet_flows(&self) -> Result<(), Box<dyn std::error::Error>> {
@@ -371,27 +1592,11 @@ impl ClickHouseAnalytics {
         if buffer.is_empty() {
             return Ok(());
         }
-
-        let mut client = self.pool.get_handle().await?;
-        let mut block = Block::new();
-        
-        for flow in buffer.drain(..) {
-            block.push((
-                flow.timestamp,
-                flow.src_ip,
-                flow.dst_ip,
-                flow.src_port,
-                flow.dst_port,
-                flow.protocol,
-                flow.bytes,
-                flow.packets,
-                flow.duration_ms,
-                flow.router_id,
-            ));
-        }
-
-        client.insert("packet_flows", block).await?;
-        Ok(())
+        let rows = buffer.drain(..).map(|flow| flow.to_row()).collect();
+        let wal_len = self.wal_len(|wal| &wal.packet_flows).await?;
+        drop(buffer);
+        self.timed_insert_block("packet_flows", rows, &self.metrics.packet_flows).await?;
+        self.wal_truncate(|wal| &wal.packet_flows, wal_len).await
     }
 
     async fn flush_bgp_updates(&self) -> Result<(), Box<dyn std::error::Error>> {
@@ -399,29 +1604,11 @@ impl ClickHouseAnalytics {
         if buffer.is_empty() {
             return Ok(());
         }
-
-        let mut client = self.pool.get_handle().await?;
-        let mut block = Block::new();
-        
-        for update in buffer.drain(..) {
-            block.push((
-                update.timestamp,
-                update.router_id,
-                update.neighbor_ip,
-                update.prefix,
-                update.prefix_length,
-                update.as_path,
-                update.next_hop,
-                update.origin,
-                update.local_pref,
-                update.med,
-                update.communities,
-                update.action,
-            ));
-        }
-
-        client.insert("bgp_updates", block).await?;
-        Ok(())
+        let rows = buffer.drain(..).map(|update| update.to_row()).collect();
+        let wal_len = self.wal_len(|wal| &wal.bgp_updates).await?;
+        drop(buffer);
+        self.timed_insert_block("bgp_updates", rows, &self.metrics.bgp_updates).await?;
+        self.wal_truncate(|wal| &wal.bgp_updates, wal_len).await
     }
 
     async fn flush_ospf_updates(&self) -> Result<(), Box<dyn std::error::Error>> {
@@ -429,28 +1616,11 @@ impl ClickHouseAnalytics {
         if buffer.is_empty() {
             return Ok(());
         }
-
-        let mut client = self.pool.get_handle().await?;
-        let mut block = Block::new();
-        
-        for update in buffer.drain(..) {
-            block.push((
-                update.timestamp,
-                update.router_id,
-                update.area_id,
-                update.lsa_type,
-                update.lsa_id,
-                update.advertising_router,
-                update.sequence_number,
-                update.age,
-                update.checksum,
-                update.length,
-                update.action,
-            ));
-        }
-
-        client.insert("ospf_updates", block).await?;
-        Ok(())
+        let rows = buffer.drain(..).map(|update| update.to_row()).collect();
+        let wal_len = self.wal_len(|wal| &wal.ospf_updates).await?;
+        drop(buffer);
+        self.timed_insert_block("ospf_updates", rows, &self.metrics.ospf_updates).await?;
+        self.wal_truncate(|wal| &wal.ospf_updates, wal_len).await
     }
 
     async fn flush_isis_updates(&self) -> Result<(), Box<dyn std::error::Error>> {
@@ -458,27 +1628,11 @@ impl ClickHouseAnalytics {
         if buffer.is_empty() {
             return Ok(());
         }
-
-        let mut client = self.pool.get_handle().await?;
-        let mut block = Block::new();
-        
-        for update in buffer.drain(..) {
-            block.push((
-                update.timestamp,
-                update.system_id,
-                update.area_id,
-                update.level,
-                update.lsp_id,
-                update.sequence_number,
-                update.remaining_lifetime,
-                update.checksum,
-                update.pdu_length,
-                update.action,
-            ));
-        }
-
-        client.insert("isis_updates", block).await?;
-        Ok(())
+        let rows = buffer.drain(..).map(|update| update.to_row()).collect();
+        let wal_len = self.wal_len(|wal| &wal.isis_updates).await?;
+        drop(buffer);
+        self.timed_insert_block("isis_updates", rows, &self.metrics.isis_updates).await?;
+        self.wal_truncate(|wal| &wal.isis_updates, wal_len).await
     }
 
     async fn flush_traffic_shaping_metrics(&self) -> Result<(), Box<dyn std::error::Error>> {
@@ -486,28 +1640,11 @@ impl ClickHouseAnalytics {
         if buffer.is_empty() {
             return Ok(());
         }
-
-        let mut client = self.pool.get_handle().await?;
-        let mut block = Block::new();
-        
-        for metric in buffer.drain(..) {
-            block.push((
-                metric.timestamp,
-                metric.router_id,
-                metric.interface,
-                metric.algorithm,
-                metric.class_id,
-                metric.packets_processed,
-                metric.packets_dropped,
-                metric.bytes_processed,
-                metric.bytes_dropped,
-                metric.queue_length,
-                metric.throughput_bps,
-            ));
-        }
-
-        client.insert("traffic_shaping_metrics", block).await?;
-        Ok(())
+        let rows = buffer.drain(..).map(|metric| metric.to_row()).collect();
+        let wal_len = self.wal_len(|wal| &wal.traffic_shaping).await?;
+        drop(buffer);
+        self.timed_insert_block("traffic_shaping_metrics", rows, &self.metrics.traffic_shaping_metrics).await?;
+        self.wal_truncate(|wal| &wal.traffic_shaping, wal_len).await
     }
 
     async fn flush_netem_impairments(&self) -> Result<(), Box<dyn std::error::Error>> {
@@ -515,23 +1652,11 @@ impl ClickHouseAnalytics {
         if buffer.is_empty() {
             return Ok(());
         }
-
-        let mut client = self.pool.get_handle().await?;
-        let mut block = Block::new();
-        
-        for impairment in buffer.drain(..) {
-            block.push((
-                impairment.timestamp,
-                impairment.router_id,
-                impairment.interface,
-                impairment.impairment_type,
-                impairment.parameters,
-                if impairment.active { 1u8 } else { 0u8 },
-            ));
-        }
-
-        client.insert("netem_impairments", block).await?;
-        Ok(())
+        let rows = buffer.drain(..).map(|impairment| impairment.to_row()).collect();
+        let wal_len = self.wal_len(|wal| &wal.netem_impairments).await?;
+        drop(buffer);
+        self.timed_insert_block("netem_impairments", rows, &self.metrics.netem_impairments).await?;
+        self.wal_truncate(|wal| &wal.netem_impairments, wal_len).await
     }
 
     pub async fn flush_all(&self) -> Result<(), Box<dyn std::error::Error>> {
@@ -546,138 +1671,103 @@ impl ClickHouseAnalytics {
     }
 
     pub async fn query_metrics(&self, query: &str) -> Result<Vec<HashMap<String, String>>, Box<dyn std::error::Error>> {
-        let mut client = self.pool.get_handle().await?;
-        let mut cursor = client.query(query).fetch_all().await?;
-        
-        let mut results = Vec::new();
-        while let Some(row) = cursor.next().await? {
-            let mut row_data = HashMap::new();
-            for (i, column) in row.columns().iter().enumerate() {
-                let value = row.get::<String, _>(i)?;
-                row_data.insert(column.name().to_string(), value);
-            }
-            results.push(row_data);
-        }
-        
-        Ok(results)
+        self.backend.query(query).await
     }
 
     pub async fn get_router_metrics(&self, router_id: &str, time_range: (u64, u64)) -> Result<Vec<HashMap<String, String>>, Box<dyn std::error::Error>> {
-        let query = format!(
-            "SELECT * FROM network_metrics 
-             WHERE router_id = '{}' 
-             AND timestamp >= {} 
-             AND timestamp <= {} 
-             ORDER BY timestamp DESC",
-            router_id, time_range.0, time_range.1
-        );
-        
+        let query = QueryBuilder::select("network_metrics")
+            .eq("router_id", router_id)
+            .between("timestamp", time_range.0, time_range.1)
+            .order_by("timestamp", true)
+            .build();
+
         self.query_metrics(&query).await
     }
 
     pub async fn get_interface_metrics(&self, router_id: &str, interface: &str, time_range: (u64, u64)) -> Result<Vec<HashMap<String, String>>, Box<dyn std::error::Error>> {
-        let query = format!(
-            "SELECT * FROM network_metrics 
-             WHERE router_id = '{}' 
-             AND interface = '{}' 
-             AND timestamp >= {} 
-             AND timestamp <= {} 
-             ORDER BY timestamp DESC",
-            router_id, interface, time_range.0, time_range.1
-        );
-        
+        let query = QueryBuilder::select("network_metrics")
+            .eq("router_id", router_id)
+            .eq("interface", interface)
+            .between("timestamp", time_range.0, time_range.1)
+            .order_by("timestamp", true)
+            .build();
+
         self.query_metrics(&query).await
     }
 
     pub async fn get_bgp_convergence_metrics(&self, router_id: &str, time_range: (u64, u64)) -> Result<Vec<HashMap<String, String>>, Box<dyn std::error::Error>> {
-        let query = format!(
-            "SELECT 
-                neighbor_ip,
-                COUNT(*) as update_count,
-                COUNTIf(action = 'advertise') as advertisements,
-                COUNTIf(action = 'withdraw') as withdrawals,
-                uniq(prefix) as unique_prefixes
-             FROM bgp_updates 
-             WHERE router_id = '{}' 
-             AND timestamp >= {} 
-             AND timestamp <= {} 
-             GROUP BY neighbor_ip 
-             ORDER BY update_count DESC",
-            router_id, time_range.0, time_range.1
-        );
-        
+        let query = QueryBuilder::select("bgp_updates")
+            .columns(&[
+                "neighbor_ip",
+                "COUNT(*) as update_count",
+                "COUNTIf(action = 'advertise') as advertisements",
+                "COUNTIf(action = 'withdraw') as withdrawals",
+                "uniq(prefix) as unique_prefixes",
+            ])
+            .eq("router_id", router_id)
+            .between("timestamp", time_range.0, time_range.1)
+            .group_by(&["neighbor_ip"])
+            .order_by("update_count", true)
+            .build();
+
         self.query_metrics(&query).await
     }
 
     pub async fn get_traffic_flow_analysis(&self, router_id: &str, time_range: (u64, u64)) -> Result<Vec<HashMap<String, String>>, Box<dyn std::error::Error>> {
-        let query = format!(
-            "SELECT 
-                src_ip,
-                dst_ip,
-                protocol,
-                SUM(bytes) as total_bytes,
-                SUM(packets) as total_packets,
-                AVG(duration_ms) as avg_duration_ms
-             FROM packet_flows 
-             WHERE router_id = '{}' 
-             AND timestamp >= {} 
-             AND timestamp <= {} 
-             GROUP BY src_ip, dst_ip, protocol 
-             ORDER BY total_bytes DESC 
-             LIMIT 100",
-            router_id, time_range.0, time_range.1
-        );
-        
+        let query = QueryBuilder::select("packet_flows")
+            .columns(&[
+                "src_ip",
+                "dst_ip",
+                "protocol",
+                "SUM(bytes) as total_bytes",
+                "SUM(packets) as total_packets",
+                "AVG(duration_ms) as avg_duration_ms",
+            ])
+            .eq("router_id", router_id)
+            .between("timestamp", time_range.0, time_range.1)
+            .group_by(&["src_ip", "dst_ip", "protocol"])
+            .order_by("total_bytes", true)
+            .limit(100)
+            .build();
+
         self.query_metrics(&query).await
     }
 
     pub async fn get_traffic_shaping_effectiveness(&self, router_id: &str, time_range: (u64, u64)) -> Result<Vec<HashMap<String, String>>, Box<dyn std::error::Error>> {
-        let query = format!(
-            "SELECT 
-                interface,
-                algorithm,
-                class_id,
-                SUM(packets_processed) as total_processed,
-                SUM(packets_dropped) as total_dropped,
-                SUM(bytes_processed) as total_bytes_processed,
-                SUM(bytes_dropped) as total_bytes_dropped,
-                AVG(throughput_bps) as avg_throughput
-             FROM traffic_shaping_metrics 
-             WHERE router_id = '{}' 
-             AND timestamp >= {} 
-             AND timestamp <= {} 
-             GROUP BY interface, algorithm, class_id 
-             ORDER BY total_processed DESC",
-            router_id, time_range.0, time_range.1
-        );
-        
+        let query = QueryBuilder::select("traffic_shaping_metrics")
+            .columns(&[
+                "interface",
+                "algorithm",
+                "class_id",
+                "SUM(packets_processed) as total_processed",
+                "SUM(packets_dropped) as total_dropped",
+                "SUM(bytes_processed) as total_bytes_processed",
+                "SUM(bytes_dropped) as total_bytes_dropped",
+                "AVG(throughput_bps) as avg_throughput",
+            ])
+            .eq("router_id", router_id)
+            .between("timestamp", time_range.0, time_range.1)
+            .group_by(&["interface", "algorithm", "class_id"])
+            .order_by("total_processed", true)
+            .build();
+
         self.query_metrics(&query).await
     }
 
     pub async fn get_network_impairment_impact(&self, router_id: &str, time_range: (u64, u64)) -> Result<Vec<HashMap<String, String>>, Box<dyn std::error::Error>> {
-        let query = format!(
-            "SELECT 
-                interface,
-                impairment_type,
-                COUNT(*) as impairment_events,
-                COUNTIf(active = 1) as active_impairments
-             FROM netem_impairments 
-             WHERE router_id = '{}' 
-             AND timestamp >= {} 
-             AND timestamp <= {} 
-             GROUP BY interface, impairment_type 
-             ORDER BY impairment_events DESC",
-            router_id, time_range.0, time_range.1
-        );
-        
-        self.query_metrics(&query).await
-    }
-}
+        let query = QueryBuilder::select("netem_impairments")
+            .columns(&[
+                "interface",
+                "impairment_type",
+                "COUNT(*) as impairment_events",
+                "COUNTIf(active = 1) as active_impairments",
+            ])
+            .eq("router_id", router_id)
+            .between("timestamp", time_range.0, time_range.1)
+            .group_by(&["interface", "impairment_type"])
+            .order_by("impairment_events", true)
+            .build();
 
-impl Drop for ClickHouseAnalytics {
-    fn drop(&mut self) {
-        // Flush all buffers on drop
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        rt.block_on(self.flush_all()).ok();
+        self.query_metrics(&query).await
     }
 }
@@ -1,34 +1,66 @@
-use std::time::Duration;
 use anyhow::Result;
-use tracing::{debug, error};
-use crate::CloudPodsStats;
+use tracing::debug;
+
+use crate::connection_pool::{Connection, ConnectionPool, LruConnectionPool, PoolCacheStats};
+
+/// Configuration for reaching the CloudPods API.
+#[derive(Debug, Clone)]
+pub struct CloudPodsConfig {
+    pub endpoint: String,
+    /// Max number of live API connections [`CloudPodsIntegration`] keeps
+    /// pooled before it starts evicting idle ones.
+    pub pool_capacity: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CloudPodsStats {
+    pub vpc_count: u32,
+    pub nat_gateway_count: u32,
+    pub load_balancer_count: u32,
+    pub service_mesh_count: u32,
+    pub total_traffic: u64,
+    pub active_connections: u32,
+}
+
+/// A single connection to the CloudPods API. In a real implementation this
+/// would wrap an actual client (e.g. an HTTP or gRPC channel); here it stands
+/// in for one, polling whatever counts the endpoint reports.
+pub struct CloudPodsClient {
+    endpoint: String,
+}
+
+impl Connection for CloudPodsClient {
+    fn connect(endpoint: &str) -> Result<Self> {
+        debug!(endpoint, "dialing CloudPods API connection");
+        Ok(Self { endpoint: endpoint.to_string() })
+    }
+}
+
+impl CloudPodsClient {
+    /// Poll the CloudPods API for current resource counts over this
+    /// connection. In real implementation, would call out to the CloudPods
+    /// API; here it reports zeros, same as the prior stub.
+    fn poll_stats(&self) -> CloudPodsStats {
+        debug!(endpoint = %self.endpoint, "polling CloudPods API for stats");
+        CloudPodsStats::default()
+    }
+}
 
 pub struct CloudPodsIntegration {
-    config: crate::CloudPodsConfig,
-    vpc_count: u32,
-    nat_gateway_count: u32,
-    load_balancer_count: u32,
-    service_mesh_count: u32,
-    total_traffic: u64,
-    active_connections: u32,
+    config: CloudPodsConfig,
+    pool: LruConnectionPool<CloudPodsClient>,
 }
 
 impl CloudPodsIntegration {
-    pub fn new(config: crate::CloudPodsConfig) -> Result<Self> {
-        Ok(Self {
-            config,
-            vpc_count: 0,
-            nat_gateway_count: 0,
-            load_balancer_count: 0,
-            service_mesh_count: 0,
-            total_traffic: 0,
-            active_connections: 0,
-        })
+    pub fn new(config: CloudPodsConfig) -> Result<Self> {
+        let pool = LruConnectionPool::new(config.pool_capacity);
+        Ok(Self { config, pool })
     }
 
     pub async fn start(&self) -> Result<()> {
         debug!("Starting CloudPods integration");
-        // In real implementation, would connect to CloudPods API
+        // Warm the pool so the first real request doesn't pay the dial cost.
+        self.pool.checkout(&self.config.endpoint)?;
         Ok(())
     }
 
@@ -38,13 +70,14 @@ impl CloudPodsIntegration {
     }
 
     pub async fn get_stats(&self) -> Result<CloudPodsStats> {
-        Ok(CloudPodsStats {
-            vpc_count: self.vpc_count,
-            nat_gateway_count: self.nat_gateway_count,
-            load_balancer_count: self.load_balancer_count,
-            service_mesh_count: self.service_mesh_count,
-            total_traffic: self.total_traffic,
-            active_connections: self.active_connections,
-        })
+        let conn = self.pool.checkout(&self.config.endpoint)?;
+        let mut stats = conn.poll_stats();
+        stats.active_connections = self.pool.stats().live_connections as u32;
+        Ok(stats)
+    }
+
+    /// Hit/miss/eviction counters for the underlying connection pool.
+    pub fn pool_stats(&self) -> PoolCacheStats {
+        self.pool.stats()
     }
 }
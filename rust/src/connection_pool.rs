@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use anyhow::{anyhow, Result};
+
+/// Something a [`ConnectionPool`] can lazily create the first time a given
+/// endpoint is requested, and keep around for reuse afterwards.
+pub trait Connection: Send + Sync + 'static {
+    fn connect(endpoint: &str) -> Result<Self>
+    where
+        Self: Sized;
+}
+
+/// Bounded, keyed cache of reusable connections — a pool-cache in the spirit
+/// of a TPU connection cache: at most `capacity` live connections are kept,
+/// least-recently-used first out, but an entry currently checked out by a
+/// caller is never evicted.
+pub trait ConnectionPool<T: Connection> {
+    /// Check out the connection for `endpoint`, dialing a new one on a miss.
+    fn checkout(&self, endpoint: &str) -> Result<PooledConnection<T>>;
+    fn stats(&self) -> PoolCacheStats;
+}
+
+/// Hit/miss/eviction counters for a [`ConnectionPool`], independent of
+/// whatever domain-specific stats the connections themselves expose.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PoolCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+    pub live_connections: usize,
+}
+
+struct Slot<T> {
+    conn: Arc<T>,
+    checked_out: u32,
+    last_used: Instant,
+}
+
+struct Inner<T> {
+    capacity: usize,
+    entries: HashMap<String, Slot<T>>,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+impl<T> Inner<T> {
+    /// Evict the least-recently-used entry that isn't checked out, if the
+    /// pool is at capacity. A pool where every entry is checked out is
+    /// allowed to temporarily exceed `capacity` rather than evict one out
+    /// from under its caller.
+    fn evict_if_needed(&mut self) {
+        if self.entries.len() < self.capacity {
+            return;
+        }
+        let victim = self
+            .entries
+            .iter()
+            .filter(|(_, slot)| slot.checked_out == 0)
+            .min_by_key(|(_, slot)| slot.last_used)
+            .map(|(endpoint, _)| endpoint.clone());
+
+        if let Some(endpoint) = victim {
+            self.entries.remove(&endpoint);
+            self.evictions += 1;
+        }
+    }
+}
+
+/// LRU-evicting [`ConnectionPool`] keyed by endpoint, generic over the
+/// connection type so the same machinery backs CloudPods, load-balancer, and
+/// service-mesh integrations alike.
+pub struct LruConnectionPool<T> {
+    inner: Arc<Mutex<Inner<T>>>,
+}
+
+impl<T: Connection> LruConnectionPool<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                capacity: capacity.max(1),
+                entries: HashMap::new(),
+                hits: 0,
+                misses: 0,
+                evictions: 0,
+            })),
+        }
+    }
+}
+
+impl<T: Connection> ConnectionPool<T> for LruConnectionPool<T> {
+    fn checkout(&self, endpoint: &str) -> Result<PooledConnection<T>> {
+        let mut inner = self.inner.lock().map_err(|e| anyhow!(e.to_string()))?;
+
+        if let Some(slot) = inner.entries.get_mut(endpoint) {
+            slot.checked_out += 1;
+            slot.last_used = Instant::now();
+            inner.hits += 1;
+            let conn = slot.conn.clone();
+            drop(inner);
+            return Ok(PooledConnection { conn, endpoint: endpoint.to_string(), pool: self.inner.clone() });
+        }
+
+        inner.misses += 1;
+        inner.evict_if_needed();
+
+        let conn = Arc::new(T::connect(endpoint)?);
+        inner.entries.insert(
+            endpoint.to_string(),
+            Slot { conn: conn.clone(), checked_out: 1, last_used: Instant::now() },
+        );
+
+        Ok(PooledConnection { conn, endpoint: endpoint.to_string(), pool: self.inner.clone() })
+    }
+
+    fn stats(&self) -> PoolCacheStats {
+        let inner = self.inner.lock().unwrap();
+        PoolCacheStats {
+            hits: inner.hits,
+            misses: inner.misses,
+            evictions: inner.evictions,
+            live_connections: inner.entries.len(),
+        }
+    }
+}
+
+/// RAII handle to a checked-out connection; returning it to the pool (on
+/// drop) makes it eligible for eviction again.
+pub struct PooledConnection<T> {
+    conn: Arc<T>,
+    endpoint: String,
+    pool: Arc<Mutex<Inner<T>>>,
+}
+
+impl<T> std::ops::Deref for PooledConnection<T> {
+    type Target = T;
+    fn deref(&self) -> &T {
+        &self.conn
+    }
+}
+
+impl<T> Drop for PooledConnection<T> {
+    fn drop(&mut self) {
+        if let Ok(mut inner) = self.pool.lock() {
+            if let Some(slot) = inner.entries.get_mut(&self.endpoint) {
+                slot.checked_out = slot.checked_out.saturating_sub(1);
+                slot.last_used = Instant::now();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FakeConnection(String);
+    impl Connection for FakeConnection {
+        fn connect(endpoint: &str) -> Result<Self> {
+            Ok(FakeConnection(endpoint.to_string()))
+        }
+    }
+
+    #[test]
+    fn checkout_of_a_fresh_endpoint_is_a_miss_then_a_hit() {
+        let pool: LruConnectionPool<FakeConnection> = LruConnectionPool::new(2);
+        let first = pool.checkout("a").unwrap();
+        drop(first);
+        let _second = pool.checkout("a").unwrap();
+
+        let stats = pool.stats();
+        assert_eq!(stats.misses, 1);
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.live_connections, 1);
+    }
+
+    #[test]
+    fn least_recently_used_entry_is_evicted_over_capacity() {
+        let pool: LruConnectionPool<FakeConnection> = LruConnectionPool::new(2);
+        drop(pool.checkout("a").unwrap());
+        drop(pool.checkout("b").unwrap());
+        drop(pool.checkout("c").unwrap());
+
+        let stats = pool.stats();
+        assert_eq!(stats.evictions, 1);
+        assert_eq!(stats.live_connections, 2);
+        assert!(pool.inner.lock().unwrap().entries.contains_key("c"));
+        assert!(!pool.inner.lock().unwrap().entries.contains_key("a"));
+    }
+
+    #[test]
+    fn checked_out_entry_survives_eviction_pressure() {
+        let pool: LruConnectionPool<FakeConnection> = LruConnectionPool::new(1);
+        let held = pool.checkout("a").unwrap();
+        drop(pool.checkout("b").unwrap());
+
+        let stats = pool.stats();
+        assert_eq!(stats.evictions, 0);
+        assert_eq!(stats.live_connections, 2);
+        drop(held);
+    }
+}
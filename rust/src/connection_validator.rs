@@ -0,0 +1,140 @@
+//! Per-interface source validation, so a spoofed `src_ip`/`src_port` can't pollute
+//! another sender's flow accounting.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use dashmap::DashMap;
+
+/// Validation policy for one interface: an allow-list of source prefixes
+/// (reverse-path style), plus an optional requirement that a flow complete a
+/// connection-setup packet (e.g. TCP SYN) before later packets on it are trusted.
+#[derive(Debug, Clone)]
+pub struct InterfacePolicy {
+    /// `(network, prefix_len)` pairs a source address must fall within. Empty
+    /// means "no subnet restriction on this interface".
+    pub allowed_source_prefixes: Vec<(IpAddr, u8)>,
+    /// Require the first packet seen for a `(src_ip, src_port)` to be a
+    /// connection-setup packet before later packets on it are admitted.
+    pub require_cookie: bool,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionValidatorConfig {
+    pub interface_policies: HashMap<String, InterfacePolicy>,
+}
+
+/// Validates that a packet's claimed source is plausible for the interface it
+/// arrived on, before it's allowed to influence flow/connection state.
+pub struct ConnectionValidator {
+    config: ConnectionValidatorConfig,
+    /// `(interface, src_ip, src_port)` tuples admitted via a connection-setup
+    /// packet, for interfaces whose policy requires one.
+    admitted: DashMap<(String, IpAddr, u16), ()>,
+}
+
+impl ConnectionValidator {
+    pub fn new(config: ConnectionValidatorConfig) -> Arc<Self> {
+        Arc::new(Self { config, admitted: DashMap::new() })
+    }
+
+    /// Validate a packet's source against `interface`'s policy. `is_connection_setup`
+    /// marks packets (e.g. a TCP SYN) allowed to establish a new cookie binding.
+    /// Returns `false` if the packet should be dropped as spoofed.
+    pub fn validate(
+        &self,
+        interface: &str,
+        src_ip: IpAddr,
+        src_port: u16,
+        is_connection_setup: bool,
+    ) -> bool {
+        let Some(policy) = self.config.interface_policies.get(interface) else {
+            return true; // No policy configured for this interface: allow by default.
+        };
+
+        if !policy.allowed_source_prefixes.is_empty()
+            && !policy
+                .allowed_source_prefixes
+                .iter()
+                .any(|&(prefix, len)| prefix_contains(prefix, len, src_ip))
+        {
+            return false;
+        }
+
+        if !policy.require_cookie {
+            return true;
+        }
+
+        let key = (interface.to_string(), src_ip, src_port);
+        if self.admitted.contains_key(&key) {
+            return true;
+        }
+        if is_connection_setup {
+            self.admitted.insert(key, ());
+            return true;
+        }
+
+        false
+    }
+}
+
+/// Whether `addr` falls within `prefix/prefix_len`. IPv4 and IPv6 addresses never
+/// match across families.
+fn prefix_contains(prefix: IpAddr, prefix_len: u8, addr: IpAddr) -> bool {
+    match (prefix, addr) {
+        (IpAddr::V4(prefix), IpAddr::V4(addr)) => {
+            let mask = if prefix_len == 0 { 0 } else { u32::MAX << (32 - prefix_len.min(32)) };
+            u32::from(prefix) & mask == u32::from(addr) & mask
+        }
+        (IpAddr::V6(prefix), IpAddr::V6(addr)) => {
+            let mask = if prefix_len == 0 { 0 } else { u128::MAX << (128 - prefix_len.min(128)) };
+            u128::from(prefix) & mask == u128::from(addr) & mask
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn policy(prefixes: Vec<(IpAddr, u8)>, require_cookie: bool) -> ConnectionValidatorConfig {
+        let mut interface_policies = HashMap::new();
+        interface_policies.insert(
+            "eth0".to_string(),
+            InterfacePolicy { allowed_source_prefixes: prefixes, require_cookie },
+        );
+        ConnectionValidatorConfig { interface_policies }
+    }
+
+    #[test]
+    fn allows_unconfigured_interfaces() {
+        let validator = ConnectionValidator::new(ConnectionValidatorConfig::default());
+        let ip = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 1));
+        assert!(validator.validate("eth1", ip, 1234, false));
+    }
+
+    #[test]
+    fn rejects_source_outside_allowed_prefix() {
+        let prefix = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 0));
+        let validator = ConnectionValidator::new(policy(vec![(prefix, 24)], false));
+        let spoofed = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 1));
+        assert!(!validator.validate("eth0", spoofed, 1234, false));
+
+        let legit = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 42));
+        assert!(validator.validate("eth0", legit, 1234, false));
+    }
+
+    #[test]
+    fn cookie_policy_requires_setup_packet_first() {
+        let validator = ConnectionValidator::new(policy(vec![], true));
+        let ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 42));
+
+        assert!(!validator.validate("eth0", ip, 1234, false));
+        assert!(validator.validate("eth0", ip, 1234, true));
+        // Now that the tuple is admitted, subsequent non-setup packets pass.
+        assert!(validator.validate("eth0", ip, 1234, false));
+    }
+}
@@ -0,0 +1,224 @@
+//! Stateful 5-tuple flow tracking with configurable TCP/UDP idle timeouts.
+
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+
+/// 5-tuple identifying a flow
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FlowKey {
+    pub src_ip: IpAddr,
+    pub dst_ip: IpAddr,
+    pub protocol: u8,
+    pub src_port: u16,
+    pub dst_port: u16,
+}
+
+/// Minimal TCP connection state machine driven by observed flags
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TcpState {
+    Syn,
+    SynAck,
+    Established,
+    Fin,
+    Closed,
+}
+
+/// TCP flag bits, as found in the 14th byte (offset 13) of the TCP header
+pub mod tcp_flags {
+    pub const FIN: u8 = 0x01;
+    pub const SYN: u8 = 0x02;
+    pub const RST: u8 = 0x04;
+    pub const ACK: u8 = 0x10;
+}
+
+/// Per-flow accounting and (for TCP) connection state
+#[derive(Debug, Clone)]
+pub struct FlowEntry {
+    pub packets: u64,
+    pub bytes: u64,
+    pub last_seen: Instant,
+    pub tcp_state: Option<TcpState>,
+}
+
+impl FlowEntry {
+    fn new(bytes: u64, tcp_state: Option<TcpState>) -> Self {
+        Self {
+            packets: 1,
+            bytes,
+            last_seen: Instant::now(),
+            tcp_state,
+        }
+    }
+}
+
+/// Configuration for flow idle timeouts
+#[derive(Debug, Clone, Copy)]
+pub struct FlowTableConfig {
+    pub udp_timeout: Duration,
+    pub tcp_timeout: Duration,
+    /// Shorter timeout applied once a TCP flow has seen FIN or RST
+    pub tcp_closing_timeout: Duration,
+}
+
+impl Default for FlowTableConfig {
+    fn default() -> Self {
+        Self {
+            udp_timeout: Duration::from_secs(10),
+            tcp_timeout: Duration::from_secs(60),
+            tcp_closing_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+/// Stateful 5-tuple flow table backed by a `DashMap`
+pub struct FlowTable {
+    flows: DashMap<FlowKey, FlowEntry>,
+    config: FlowTableConfig,
+}
+
+impl FlowTable {
+    pub fn new(config: FlowTableConfig) -> Arc<Self> {
+        Arc::new(Self {
+            flows: DashMap::new(),
+            config,
+        })
+    }
+
+    /// Record a packet against its flow, advancing TCP state if `tcp_flags` is given.
+    pub fn record_packet(&self, key: FlowKey, bytes: u64, tcp_flags: Option<u8>) {
+        let next_state = tcp_flags.map(Self::advance_tcp_state);
+
+        self.flows
+            .entry(key)
+            .and_modify(|entry| {
+                entry.packets += 1;
+                entry.bytes += bytes;
+                entry.last_seen = Instant::now();
+                if let Some(state) = next_state {
+                    entry.tcp_state = Some(Self::merge_tcp_state(entry.tcp_state, state));
+                }
+            })
+            .or_insert_with(|| FlowEntry::new(bytes, next_state));
+    }
+
+    fn advance_tcp_state(flags: u8) -> TcpState {
+        if flags & tcp_flags::RST != 0 || flags & tcp_flags::FIN != 0 {
+            TcpState::Fin
+        } else if flags & tcp_flags::SYN != 0 && flags & tcp_flags::ACK != 0 {
+            TcpState::SynAck
+        } else if flags & tcp_flags::SYN != 0 {
+            TcpState::Syn
+        } else {
+            TcpState::Established
+        }
+    }
+
+    /// A flow's state only moves forward (Syn -> SynAck -> Established -> Fin/Closed),
+    /// never backward, so a stray retransmitted SYN can't resurrect a closed flow.
+    fn merge_tcp_state(current: Option<TcpState>, observed: TcpState) -> TcpState {
+        match (current, observed) {
+            (Some(TcpState::Fin), _) | (Some(TcpState::Closed), _) => current.unwrap(),
+            (Some(TcpState::SynAck), TcpState::Syn) => TcpState::SynAck,
+            (Some(TcpState::Established), TcpState::Syn) | (Some(TcpState::Established), TcpState::SynAck) => {
+                TcpState::Established
+            }
+            _ => observed,
+        }
+    }
+
+    /// Evict flows that have been idle past their protocol's timeout.
+    pub fn sweep(&self) -> usize {
+        let now = Instant::now();
+        let mut evicted = 0;
+        self.flows.retain(|key, entry| {
+            let timeout = if key.protocol == 6 {
+                match entry.tcp_state {
+                    Some(TcpState::Fin) | Some(TcpState::Closed) => self.config.tcp_closing_timeout,
+                    _ => self.config.tcp_timeout,
+                }
+            } else {
+                self.config.udp_timeout
+            };
+
+            let keep = now.duration_since(entry.last_seen) < timeout;
+            if !keep {
+                evicted += 1;
+            }
+            keep
+        });
+        evicted
+    }
+
+    pub fn active_flow_count(&self) -> usize {
+        self.flows.len()
+    }
+
+    pub fn get(&self, key: &FlowKey) -> Option<FlowEntry> {
+        self.flows.get(key).map(|entry| entry.clone())
+    }
+}
+
+/// Spawn a background task that periodically sweeps `table` for expired flows.
+pub fn spawn_sweeper(table: Arc<FlowTable>, interval: Duration) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let evicted = table.sweep();
+            if evicted > 0 {
+                tracing::debug!("Flow table sweep evicted {} expired flows", evicted);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn key() -> FlowKey {
+        FlowKey {
+            src_ip: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+            dst_ip: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)),
+            protocol: 6,
+            src_port: 1234,
+            dst_port: 443,
+        }
+    }
+
+    #[test]
+    fn new_flow_tracked_on_first_packet() {
+        let table = FlowTable::new(FlowTableConfig::default());
+        table.record_packet(key(), 100, Some(tcp_flags::SYN));
+        assert_eq!(table.active_flow_count(), 1);
+        let entry = table.get(&key()).unwrap();
+        assert_eq!(entry.packets, 1);
+        assert_eq!(entry.tcp_state, Some(TcpState::Syn));
+    }
+
+    #[test]
+    fn tcp_state_advances_to_established() {
+        let table = FlowTable::new(FlowTableConfig::default());
+        table.record_packet(key(), 100, Some(tcp_flags::SYN));
+        table.record_packet(key(), 60, Some(tcp_flags::SYN | tcp_flags::ACK));
+        table.record_packet(key(), 200, Some(tcp_flags::ACK));
+        let entry = table.get(&key()).unwrap();
+        assert_eq!(entry.packets, 3);
+        assert_eq!(entry.tcp_state, Some(TcpState::Established));
+    }
+
+    #[test]
+    fn fin_state_is_sticky() {
+        let table = FlowTable::new(FlowTableConfig::default());
+        table.record_packet(key(), 100, Some(tcp_flags::SYN));
+        table.record_packet(key(), 60, Some(tcp_flags::FIN));
+        table.record_packet(key(), 60, Some(tcp_flags::SYN));
+        let entry = table.get(&key()).unwrap();
+        assert_eq!(entry.tcp_state, Some(TcpState::Fin));
+    }
+}
@@ -0,0 +1,246 @@
+//! IPv4/IPv6 fragment reassembly keyed on `(src_ip, dst_ip, protocol, identification)`.
+
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use dashmap::DashMap;
+
+/// Identifies the datagram a fragment belongs to.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FragmentKey {
+    pub src_ip: IpAddr,
+    pub dst_ip: IpAddr,
+    pub protocol: u8,
+    pub identification: u32,
+}
+
+/// Configuration for the reassembly buffer.
+#[derive(Debug, Clone, Copy)]
+pub struct ReassemblyConfig {
+    /// Largest reassembled datagram allowed per key before the partial is dropped.
+    pub max_bytes_per_key: usize,
+    /// How long a partial datagram may sit without a new fragment before it expires.
+    pub timeout: Duration,
+}
+
+impl Default for ReassemblyConfig {
+    fn default() -> Self {
+        Self {
+            max_bytes_per_key: 65535,
+            timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// A half-open byte range `[start, end)` covered by fragments received so far.
+type Range = (usize, usize);
+
+struct PartialDatagram {
+    data: Vec<u8>,
+    covered: Vec<Range>,
+    total_len: Option<usize>,
+    last_seen: Instant,
+}
+
+impl PartialDatagram {
+    fn new() -> Self {
+        Self {
+            data: Vec::new(),
+            covered: Vec::new(),
+            total_len: None,
+            last_seen: Instant::now(),
+        }
+    }
+
+    /// Merges `(start, end)` into `covered`, keeping it sorted and non-overlapping.
+    fn add_range(&mut self, start: usize, end: usize) {
+        self.covered.push((start, end));
+        self.covered.sort_unstable_by_key(|r| r.0);
+        let mut merged: Vec<Range> = Vec::with_capacity(self.covered.len());
+        for (start, end) in self.covered.drain(..) {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        self.covered = merged;
+    }
+
+    fn is_complete(&self) -> bool {
+        matches!(self.total_len, Some(len) if self.covered.as_slice() == [(0, len)])
+    }
+}
+
+/// Buffers out-of-order IP fragments until a datagram is fully covered, with a
+/// per-key byte cap and idle timeout to bound memory used by incomplete ones.
+pub struct FragmentReassembler {
+    partials: DashMap<FragmentKey, PartialDatagram>,
+    config: ReassemblyConfig,
+    timeouts: AtomicU64,
+}
+
+impl FragmentReassembler {
+    pub fn new(config: ReassemblyConfig) -> Arc<Self> {
+        Arc::new(Self {
+            partials: DashMap::new(),
+            config,
+            timeouts: AtomicU64::new(0),
+        })
+    }
+
+    /// Feed one fragment's payload into the buffer for `key`. `offset` and `data`
+    /// describe where this fragment's bytes sit in the reassembled L4 payload;
+    /// `more_fragments` is the datagram's More-Fragments bit. Returns the
+    /// complete payload once `[0, total_len)` is fully covered.
+    pub fn add_fragment(
+        &self,
+        key: FragmentKey,
+        offset: usize,
+        more_fragments: bool,
+        data: &[u8],
+    ) -> Option<Vec<u8>> {
+        let end = offset + data.len();
+        if end > self.config.max_bytes_per_key {
+            // Oversized or malformed reassembly target; drop any partial state for it.
+            self.partials.remove(&key);
+            return None;
+        }
+
+        let mut entry = self.partials.entry(key).or_insert_with(PartialDatagram::new);
+
+        // Reject fragments whose overlap with already-received bytes disagrees
+        // with what's already buffered -- a sign of an overlapping-fragment attack.
+        for &(start, existing_end) in &entry.covered {
+            let overlap_start = start.max(offset);
+            let overlap_end = existing_end.min(end);
+            if overlap_start < overlap_end
+                && entry.data[overlap_start..overlap_end]
+                    != data[(overlap_start - offset)..(overlap_end - offset)]
+            {
+                return None;
+            }
+        }
+
+        if entry.data.len() < end {
+            entry.data.resize(end, 0);
+        }
+        entry.data[offset..end].copy_from_slice(data);
+        entry.add_range(offset, end);
+        entry.last_seen = Instant::now();
+
+        if !more_fragments {
+            entry.total_len = Some(end);
+        }
+
+        if entry.is_complete() {
+            let total_len = entry.total_len.unwrap();
+            let complete = entry.data[..total_len].to_vec();
+            drop(entry);
+            self.partials.remove(&key);
+            Some(complete)
+        } else {
+            None
+        }
+    }
+
+    /// Evict partial datagrams that have been idle past the configured timeout.
+    pub fn sweep(&self) -> usize {
+        let now = Instant::now();
+        let timeout = self.config.timeout;
+        let mut evicted = 0;
+        self.partials.retain(|_, partial| {
+            let keep = now.duration_since(partial.last_seen) < timeout;
+            if !keep {
+                evicted += 1;
+            }
+            keep
+        });
+        if evicted > 0 {
+            self.timeouts.fetch_add(evicted as u64, Ordering::Relaxed);
+        }
+        evicted
+    }
+
+    /// Total number of partial datagrams ever dropped for sitting idle too long.
+    pub fn timeouts_total(&self) -> u64 {
+        self.timeouts.load(Ordering::Relaxed)
+    }
+}
+
+/// Spawn a background task that periodically sweeps `reassembler` for expired partials.
+pub fn spawn_sweeper(
+    reassembler: Arc<FragmentReassembler>,
+    interval: Duration,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let evicted = reassembler.sweep();
+            if evicted > 0 {
+                tracing::debug!("Fragment reassembly sweep expired {} partial datagrams", evicted);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    fn key() -> FragmentKey {
+        FragmentKey {
+            src_ip: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)),
+            dst_ip: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2)),
+            protocol: 17,
+            identification: 4242u32,
+        }
+    }
+
+    #[test]
+    fn reassembles_in_order_fragments() {
+        let reassembler = FragmentReassembler::new(ReassemblyConfig::default());
+        assert!(reassembler.add_fragment(key(), 0, true, &[1, 2, 3, 4]).is_none());
+        let complete = reassembler.add_fragment(key(), 4, false, &[5, 6]).unwrap();
+        assert_eq!(complete, vec![1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn reassembles_out_of_order_fragments() {
+        let reassembler = FragmentReassembler::new(ReassemblyConfig::default());
+        assert!(reassembler.add_fragment(key(), 4, false, &[5, 6]).is_none());
+        let complete = reassembler.add_fragment(key(), 0, true, &[1, 2, 3, 4]).unwrap();
+        assert_eq!(complete, vec![1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn rejects_conflicting_overlap() {
+        let reassembler = FragmentReassembler::new(ReassemblyConfig::default());
+        assert!(reassembler.add_fragment(key(), 0, true, &[1, 2, 3, 4]).is_none());
+        // Overlapping bytes disagree with what's already buffered.
+        assert!(reassembler.add_fragment(key(), 2, false, &[9, 9]).is_none());
+    }
+
+    #[test]
+    fn sweep_expires_idle_partials_and_counts_timeouts() {
+        let reassembler = FragmentReassembler::new(ReassemblyConfig {
+            max_bytes_per_key: 65535,
+            timeout: Duration::from_millis(0),
+        });
+        reassembler.add_fragment(key(), 0, true, &[1, 2, 3, 4]);
+        assert_eq!(reassembler.sweep(), 1);
+        assert_eq!(reassembler.timeouts_total(), 1);
+    }
+
+    #[test]
+    fn oversized_datagram_is_dropped() {
+        let reassembler = FragmentReassembler::new(ReassemblyConfig {
+            max_bytes_per_key: 8,
+            timeout: Duration::from_secs(30),
+        });
+        assert!(reassembler.add_fragment(key(), 0, true, &[0u8; 16]).is_none());
+    }
+}
@@ -0,0 +1,215 @@
+//! Linux kernel FIB synchronization: mirror the in-memory [`RoutingTable`]
+//! into the kernel's forwarding table, and import the kernel's current
+//! routes on startup, using netlink route messages (`RTM_NEWROUTE`,
+//! `RTM_DELROUTE`, and `RTM_GETROUTE` dumps). Entirely gated behind the
+//! `netlink` cargo feature, so the rest of the crate — and any no-std or
+//! embedded consumer that only wants the pure routing data structures —
+//! never pulls in the netlink stack.
+
+use std::collections::HashSet;
+use std::net::IpAddr;
+
+use futures::TryStreamExt;
+use netlink_packet_route::route::{RouteAddress, RouteAttribute, RouteMessage, RouteProtocol, RouteScope};
+use netlink_packet_route::AddressFamily;
+use rtnetlink::{new_connection, Handle, IpVersion};
+
+use crate::{Route, RoutingTable};
+
+/// A live handle onto the kernel's netlink route socket.
+pub struct KernelSync {
+    handle: Handle,
+}
+
+impl KernelSync {
+    /// Open the netlink route socket, spawning its background I/O task onto
+    /// the current tokio runtime.
+    pub fn new() -> Result<Self, String> {
+        let (connection, handle, _) = new_connection().map_err(|e| e.to_string())?;
+        tokio::spawn(connection);
+        Ok(Self { handle })
+    }
+
+    /// Push every route currently in `table` into the kernel FIB as
+    /// `RTM_NEWROUTE`, then remove any kernel route that isn't present in
+    /// `table` as `RTM_DELROUTE`. Kernel-side routes are matched against
+    /// `table` by destination CIDR, so a route the kernel learned through a
+    /// path this crate doesn't track (e.g. a manually-added one) is left
+    /// alone only if its destination also appears in `table`.
+    pub async fn sync_to_kernel(&self, table: &RoutingTable) -> Result<(), String> {
+        let wanted: Vec<&Route> = table.get_all_routes();
+        let wanted_destinations: HashSet<&str> =
+            wanted.iter().map(|route| route.destination.as_str()).collect();
+
+        for route in &wanted {
+            self.push_route(route).await?;
+        }
+
+        for kernel_route in self.import_from_kernel().await? {
+            if !wanted_destinations.contains(kernel_route.destination.as_str()) {
+                self.withdraw_route(&kernel_route).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Dump the kernel's current IPv4 and IPv6 routes (`RTM_GETROUTE`) and
+    /// parse each into a [`Route`]: destination CIDR, gateway, interface
+    /// name (resolved from the kernel's interface index), metric, and a
+    /// protocol tag taken from the route's own protocol field. Routes that
+    /// carry no output interface are skipped.
+    pub async fn import_from_kernel(&self) -> Result<Vec<Route>, String> {
+        let mut routes = Vec::new();
+        for ip_version in [IpVersion::V4, IpVersion::V6] {
+            let mut dump = self.handle.route().get(ip_version).execute();
+            while let Some(message) = dump.try_next().await.map_err(|e| e.to_string())? {
+                if let Some(route) = self.parse_route(&message).await? {
+                    routes.push(route);
+                }
+            }
+        }
+        Ok(routes)
+    }
+
+    /// Install `route` as `RTM_NEWROUTE`, replacing any existing kernel
+    /// route for the same destination.
+    async fn push_route(&self, route: &Route) -> Result<(), String> {
+        let (addr, prefix_len) = parse_cidr(&route.destination)?;
+        let gateway: IpAddr = route.gateway.parse().map_err(|e: std::net::AddrParseError| e.to_string())?;
+
+        let mut request = self.handle.route().add();
+        request = match addr {
+            IpAddr::V4(dest) => request
+                .v4()
+                .destination_prefix(dest, prefix_len)
+                .gateway(match gateway {
+                    IpAddr::V4(gw) => gw,
+                    IpAddr::V6(_) => return Err("IPv6 gateway for an IPv4 destination".to_string()),
+                }),
+            IpAddr::V6(dest) => request
+                .v6()
+                .destination_prefix(dest, prefix_len)
+                .gateway(match gateway {
+                    IpAddr::V6(gw) => gw,
+                    IpAddr::V4(_) => return Err("IPv4 gateway for an IPv6 destination".to_string()),
+                }),
+        };
+        request
+            .priority(route.metric)
+            .replace()
+            .execute()
+            .await
+            .map_err(|e| e.to_string())
+    }
+
+    /// Remove `route` from the kernel FIB as `RTM_DELROUTE`.
+    async fn withdraw_route(&self, route: &Route) -> Result<(), String> {
+        let (addr, prefix_len) = parse_cidr(&route.destination)?;
+        let mut dump = match addr {
+            IpAddr::V4(_) => self.handle.route().get(IpVersion::V4).execute(),
+            IpAddr::V6(_) => self.handle.route().get(IpVersion::V6).execute(),
+        };
+        while let Some(message) = dump.try_next().await.map_err(|e| e.to_string())? {
+            if destination_matches(&message, addr, prefix_len) {
+                self.handle.route().del(message).execute().await.map_err(|e| e.to_string())?;
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+
+    async fn parse_route(&self, message: &RouteMessage) -> Result<Option<Route>, String> {
+        let family = message.header.address_family;
+        let prefix_len = message.header.destination_prefix_length;
+
+        let mut destination = None;
+        let mut gateway = None;
+        let mut out_index = None;
+        let mut metric = None;
+
+        for attr in &message.attributes {
+            match attr {
+                RouteAttribute::Destination(RouteAddress::Inet(addr)) => destination = Some(IpAddr::V4(*addr)),
+                RouteAttribute::Destination(RouteAddress::Inet6(addr)) => destination = Some(IpAddr::V6(*addr)),
+                RouteAttribute::Gateway(RouteAddress::Inet(addr)) => gateway = Some(IpAddr::V4(*addr).to_string()),
+                RouteAttribute::Gateway(RouteAddress::Inet6(addr)) => gateway = Some(IpAddr::V6(*addr).to_string()),
+                RouteAttribute::Oif(index) => out_index = Some(*index),
+                RouteAttribute::Priority(priority) => metric = Some(*priority),
+                _ => {}
+            }
+        }
+
+        // A default route has no RTA_DST attribute at all; its destination
+        // is the family's unspecified address with the header's prefix length.
+        let destination = destination.unwrap_or(match family {
+            AddressFamily::Inet6 => IpAddr::V6(std::net::Ipv6Addr::UNSPECIFIED),
+            _ => IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED),
+        });
+
+        let Some(out_index) = out_index else {
+            return Ok(None);
+        };
+        let interface = self.interface_name(out_index).await?;
+
+        Ok(Some(Route {
+            destination: format!("{}/{}", destination, prefix_len),
+            gateway: gateway.unwrap_or_default(),
+            interface,
+            prefix_length: prefix_len,
+            metric: metric.unwrap_or(0),
+            protocol: protocol_tag(message.header.protocol),
+            is_active: message.header.scope == RouteScope::Universe,
+            expires_at: None,
+            local_pref: None,
+            as_path_len: None,
+            med: None,
+        }))
+    }
+
+    async fn interface_name(&self, index: u32) -> Result<String, String> {
+        let mut links = self.handle.link().get().match_index(index).execute();
+        match links.try_next().await.map_err(|e| e.to_string())? {
+            Some(link) => Ok(link
+                .attributes
+                .iter()
+                .find_map(|attr| match attr {
+                    netlink_packet_route::link::LinkAttribute::IfName(name) => Some(name.clone()),
+                    _ => None,
+                })
+                .unwrap_or_else(|| index.to_string())),
+            None => Ok(index.to_string()),
+        }
+    }
+}
+
+fn protocol_tag(protocol: RouteProtocol) -> String {
+    match protocol {
+        RouteProtocol::Bgp => "bgp",
+        RouteProtocol::Ospf => "ospf",
+        RouteProtocol::Isis => "isis",
+        RouteProtocol::Static => "static",
+        _ => "kernel",
+    }
+    .to_string()
+}
+
+fn destination_matches(message: &RouteMessage, addr: IpAddr, prefix_len: u8) -> bool {
+    if message.header.destination_prefix_length != prefix_len {
+        return false;
+    }
+    message.attributes.iter().any(|attr| match (attr, addr) {
+        (RouteAttribute::Destination(RouteAddress::Inet(dest)), IpAddr::V4(addr)) => *dest == addr,
+        (RouteAttribute::Destination(RouteAddress::Inet6(dest)), IpAddr::V6(addr)) => *dest == addr,
+        _ => false,
+    })
+}
+
+fn parse_cidr(destination: &str) -> Result<(IpAddr, u8), String> {
+    let (addr_part, prefix_part) = destination
+        .split_once('/')
+        .ok_or_else(|| format!("Destination {} is not in network/prefix form", destination))?;
+    let addr: IpAddr = addr_part.parse().map_err(|e: std::net::AddrParseError| e.to_string())?;
+    let prefix_len = prefix_part.parse::<u8>().map_err(|e| e.to_string())?;
+    Ok((addr, prefix_len))
+}
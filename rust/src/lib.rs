@@ -2,27 +2,60 @@ use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use serde::{Deserialize, Serialize};
+
+pub mod connection_validator;
+pub mod flow_table;
+pub mod fragment_reassembly;
+#[cfg(feature = "netlink")]
+pub mod kernel;
+pub mod metric_store;
 pub mod packet_engine;
+pub mod pcap_writer;
 pub mod routing_table;
+pub mod simulation;
+pub mod storage;
 
 // Re-export main components
 pub use packet_engine::PacketEngine;
 pub use routing_table::RoutingTable;
+pub use storage::Backend;
+
+use metric_store::MetricStore;
+use storage::RingBufferBackend;
+
+fn current_timestamp_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64
+}
+
+/// Default capacity of the ring-buffer backend a [`RouterAnalytics`] uses
+/// when constructed with [`RouterAnalytics::new`]; override via
+/// [`RouterAnalytics::with_backend`] for a persistent (e.g. SQLite) backend
+/// or a different retention window.
+const DEFAULT_RING_BUFFER_CAPACITY: usize = 10_000;
 
 /// Router Analytics Engine
 /// High-performance analytics engine for router simulation
 pub struct RouterAnalytics {
     packet_engine: Arc<Mutex<PacketEngine>>,
     routing_table: Arc<Mutex<RoutingTable>>,
-    metrics: Arc<Mutex<HashMap<String, f64>>>,
+    metrics: MetricStore,
+    backend: Arc<dyn Backend>,
 }
 
 impl RouterAnalytics {
     pub fn new() -> Self {
+        Self::with_backend(Arc::new(RingBufferBackend::new(DEFAULT_RING_BUFFER_CAPACITY)))
+    }
+
+    /// Build analytics on top of any [`Backend`], e.g. a [`storage::SqliteBackend`]
+    /// when historical queries need to survive a restart.
+    pub fn with_backend(backend: Arc<dyn Backend>) -> Self {
         Self {
             packet_engine: Arc::new(Mutex::new(PacketEngine::new())),
             routing_table: Arc::new(Mutex::new(RoutingTable::new())),
-            metrics: Arc::new(Mutex::new(HashMap::new())),
+            metrics: MetricStore::new(),
+            backend,
         }
     }
 
@@ -30,6 +63,8 @@ impl RouterAnalytics {
     pub fn process_packet(&self, packet: &Packet) -> Result<(), String> {
         let mut engine = self.packet_engine.lock().map_err(|e| e.to_string())?;
         engine.process_packet(packet)?;
+        drop(engine);
+        self.backend.record_packet(packet)?;
         Ok(())
     }
 
@@ -37,6 +72,8 @@ impl RouterAnalytics {
     pub fn add_route(&self, route: &Route) -> Result<(), String> {
         let mut table = self.routing_table.lock().map_err(|e| e.to_string())?;
         table.add_route(route)?;
+        drop(table);
+        self.backend.record_route(route)?;
         Ok(())
     }
 
@@ -61,28 +98,62 @@ impl RouterAnalytics {
 
     /// Update a metric
     pub fn update_metric(&self, name: &str, value: f64) -> Result<(), String> {
-        let mut metrics = self.metrics.lock().map_err(|e| e.to_string())?;
-        metrics.insert(name.to_string(), value);
+        self.update_metric_tagged(name, value, HashMap::new())
+    }
+
+    /// Update a metric and record it with the given tags, so it can later be
+    /// told apart from same-named samples taken under different conditions
+    /// (e.g. `interface`, `router_id`).
+    pub fn update_metric_tagged(&self, name: &str, value: f64, tags: HashMap<String, String>) -> Result<(), String> {
+        let timestamp = current_timestamp_ms();
+        self.metrics.record(name, timestamp, value, tags.clone());
+        self.backend.record_metric(&MetricData { name: name.to_string(), value, timestamp, tags })?;
         Ok(())
     }
 
-    /// Get all metrics
+    /// Get the latest value of every metric
     pub fn get_metrics(&self) -> Result<HashMap<String, f64>, String> {
-        let metrics = self.metrics.lock().map_err(|e| e.to_string())?;
-        Ok(metrics.clone())
+        Ok(self.metrics.latest_values())
+    }
+
+    /// Override how long samples for `name` are retained, taking precedence
+    /// over the store's default retention window.
+    pub fn set_metric_retention(&self, name: &str, retention_ms: u64) {
+        self.metrics.set_retention(name, retention_ms);
+    }
+
+    /// Average per-second rate of change of `name` over the trailing
+    /// `window_ms`. `None` if fewer than two samples fall in the window.
+    pub fn metric_rate(&self, name: &str, window_ms: u64) -> Option<f64> {
+        self.metrics.rate(name, window_ms, current_timestamp_ms())
+    }
+
+    /// Mean value of `name` over the trailing `window_ms`.
+    pub fn metric_avg(&self, name: &str, window_ms: u64) -> Option<f64> {
+        self.metrics.avg(name, window_ms, current_timestamp_ms())
+    }
+
+    /// `p`-th percentile (0.0-100.0) of `name` over the trailing `window_ms`.
+    pub fn metric_percentile(&self, name: &str, p: f64, window_ms: u64) -> Option<f64> {
+        self.metrics.percentile(name, p, window_ms, current_timestamp_ms())
+    }
+
+    /// Drop metric samples that have aged out of their retention window.
+    /// Intended to be called periodically from a background task.
+    pub fn purge_metrics(&self) {
+        self.metrics.purge(current_timestamp_ms());
     }
 
     /// Reset all statistics
     pub fn reset(&self) -> Result<(), String> {
         let mut engine = self.packet_engine.lock().map_err(|e| e.to_string())?;
         engine.reset();
-        
+
         let mut table = self.routing_table.lock().map_err(|e| e.to_string())?;
         table.reset();
-        
-        let mut metrics = self.metrics.lock().map_err(|e| e.to_string())?;
-        metrics.clear();
-        
+
+        self.metrics.clear();
+
         Ok(())
     }
 }
@@ -132,7 +203,7 @@ impl Packet {
 }
 
 /// Route representation
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Route {
     pub destination: String,
     pub gateway: String,
@@ -141,6 +212,16 @@ pub struct Route {
     pub metric: u32,
     pub protocol: String,
     pub is_active: bool,
+    /// Millis since epoch after which this route is stale, e.g. a TTL a
+    /// dynamic protocol or DHCP lease attached when it learned the route.
+    /// `None` means the route never expires on its own.
+    pub expires_at: Option<u64>,
+    /// BGP LOCAL_PREF, AS_PATH length, and MED, present only on routes
+    /// learned via BGP. Used by [`routing_table::bgp_best_path`] to decide
+    /// between routes that tie on prefix length.
+    pub local_pref: Option<u32>,
+    pub as_path_len: Option<u32>,
+    pub med: Option<u32>,
 }
 
 impl Route {
@@ -160,6 +241,10 @@ impl Route {
             metric,
             protocol,
             is_active: true,
+            expires_at: None,
+            local_pref: None,
+            as_path_len: None,
+            med: None,
         }
     }
 }
@@ -342,6 +427,20 @@ impl Default for RouterAnalytics {
     }
 }
 
+impl AnalyticsQuery for RouterAnalytics {
+    fn query_packets(&self, filter: &PacketFilter) -> Result<Vec<Packet>, String> {
+        self.backend.query_packets(filter)
+    }
+
+    fn query_routes(&self, filter: &RouteFilter) -> Result<Vec<Route>, String> {
+        self.backend.query_routes(filter)
+    }
+
+    fn query_metrics(&self, filter: &MetricFilter) -> Result<Vec<MetricData>, String> {
+        Ok(self.metrics.query(filter))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
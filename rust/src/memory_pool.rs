@@ -2,12 +2,34 @@ use std::collections::VecDeque;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
 
-/// Memory pool for efficient packet buffer allocation
+/// Smallest size class holds buffers of `1 << MIN_CLASS_SHIFT` bytes (64 B).
+const MIN_CLASS_SHIFT: u32 = 6;
+
+/// Size class a `size`-byte request falls into: `class(size) = max(0, ceil(log2(size)) - MIN_CLASS_SHIFT)`.
+/// Buckets buffers by power-of-two capacity so `get_packet`/`return_packet`
+/// are O(1) pops/pushes on the right deque instead of a linear scan.
+fn size_class(size: usize) -> usize {
+    let size = size.max(1);
+    let ceil_log2 = (usize::BITS - (size - 1).leading_zeros()) as i64;
+    (ceil_log2 - MIN_CLASS_SHIFT as i64).max(0) as usize
+}
+
+/// True capacity every buffer in size class `class` is allocated with.
+fn class_capacity(class: usize) -> usize {
+    1usize << (class as u32 + MIN_CLASS_SHIFT)
+}
+
+/// Memory pool for efficient packet buffer allocation, organized into
+/// segregated free lists keyed by power-of-two size class so a request never
+/// has to scan past buffers that are the wrong size to find one that fits.
 pub struct MemoryPool {
-    buffers: Arc<Mutex<VecDeque<PacketBuffer>>>,
-    buffer_size: usize,
+    classes: Arc<Mutex<Vec<VecDeque<PacketBuffer>>>>,
+    class_stats: Arc<Mutex<Vec<ClassStats>>>,
+    /// Largest size class this pool will allocate or accept back; buffers
+    /// that would fall into a coarser class are freed instead of pooled.
+    max_class: usize,
     max_pool_size: usize,
-    created_buffers: Arc<Mutex<usize>>,
+    next_id: Arc<Mutex<u64>>,
     total_allocations: Arc<Mutex<usize>>,
     total_deallocations: Arc<Mutex<usize>>,
 }
@@ -73,13 +95,18 @@ impl PacketBuffer {
 }
 
 impl MemoryPool {
-    /// Create a new memory pool
-    pub fn new(max_pool_size: usize, buffer_size: usize) -> Self {
+    /// Create a new memory pool. `max_pool_size` caps how many buffers each
+    /// size class retains; `max_buffer_size` is the ceiling class, sized to
+    /// cover it, past which `return_packet` frees a buffer instead of
+    /// pooling it.
+    pub fn new(max_pool_size: usize, max_buffer_size: usize) -> Self {
+        let max_class = size_class(max_buffer_size);
         Self {
-            buffers: Arc::new(Mutex::new(VecDeque::new())),
-            buffer_size,
+            classes: Arc::new(Mutex::new((0..=max_class).map(|_| VecDeque::new()).collect())),
+            class_stats: Arc::new(Mutex::new(vec![ClassStats::default(); max_class + 1])),
+            max_class,
             max_pool_size,
-            created_buffers: Arc::new(Mutex::new(0)),
+            next_id: Arc::new(Mutex::new(0)),
             total_allocations: Arc::new(Mutex::new(0)),
             total_deallocations: Arc::new(Mutex::new(0)),
         }
@@ -87,88 +114,130 @@ impl MemoryPool {
 
     /// Get a packet buffer from the pool
     pub fn get_packet(&self, size: usize) -> Result<PacketBuffer, String> {
-        let mut buffers = self.buffers.lock().unwrap();
-        
-        // Try to find a suitable buffer in the pool
-        if let Some(index) = buffers.iter().position(|buf| buf.data.len() >= size) {
-            let mut buffer = buffers.remove(index).unwrap();
+        let class = size_class(size).min(self.max_class);
+        *self.total_allocations.lock().unwrap() += 1;
+
+        let popped = self.classes.lock().unwrap()[class].pop_front();
+        if let Some(mut buffer) = popped {
             buffer.resize(size);
             buffer.last_used = Instant::now();
-            
-            *self.total_allocations.lock().unwrap() += 1;
+            self.class_stats.lock().unwrap()[class].reused += 1;
             return Ok(buffer);
         }
 
-        // Create a new buffer if pool is empty or no suitable buffer found
-        let buffer_id = *self.created_buffers.lock().unwrap() as u64;
-        let mut buffer = PacketBuffer::new(size.max(self.buffer_size), buffer_id);
+        let buffer_id = {
+            let mut next_id = self.next_id.lock().unwrap();
+            let id = *next_id;
+            *next_id += 1;
+            id
+        };
+
+        let mut buffer = PacketBuffer::new(class_capacity(class), buffer_id);
         buffer.resize(size);
-        
-        *self.created_buffers.lock().unwrap() += 1;
-        *self.total_allocations.lock().unwrap() += 1;
-        
+        self.class_stats.lock().unwrap()[class].created += 1;
+
         Ok(buffer)
     }
 
     /// Return a packet buffer to the pool
     pub fn return_packet(&self, mut buffer: PacketBuffer) {
-        // Don't return buffers that are too old or too small
-        if buffer.is_expired(Duration::from_secs(300)) || buffer.data.len() < self.buffer_size {
+        if buffer.is_expired(Duration::from_secs(300)) {
             *self.total_deallocations.lock().unwrap() += 1;
             return;
         }
 
-        let mut buffers = self.buffers.lock().unwrap();
-        
-        // Don't exceed max pool size
-        if buffers.len() >= self.max_pool_size {
+        // Recompute the class from the buffer's true capacity, not the
+        // logical `size` it was last resized to, so a buffer returned to
+        // class k is guaranteed to have capacity >= 1<<k for any future
+        // `get_packet` landing in that class.
+        let class = size_class(buffer.data.len());
+        if class > self.max_class {
+            *self.total_deallocations.lock().unwrap() += 1;
+            return;
+        }
+
+        let mut classes = self.classes.lock().unwrap();
+        if classes[class].len() >= self.max_pool_size {
             *self.total_deallocations.lock().unwrap() += 1;
             return;
         }
 
-        // Reset buffer for reuse
-        buffer.size = self.buffer_size;
         buffer.data.fill(0);
+        buffer.size = buffer.data.len();
         buffer.last_used = Instant::now();
-        
-        buffers.push_back(buffer);
+
+        classes[class].push_back(buffer);
         *self.total_deallocations.lock().unwrap() += 1;
     }
 
     /// Get pool statistics
     pub fn get_stats(&self) -> PoolStats {
-        let buffers = self.buffers.lock().unwrap();
-        let created = *self.created_buffers.lock().unwrap();
+        let classes = self.classes.lock().unwrap();
+        let class_stats = self.class_stats.lock().unwrap();
         let allocations = *self.total_allocations.lock().unwrap();
         let deallocations = *self.total_deallocations.lock().unwrap();
 
+        let pool_size: usize = classes.iter().map(VecDeque::len).sum();
+        let per_class: Vec<ClassStats> = class_stats
+            .iter()
+            .enumerate()
+            .map(|(class, stats)| ClassStats {
+                capacity: class_capacity(class),
+                created: stats.created,
+                reused: stats.reused,
+            })
+            .collect();
+        let created: usize = per_class.iter().map(|c| c.created).sum();
+
         PoolStats {
-            pool_size: buffers.len(),
+            pool_size,
             max_pool_size: self.max_pool_size,
-            buffer_size: self.buffer_size,
+            max_buffer_size: class_capacity(self.max_class),
             created_buffers: created,
             total_allocations: allocations,
             total_deallocations: deallocations,
-            active_buffers: created - deallocations,
+            active_buffers: created.saturating_sub(deallocations),
+            per_class,
         }
     }
 
     /// Clean up expired and stale buffers
     pub fn cleanup(&self) {
-        let mut buffers = self.buffers.lock().unwrap();
-        let now = Instant::now();
+        let mut classes = self.classes.lock().unwrap();
         let max_age = Duration::from_secs(300);
         let max_idle = Duration::from_secs(60);
 
-        buffers.retain(|buf| {
-            !buf.is_expired(max_age) && !buf.is_stale(max_idle)
-        });
+        for queue in classes.iter_mut() {
+            queue.retain(|buf| !buf.is_expired(max_age) && !buf.is_stale(max_idle));
+        }
     }
 
     /// Force cleanup of all buffers
     pub fn clear(&self) {
-        let mut buffers = self.buffers.lock().unwrap();
-        buffers.clear();
+        let mut classes = self.classes.lock().unwrap();
+        for queue in classes.iter_mut() {
+            queue.clear();
+        }
+    }
+}
+
+/// Per-size-class allocation counters, as exposed via [`PoolStats::per_class`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClassStats {
+    pub capacity: usize,
+    pub created: usize,
+    pub reused: usize,
+}
+
+impl ClassStats {
+    /// Percentage of `get_packet` calls landing in this class that were
+    /// served from the free list rather than freshly allocated.
+    pub fn hit_rate(&self) -> f64 {
+        let total = self.created + self.reused;
+        if total == 0 {
+            return 0.0;
+        }
+        self.reused as f64 / total as f64 * 100.0
     }
 }
 
@@ -177,21 +246,25 @@ impl MemoryPool {
 pub struct PoolStats {
     pub pool_size: usize,
     pub max_pool_size: usize,
-    pub buffer_size: usize,
+    pub max_buffer_size: usize,
     pub created_buffers: usize,
     pub total_allocations: usize,
     pub total_deallocations: usize,
     pub active_buffers: usize,
+    pub per_class: Vec<ClassStats>,
 }
 
 impl PoolStats {
     /// Get allocation hit rate (percentage of allocations served from pool)
+    /// across all size classes. See [`ClassStats::hit_rate`] for a per-class
+    /// breakdown.
     pub fn hit_rate(&self) -> f64 {
-        if self.total_allocations == 0 {
+        let total: usize = self.per_class.iter().map(|c| c.created + c.reused).sum();
+        if total == 0 {
             return 0.0;
         }
-        let pool_hits = self.total_allocations - self.active_buffers;
-        pool_hits as f64 / self.total_allocations as f64 * 100.0
+        let reused: usize = self.per_class.iter().map(|c| c.reused).sum();
+        reused as f64 / total as f64 * 100.0
     }
 
     /// Get pool utilization (percentage of max pool size used)
@@ -212,18 +285,17 @@ mod tests {
         let pool = MemoryPool::new(100, 1500);
         let stats = pool.get_stats();
         assert_eq!(stats.max_pool_size, 100);
-        assert_eq!(stats.buffer_size, 1500);
         assert_eq!(stats.pool_size, 0);
     }
 
     #[test]
     fn test_packet_allocation() {
         let pool = MemoryPool::new(10, 1500);
-        
+
         let buffer = pool.get_packet(1000).unwrap();
         assert_eq!(buffer.size, 1000);
-        assert_eq!(buffer.data.len(), 1500); // Allocated with max size
-        
+        assert_eq!(buffer.data.len(), 1024); // Rounded up to the 1024 B size class
+
         let stats = pool.get_stats();
         assert_eq!(stats.total_allocations, 1);
         assert_eq!(stats.active_buffers, 1);
@@ -232,10 +304,10 @@ mod tests {
     #[test]
     fn test_packet_return() {
         let pool = MemoryPool::new(10, 1500);
-        
+
         let buffer = pool.get_packet(1000).unwrap();
         pool.return_packet(buffer);
-        
+
         let stats = pool.get_stats();
         assert_eq!(stats.pool_size, 1);
         assert_eq!(stats.total_deallocations, 1);
@@ -244,39 +316,84 @@ mod tests {
     #[test]
     fn test_pool_reuse() {
         let pool = MemoryPool::new(10, 1500);
-        
+
         // Allocate and return a buffer
         let buffer1 = pool.get_packet(1000).unwrap();
         let buffer_id1 = buffer1.id;
         pool.return_packet(buffer1);
-        
-        // Allocate again - should reuse the same buffer
+
+        // Allocate again from the same size class - should reuse the buffer
+        // just returned instead of creating a new one.
         let buffer2 = pool.get_packet(1000).unwrap();
         let buffer_id2 = buffer2.id;
-        
-        // Should be different IDs since we're creating new buffers
-        // (in a real implementation, we might reuse the same buffer)
-        assert_ne!(buffer_id1, buffer_id2);
+
+        assert_eq!(buffer_id1, buffer_id2);
+
+        let stats = pool.get_stats();
+        assert_eq!(stats.per_class[size_class(1000)].created, 1);
+        assert_eq!(stats.per_class[size_class(1000)].reused, 1);
     }
 
     #[test]
     fn test_pool_cleanup() {
         let pool = MemoryPool::new(10, 1500);
-        
+
         // Fill the pool
         for _ in 0..5 {
             let buffer = pool.get_packet(1000).unwrap();
             pool.return_packet(buffer);
         }
-        
+
         let stats_before = pool.get_stats();
         assert_eq!(stats_before.pool_size, 5);
-        
+
         // Cleanup
         pool.cleanup();
-        
+
         let stats_after = pool.get_stats();
         // Pool size should remain the same since buffers are not expired
         assert_eq!(stats_after.pool_size, 5);
     }
+
+    #[test]
+    fn size_class_buckets_by_power_of_two_capacity() {
+        assert_eq!(size_class(1), 0);
+        assert_eq!(size_class(64), 0);
+        assert_eq!(size_class(65), 1);
+        assert_eq!(size_class(128), 1);
+        assert_eq!(size_class(129), 2);
+
+        assert_eq!(class_capacity(0), 64);
+        assert_eq!(class_capacity(1), 128);
+    }
+
+    #[test]
+    fn returned_buffer_always_covers_its_recomputed_class() {
+        let pool = MemoryPool::new(10, 4096);
+
+        // A request for 200 bytes lands in the 256 B class...
+        let buffer = pool.get_packet(200).unwrap();
+        assert_eq!(buffer.data.len(), class_capacity(size_class(200)));
+        pool.return_packet(buffer);
+
+        // ...so a later 256-byte request is served from that class without
+        // allocating a new buffer.
+        let reused = pool.get_packet(256).unwrap();
+        let stats = pool.get_stats();
+        assert_eq!(stats.per_class[size_class(256)].reused, 1);
+        assert!(reused.data.len() >= 256);
+    }
+
+    #[test]
+    fn oversized_buffers_are_not_pooled() {
+        let pool = MemoryPool::new(10, 512);
+
+        let buffer = pool.get_packet(4096).unwrap();
+        pool.return_packet(buffer);
+
+        // 4096 bytes falls into a class beyond the pool's 512-byte ceiling,
+        // so nothing was pooled.
+        let stats = pool.get_stats();
+        assert_eq!(stats.pool_size, 0);
+    }
 }
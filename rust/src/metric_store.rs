@@ -0,0 +1,228 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use crate::{MetricData, MetricFilter};
+
+/// Default retention window (ms) for a metric that hasn't been given an
+/// explicit override via [`MetricStore::set_retention`].
+const DEFAULT_RETENTION_MS: u64 = 5 * 60 * 1000;
+
+/// Upper bound on samples kept per metric name, independent of the retention
+/// window, so a high-frequency metric can't grow unbounded memory before its
+/// oldest samples age out.
+const DEFAULT_MAX_SAMPLES_PER_METRIC: usize = 10_000;
+
+#[derive(Debug, Clone)]
+struct Sample {
+    timestamp: u64,
+    value: f64,
+    tags: HashMap<String, String>,
+}
+
+/// Bounded time-series store for [`crate::RouterAnalytics`] metrics: each
+/// name keeps a window of recent `(timestamp, value, tags)` samples, purged
+/// by epoch (wall-clock time) on insert and via a periodic [`Self::purge`]
+/// sweep, rather than kept forever like a flat `HashMap<String, f64>`.
+/// Mirrors how gossip membership tables expire entries once their epoch
+/// falls outside a retention window.
+pub struct MetricStore {
+    samples: Mutex<HashMap<String, VecDeque<Sample>>>,
+    retention_overrides: Mutex<HashMap<String, u64>>,
+    default_retention_ms: u64,
+    max_samples_per_metric: usize,
+}
+
+impl MetricStore {
+    pub fn new() -> Self {
+        Self {
+            samples: Mutex::new(HashMap::new()),
+            retention_overrides: Mutex::new(HashMap::new()),
+            default_retention_ms: DEFAULT_RETENTION_MS,
+            max_samples_per_metric: DEFAULT_MAX_SAMPLES_PER_METRIC,
+        }
+    }
+
+    pub fn with_retention(default_retention_ms: u64) -> Self {
+        Self { default_retention_ms, ..Self::new() }
+    }
+
+    /// Override the retention window for one metric name, taking precedence
+    /// over the store's default.
+    pub fn set_retention(&self, name: &str, retention_ms: u64) {
+        self.retention_overrides.lock().unwrap().insert(name.to_string(), retention_ms);
+    }
+
+    fn retention_for(&self, name: &str) -> u64 {
+        self.retention_overrides.lock().unwrap().get(name).copied().unwrap_or(self.default_retention_ms)
+    }
+
+    /// Record a sample, then drop anything in this metric's window that has
+    /// since fallen outside its retention period (evaluated as of `timestamp`).
+    pub fn record(&self, name: &str, timestamp: u64, value: f64, tags: HashMap<String, String>) {
+        let retention_ms = self.retention_for(name);
+        let mut samples = self.samples.lock().unwrap();
+        let window = samples.entry(name.to_string()).or_default();
+        window.push_back(Sample { timestamp, value, tags });
+        Self::purge_window(window, timestamp, retention_ms, self.max_samples_per_metric);
+    }
+
+    fn purge_window(window: &mut VecDeque<Sample>, now: u64, retention_ms: u64, max_samples: usize) {
+        let cutoff = now.saturating_sub(retention_ms);
+        while window.front().is_some_and(|s| s.timestamp < cutoff) {
+            window.pop_front();
+        }
+        while window.len() > max_samples {
+            window.pop_front();
+        }
+    }
+
+    /// Drop samples that have aged out of their metric's retention window,
+    /// evaluated as of `now`. Call periodically as a background sweep;
+    /// `record` already purges its own metric's window on every insert.
+    pub fn purge(&self, now: u64) {
+        let mut samples = self.samples.lock().unwrap();
+        let overrides = self.retention_overrides.lock().unwrap();
+        for (name, window) in samples.iter_mut() {
+            let retention_ms = overrides.get(name).copied().unwrap_or(self.default_retention_ms);
+            Self::purge_window(window, now, retention_ms, self.max_samples_per_metric);
+        }
+    }
+
+    /// Snapshot of the latest value per metric name, mirroring the old
+    /// `RouterAnalytics::get_metrics` flat-map API.
+    pub fn latest_values(&self) -> HashMap<String, f64> {
+        self.samples
+            .lock()
+            .unwrap()
+            .iter()
+            .filter_map(|(name, window)| window.back().map(|s| (name.clone(), s.value)))
+            .collect()
+    }
+
+    fn samples_in_window(&self, name: &str, window_ms: u64, now: u64) -> Vec<Sample> {
+        let cutoff = now.saturating_sub(window_ms);
+        self.samples
+            .lock()
+            .unwrap()
+            .get(name)
+            .map(|window| window.iter().filter(|s| s.timestamp >= cutoff).cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// Average per-second rate of change of `name` over the trailing
+    /// `window_ms`: `(last - first) / elapsed_seconds`. `None` if fewer than
+    /// two samples fall in the window.
+    pub fn rate(&self, name: &str, window_ms: u64, now: u64) -> Option<f64> {
+        let samples = self.samples_in_window(name, window_ms, now);
+        let (first, last) = (samples.first()?, samples.last()?);
+        let elapsed_ms = last.timestamp.saturating_sub(first.timestamp);
+        if elapsed_ms == 0 {
+            return None;
+        }
+        Some((last.value - first.value) / (elapsed_ms as f64 / 1000.0))
+    }
+
+    /// Mean value of `name` over the trailing `window_ms`.
+    pub fn avg(&self, name: &str, window_ms: u64, now: u64) -> Option<f64> {
+        let samples = self.samples_in_window(name, window_ms, now);
+        if samples.is_empty() {
+            return None;
+        }
+        Some(samples.iter().map(|s| s.value).sum::<f64>() / samples.len() as f64)
+    }
+
+    /// `p`-th percentile (0.0-100.0) of `name` over the trailing `window_ms`,
+    /// via the nearest-rank method.
+    pub fn percentile(&self, name: &str, p: f64, window_ms: u64, now: u64) -> Option<f64> {
+        let mut values: Vec<f64> = self.samples_in_window(name, window_ms, now).into_iter().map(|s| s.value).collect();
+        if values.is_empty() {
+            return None;
+        }
+        values.sort_by(|a, b| a.total_cmp(b));
+        let rank = ((p / 100.0 * values.len() as f64).ceil() as usize).clamp(1, values.len());
+        Some(values[rank - 1])
+    }
+
+    /// Retained samples matching `filter`, as [`MetricData`] points.
+    pub fn query(&self, filter: &MetricFilter) -> Vec<MetricData> {
+        self.samples
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(name, _)| filter.name.as_deref().map_or(true, |n| n == name.as_str()))
+            .flat_map(|(name, window)| {
+                window.iter().filter(move |s| {
+                    filter.time_range.map_or(true, |(start, end)| s.timestamp >= start && s.timestamp <= end)
+                        && filter.value_range.map_or(true, |(low, high)| s.value >= low && s.value <= high)
+                })
+                .map(move |s| MetricData { name: name.clone(), value: s.value, timestamp: s.timestamp, tags: s.tags.clone() })
+            })
+            .collect()
+    }
+
+    /// Remove all retained samples, e.g. as part of `RouterAnalytics::reset`.
+    pub fn clear(&self) {
+        self.samples.lock().unwrap().clear();
+    }
+}
+
+impl Default for MetricStore {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn latest_values_reports_the_most_recent_sample_per_metric() {
+        let store = MetricStore::new();
+        store.record("cpu_usage", 100, 10.0, HashMap::new());
+        store.record("cpu_usage", 200, 20.0, HashMap::new());
+
+        assert_eq!(store.latest_values().get("cpu_usage"), Some(&20.0));
+    }
+
+    #[test]
+    fn samples_older_than_retention_are_purged_on_insert() {
+        let store = MetricStore::with_retention(1000);
+        store.record("cpu_usage", 0, 1.0, HashMap::new());
+        store.record("cpu_usage", 5000, 2.0, HashMap::new());
+
+        let filtered = store.query(&MetricFilter { name: Some("cpu_usage".to_string()), time_range: None, value_range: None });
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].value, 2.0);
+    }
+
+    #[test]
+    fn rate_is_change_over_elapsed_seconds() {
+        let store = MetricStore::new();
+        store.record("packets_total", 0, 0.0, HashMap::new());
+        store.record("packets_total", 2000, 200.0, HashMap::new());
+
+        assert_eq!(store.rate("packets_total", 10_000, 2000), Some(100.0));
+    }
+
+    #[test]
+    fn avg_and_percentile_over_a_window() {
+        let store = MetricStore::new();
+        for (i, value) in [10.0, 20.0, 30.0, 40.0].into_iter().enumerate() {
+            store.record("latency_ms", i as u64 * 100, value, HashMap::new());
+        }
+
+        assert_eq!(store.avg("latency_ms", 10_000, 300), Some(25.0));
+        assert_eq!(store.percentile("latency_ms", 50.0, 10_000, 300), Some(20.0));
+    }
+
+    #[test]
+    fn per_metric_retention_override_takes_precedence() {
+        let store = MetricStore::new();
+        store.set_retention("short_lived", 100);
+        store.record("short_lived", 0, 1.0, HashMap::new());
+        store.purge(500);
+
+        assert!(store.latest_values().get("short_lived").is_none());
+    }
+}
@@ -1,6 +1,52 @@
+use std::net::SocketAddr;
 use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::{Duration, Instant};
+use dashmap::DashMap;
 use serde::{Serialize, Deserialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+/// Upper bound (in nanoseconds) of each latency histogram bucket, exponential
+/// from 100ns to ~1s. The final bucket is implicitly "+Inf".
+const LATENCY_BUCKET_BOUNDS_NS: [u64; 10] = [
+    100, 1_000, 10_000, 100_000, 1_000_000, 10_000_000, 100_000_000, 300_000_000, 1_000_000_000,
+    u64::MAX,
+];
+
+/// Turn per-bucket sample counts into `(bound, cumulative_count)` pairs, matching
+/// Prometheus histogram bucket semantics (each bucket counts everything <= its bound).
+fn cumulative_histogram(per_bucket_counts: [u64; LATENCY_BUCKET_BOUNDS_NS.len()]) -> Vec<(u64, u64)> {
+    let mut running = 0u64;
+    LATENCY_BUCKET_BOUNDS_NS
+        .iter()
+        .zip(per_bucket_counts)
+        .map(|(&bound, count)| {
+            running += count;
+            (bound, running)
+        })
+        .collect()
+}
+
+/// Estimate the `quantile` (e.g. 0.99 for p99) latency by walking `histogram`'s
+/// cumulative bucket counts and returning the bound of the first bucket whose
+/// count reaches `quantile * total`. Accurate to the bucket's upper bound, not
+/// the exact sample -- the tradeoff for O(1) lock-free recording.
+fn estimate_quantile(histogram: &[(u64, u64)], quantile: f64) -> u64 {
+    let Some(&(_, total)) = histogram.last() else {
+        return 0;
+    };
+    if total == 0 {
+        return 0;
+    }
+
+    let target = (quantile * total as f64).ceil() as u64;
+    histogram
+        .iter()
+        .find(|&&(_, cumulative)| cumulative >= target)
+        .map(|&(bound, _)| bound)
+        .unwrap_or(0)
+}
 
 /// Performance metrics collector
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,6 +68,12 @@ pub struct Metrics {
     pub routing_table_size: u64,
     pub active_neighbors: u32,
     pub active_interfaces: u32,
+    /// `(bucket_upper_bound_ns, cumulative_count)` pairs, last bound is `u64::MAX` ("+Inf")
+    pub latency_histogram: Vec<(u64, u64)>,
+    pub p50_latency_ns: u64,
+    pub p90_latency_ns: u64,
+    pub p99_latency_ns: u64,
+    pub p999_latency_ns: u64,
 }
 
 impl Default for Metrics {
@@ -44,6 +96,11 @@ impl Default for Metrics {
             routing_table_size: 0,
             active_neighbors: 0,
             active_interfaces: 0,
+            latency_histogram: Vec::new(),
+            p50_latency_ns: 0,
+            p90_latency_ns: 0,
+            p99_latency_ns: 0,
+            p999_latency_ns: 0,
         }
     }
 }
@@ -61,6 +118,9 @@ pub struct MetricsCollector {
     latency_count: AtomicU64,
     max_latency: AtomicU64,
     min_latency: AtomicU64,
+    /// One per-bucket sample counter per bound in `LATENCY_BUCKET_BOUNDS_NS`, incremented
+    /// with relaxed `fetch_add` so recording stays lock-free and allocation-free.
+    latency_buckets: [AtomicU64; LATENCY_BUCKET_BOUNDS_NS.len()],
 }
 
 impl MetricsCollector {
@@ -78,6 +138,7 @@ impl MetricsCollector {
             latency_count: AtomicU64::new(0),
             max_latency: AtomicU64::new(0),
             min_latency: AtomicU64::new(0),
+            latency_buckets: std::array::from_fn(|_| AtomicU64::new(0)),
         }
     }
 
@@ -143,6 +204,25 @@ impl MetricsCollector {
                 break;
             }
         }
+
+        let bucket = LATENCY_BUCKET_BOUNDS_NS
+            .iter()
+            .position(|&bound| latency_ns <= bound)
+            .unwrap_or(LATENCY_BUCKET_BOUNDS_NS.len() - 1);
+        self.latency_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Raw `(sum, count)` of recorded latencies, for merging averages across shards.
+    fn latency_sum_count(&self) -> (u64, u64) {
+        (
+            self.latency_sum.load(Ordering::Relaxed),
+            self.latency_count.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Per-bucket (non-cumulative) sample counts, aligned with `LATENCY_BUCKET_BOUNDS_NS`.
+    fn latency_buckets(&self) -> [u64; LATENCY_BUCKET_BOUNDS_NS.len()] {
+        std::array::from_fn(|i| self.latency_buckets[i].load(Ordering::Relaxed))
     }
 
     /// Get current metrics
@@ -181,6 +261,12 @@ impl MetricsCollector {
         let max_latency_ns = self.max_latency.load(Ordering::Relaxed);
         let min_latency_ns = self.min_latency.load(Ordering::Relaxed);
 
+        let latency_histogram = cumulative_histogram(self.latency_buckets());
+        let p50_latency_ns = estimate_quantile(&latency_histogram, 0.50);
+        let p90_latency_ns = estimate_quantile(&latency_histogram, 0.90);
+        let p99_latency_ns = estimate_quantile(&latency_histogram, 0.99);
+        let p999_latency_ns = estimate_quantile(&latency_histogram, 0.999);
+
         Metrics {
             packets_processed,
             bytes_processed,
@@ -199,6 +285,11 @@ impl MetricsCollector {
             routing_table_size: 0, // Would be populated by routing table
             active_neighbors: 0, // Would be populated by protocol handlers
             active_interfaces: 0, // Would be populated by interface manager
+            latency_histogram,
+            p50_latency_ns,
+            p90_latency_ns,
+            p99_latency_ns,
+            p999_latency_ns,
         }
     }
 
@@ -214,6 +305,9 @@ impl MetricsCollector {
         self.latency_count.store(0, Ordering::Relaxed);
         self.max_latency.store(0, Ordering::Relaxed);
         self.min_latency.store(0, Ordering::Relaxed);
+        for bucket in &self.latency_buckets {
+            bucket.store(0, Ordering::Relaxed);
+        }
     }
 }
 
@@ -223,6 +317,173 @@ impl Default for MetricsCollector {
     }
 }
 
+/// Registry of one `MetricsCollector` per worker id, so per-worker imbalance is
+/// visible instead of being hidden behind a single global rollup.
+pub struct MetricsRegistry {
+    workers: DashMap<usize, Arc<MetricsCollector>>,
+}
+
+impl MetricsRegistry {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self { workers: DashMap::new() })
+    }
+
+    /// Get (creating if necessary) the collector for `worker_id`.
+    pub fn worker(&self, worker_id: usize) -> Arc<MetricsCollector> {
+        self.workers
+            .entry(worker_id)
+            .or_insert_with(|| Arc::new(MetricsCollector::new()))
+            .clone()
+    }
+
+    /// Per-worker snapshots, labeled by worker id.
+    pub fn per_worker_metrics(&self) -> Vec<(usize, Metrics)> {
+        self.workers
+            .iter()
+            .map(|entry| (*entry.key(), entry.value().get_metrics()))
+            .collect()
+    }
+
+    /// Merged global view across every worker, equivalent to what a single
+    /// shared `MetricsCollector::get_metrics()` would have reported.
+    pub fn merged_metrics(&self) -> Metrics {
+        let mut merged = Metrics::default();
+        let mut latency_sum = 0u64;
+        let mut latency_count = 0u64;
+        let mut bucket_totals = [0u64; LATENCY_BUCKET_BOUNDS_NS.len()];
+        let mut uptime_f64 = 0.0f64;
+
+        for entry in self.workers.iter() {
+            let collector = entry.value();
+            merged.packets_processed += collector.packets_processed.load(Ordering::Relaxed);
+            merged.bytes_processed += collector.bytes_processed.load(Ordering::Relaxed);
+            merged.packets_dropped += collector.packets_dropped.load(Ordering::Relaxed);
+            merged.packets_forwarded += collector.packets_forwarded.load(Ordering::Relaxed);
+            merged.packets_routed += collector.packets_routed.load(Ordering::Relaxed);
+            merged.errors += collector.errors.load(Ordering::Relaxed);
+            merged.max_latency_ns = merged.max_latency_ns.max(collector.max_latency.load(Ordering::Relaxed));
+            let min = collector.min_latency.load(Ordering::Relaxed);
+            if min > 0 && (merged.min_latency_ns == 0 || min < merged.min_latency_ns) {
+                merged.min_latency_ns = min;
+            }
+
+            let (sum, count) = collector.latency_sum_count();
+            latency_sum += sum;
+            latency_count += count;
+            for (total, count) in bucket_totals.iter_mut().zip(collector.latency_buckets()) {
+                *total += count;
+            }
+
+            uptime_f64 = uptime_f64.max(collector.start_time.elapsed().as_secs_f64());
+        }
+
+        merged.uptime_seconds = uptime_f64 as u64;
+        merged.average_latency_ns = if latency_count > 0 { latency_sum / latency_count } else { 0 };
+        merged.packets_per_second = if uptime_f64 > 0.0 { merged.packets_processed as f64 / uptime_f64 } else { 0.0 };
+        merged.bytes_per_second = if uptime_f64 > 0.0 { merged.bytes_processed as f64 / uptime_f64 } else { 0.0 };
+        merged.latency_histogram = cumulative_histogram(bucket_totals);
+        merged.p50_latency_ns = estimate_quantile(&merged.latency_histogram, 0.50);
+        merged.p90_latency_ns = estimate_quantile(&merged.latency_histogram, 0.90);
+        merged.p99_latency_ns = estimate_quantile(&merged.latency_histogram, 0.99);
+        merged.p999_latency_ns = estimate_quantile(&merged.latency_histogram, 0.999);
+
+        merged
+    }
+
+    /// Render every worker's metrics plus the merged total in Prometheus text
+    /// exposition format (see https://prometheus.io/docs/instrumenting/exposition_formats/).
+    pub fn render_prometheus(&self) -> String {
+        let mut out = String::new();
+        out.push_str("# HELP router_packets_processed_total Packets processed\n");
+        out.push_str("# TYPE router_packets_processed_total counter\n");
+        for (worker_id, metrics) in self.per_worker_metrics() {
+            out.push_str(&format!(
+                "router_packets_processed_total{{worker=\"{worker_id}\"}} {}\n",
+                metrics.packets_processed
+            ));
+        }
+
+        out.push_str("# HELP router_bytes_processed_total Bytes processed\n");
+        out.push_str("# TYPE router_bytes_processed_total counter\n");
+        for (worker_id, metrics) in self.per_worker_metrics() {
+            out.push_str(&format!(
+                "router_bytes_processed_total{{worker=\"{worker_id}\"}} {}\n",
+                metrics.bytes_processed
+            ));
+        }
+
+        out.push_str("# HELP router_packets_dropped_total Packets dropped\n");
+        out.push_str("# TYPE router_packets_dropped_total counter\n");
+        for (worker_id, metrics) in self.per_worker_metrics() {
+            out.push_str(&format!(
+                "router_packets_dropped_total{{worker=\"{worker_id}\"}} {}\n",
+                metrics.packets_dropped
+            ));
+        }
+
+        out.push_str("# HELP router_errors_total Processing errors\n");
+        out.push_str("# TYPE router_errors_total counter\n");
+        for (worker_id, metrics) in self.per_worker_metrics() {
+            out.push_str(&format!(
+                "router_errors_total{{worker=\"{worker_id}\"}} {}\n",
+                metrics.errors
+            ));
+        }
+
+        out.push_str("# HELP router_latency_seconds Packet processing latency\n");
+        out.push_str("# TYPE router_latency_seconds histogram\n");
+        for (worker_id, metrics) in self.per_worker_metrics() {
+            for (bound_ns, count) in &metrics.latency_histogram {
+                let le = if *bound_ns == u64::MAX {
+                    "+Inf".to_string()
+                } else {
+                    format!("{}", *bound_ns as f64 / 1_000_000_000.0)
+                };
+                out.push_str(&format!(
+                    "router_latency_seconds_bucket{{worker=\"{worker_id}\",le=\"{le}\"}} {count}\n"
+                ));
+            }
+        }
+
+        out
+    }
+}
+
+/// Serve `/metrics` over plain HTTP until the listener is dropped or errors out.
+pub async fn serve_metrics(registry: Arc<MetricsRegistry>, addr: SocketAddr) -> std::io::Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    loop {
+        let (mut socket, _) = listener.accept().await?;
+        let registry = registry.clone();
+        tokio::spawn(async move {
+            let mut buf = [0u8; 1024];
+            // Only the request line is needed to decide path; headers/body are ignored.
+            if socket.read(&mut buf).await.is_err() {
+                return;
+            }
+
+            let request_line = String::from_utf8_lossy(&buf);
+            let body = if request_line.starts_with("GET /metrics") {
+                registry.render_prometheus()
+            } else {
+                String::new()
+            };
+
+            let response = if body.is_empty() {
+                "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n".to_string()
+            } else {
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\r\n{}",
+                    body.len(),
+                    body
+                )
+            };
+
+            let _ = socket.write_all(response.as_bytes()).await;
+        });
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -273,4 +534,60 @@ mod tests {
         assert_eq!(metrics_after.packets_processed, 0);
         assert_eq!(metrics_after.packets_dropped, 0);
     }
+
+    #[test]
+    fn test_latency_histogram_buckets() {
+        let collector = MetricsCollector::new();
+        collector.record_latency(50); // falls in the first (100ns) bucket
+        collector.record_latency(5_000_000_000); // falls in the overflow (+Inf) bucket
+
+        let metrics = collector.get_metrics();
+        let (first_bound, first_count) = metrics.latency_histogram[0];
+        assert_eq!(first_bound, 100);
+        assert_eq!(first_count, 1);
+
+        let (last_bound, last_count) = *metrics.latency_histogram.last().unwrap();
+        assert_eq!(last_bound, u64::MAX);
+        assert_eq!(last_count, 2); // cumulative: the +Inf bucket holds every sample
+    }
+
+    #[test]
+    fn test_quantiles_track_bucket_of_sample() {
+        let collector = MetricsCollector::new();
+        // 99 samples in the 1us bucket, 1 outlier in the 1s bucket.
+        for _ in 0..99 {
+            collector.record_latency(500);
+        }
+        collector.record_latency(500_000_000);
+
+        let metrics = collector.get_metrics();
+        assert_eq!(metrics.p50_latency_ns, 1_000);
+        assert_eq!(metrics.p90_latency_ns, 1_000);
+        assert_eq!(metrics.p99_latency_ns, 1_000);
+        assert_eq!(metrics.p999_latency_ns, 1_000_000_000);
+    }
+
+    #[test]
+    fn test_registry_merges_per_worker_metrics() {
+        let registry = MetricsRegistry::new();
+        registry.worker(0).record_packet_processed(100);
+        registry.worker(1).record_packet_processed(200);
+
+        let per_worker = registry.per_worker_metrics();
+        assert_eq!(per_worker.len(), 2);
+
+        let merged = registry.merged_metrics();
+        assert_eq!(merged.packets_processed, 2);
+        assert_eq!(merged.bytes_processed, 300);
+    }
+
+    #[test]
+    fn test_render_prometheus_includes_worker_label() {
+        let registry = MetricsRegistry::new();
+        registry.worker(0).record_packet_processed(1500);
+
+        let rendered = registry.render_prometheus();
+        assert!(rendered.contains("router_packets_processed_total{worker=\"0\"} 1"));
+        assert!(rendered.contains("router_latency_seconds_bucket"));
+    }
 }
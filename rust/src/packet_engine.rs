@@ -1,8 +1,336 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::{Arc, Mutex};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender, TrySendError};
+use parking_lot::RwLock;
 use serde::{Deserialize, Serialize};
 
+/// Sliding window, in seconds, that `AbuseTracker` judges a source IP's
+/// recent behavior over -- events older than this fall out of consideration.
+const ABUSE_WINDOW_SECS: u64 = 60;
+
+/// How long a source IP stays in the blocklist once `process_packet` flags
+/// it for a port scan or flood.
+const BAN_DURATION_SECS: u64 = 600;
+
+/// Distinct destination ports touched within `ABUSE_WINDOW_SECS` that marks
+/// a source IP as running a port scan.
+const PORT_SCAN_PORT_THRESHOLD: usize = 20;
+
+/// Packets (of any protocol) from one source IP within `ABUSE_WINDOW_SECS`
+/// that marks a SYN/connection flood.
+const FLOOD_PACKET_THRESHOLD: usize = 200;
+
+/// ICMP packets from one source IP within `ABUSE_WINDOW_SECS` that marks an
+/// ICMP flood.
+const ICMP_FLOOD_PACKET_THRESHOLD: usize = 100;
+
+/// Default capacity of the ingest queue `PacketEngine::new` creates; once
+/// full, `process_packet` drops the packet and counts it rather than
+/// blocking the caller. Override with `PacketEngine::with_queue_capacity`.
+const DEFAULT_QUEUE_CAPACITY: usize = 4096;
+
+/// How often a worker thread re-checks for expired flows/bans and
+/// refreshes the lazily-updated `router_stats` snapshot, instead of doing
+/// it on every single packet.
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How long a worker blocks on the ingest channel before checking the stop
+/// flag and whether a cleanup pass is due -- keeps shutdown responsive even
+/// when no packets are arriving.
+const WORKER_POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// Capacity of each subscriber's channel created by `subscribe_expired_flows`.
+const EXPIRED_FLOW_CHANNEL_CAPACITY: usize = 1024;
+
+/// NetFlow v9 template ID used for IPv4 flow records exported from `FlowStats`.
+const NETFLOW_V9_TEMPLATE_ID_V4: u16 = 258;
+/// NetFlow v9 template ID used for IPv6 flow records.
+const NETFLOW_V9_TEMPLATE_ID_V6: u16 = 259;
+
+/// `(information element type, field length in bytes)`, in the IANA
+/// IPFIX/NetFlow v9 numbering, for the fields `FlowStats` actually tracks:
+/// source/destination address, protocol, packet/byte counts, first/last-seen
+/// timestamps, and the ingress interface. `FlowStats` has no port fields --
+/// its `flow_id` folds them into an opaque string -- so unlike a full
+/// 5-tuple export there's no `L4_SRC_PORT`/`L4_DST_PORT` here.
+const NETFLOW_V9_FIELDS_V4: &[(u16, u16)] = &[
+    (8, 4),  // IPV4_SRC_ADDR
+    (12, 4), // IPV4_DST_ADDR
+    (4, 1),  // PROTOCOL
+    (2, 4),  // IN_PKTS
+    (1, 4),  // IN_BYTES
+    (22, 4), // FIRST_SWITCHED
+    (21, 4), // LAST_SWITCHED
+    (10, 4), // INPUT_SNMP (ingress interface, hashed from its name)
+];
+
+/// Same fields as [`NETFLOW_V9_FIELDS_V4`], but with 16-byte IPv6 addresses.
+const NETFLOW_V9_FIELDS_V6: &[(u16, u16)] = &[
+    (27, 16), // IPV6_SRC_ADDR
+    (28, 16), // IPV6_DST_ADDR
+    (4, 1),
+    (2, 4),
+    (1, 4),
+    (22, 4),
+    (21, 4),
+    (10, 4),
+];
+
+/// Number of independent hashed counter rows the Count-Min Sketch keeps per
+/// flow key. Each row divides by e the chance the *minimum* across rows is still
+/// inflated by a collision, so the sketch's estimate exceeds the true value
+/// by more than `CMS_WIDTH`'s error bound with probability at most
+/// `e^-CMS_DEPTH` (~1.8% at depth 4).
+const CMS_DEPTH: usize = 4;
+
+/// Counters per Count-Min Sketch row. A wider row makes collisions between
+/// unrelated flow keys rarer: the sketch over-counts a flow's true byte
+/// total by at most `total_bytes_seen * e / CMS_WIDTH` (~0.13% of all
+/// bytes ever added, at width 2048), at the memory cost of
+/// `CMS_DEPTH * CMS_WIDTH` `u64` counters total.
+const CMS_WIDTH: usize = 2048;
+
+/// Capacity of the Space-Saving top-flow tracker `FlowSketch` keeps as a
+/// memory-bounded alternative to storing every flow's exact byte count --
+/// see the identically-shaped estimator in `analytics.rs` for the same
+/// algorithm applied to application/protocol/source/destination heavy
+/// hitters instead of flows.
+const SKETCH_TOP_K_CAPACITY: usize = 50;
+
+/// `log2` of the register count in `FlowSketch`'s HyperLogLog cardinality
+/// estimator (`m = 2^HLL_PRECISION_BITS` one-byte registers). Standard
+/// error is approximately `1.04 / sqrt(m)` -- about 3.3% at precision 10
+/// (`m = 1024`, 1 KiB of registers) -- independent of how many distinct
+/// flows are actually being counted.
+const HLL_PRECISION_BITS: u32 = 10;
+
+/// Whether `PacketEngine` keeps an exact per-flow table (unbounded memory,
+/// but precise) or a fixed-memory sketch (`get_top_flows_approx`,
+/// `estimate_active_flow_count`, `estimate_flow_bytes`) in its place. Set
+/// once at construction via `PacketEngine::with_measurement_mode` -- a busy
+/// router with more concurrent flows than fit comfortably in a `HashMap`
+/// should use `Sketch`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeasurementMode {
+    /// Store every flow's exact stats in an unbounded `HashMap`.
+    Exact,
+    /// Track flows through `FlowSketch` instead: bounded memory, but
+    /// byte-volume and flow-count queries become estimates.
+    Sketch,
+}
+
+/// A `CMS_DEPTH x CMS_WIDTH` array of hashed counters estimating a flow
+/// key's total byte volume in fixed memory, independent of how many
+/// distinct flows have been seen. Never under-estimates: `estimate` takes
+/// the *minimum* across rows, so the only error a collision can introduce
+/// is inflating the count, never deflating it. See `CMS_DEPTH`/`CMS_WIDTH`
+/// for the resulting error bound.
+struct CountMinSketch {
+    counters: Vec<Vec<u64>>,
+}
+
+impl CountMinSketch {
+    fn new(depth: usize, width: usize) -> Self {
+        Self { counters: vec![vec![0u64; width]; depth] }
+    }
+
+    fn row_index(&self, row: usize, key: &str) -> usize {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        row.hash(&mut hasher);
+        key.hash(&mut hasher);
+        (hasher.finish() as usize) % self.counters[row].len()
+    }
+
+    fn add(&mut self, key: &str, count: u64) {
+        for row in 0..self.counters.len() {
+            let index = self.row_index(row, key);
+            self.counters[row][index] += count;
+        }
+    }
+
+    fn estimate(&self, key: &str) -> u64 {
+        (0..self.counters.len())
+            .map(|row| self.counters[row][self.row_index(row, key)])
+            .min()
+            .unwrap_or(0)
+    }
+}
+
+/// Space-Saving top-K tracker: keeps exact counts for up to `capacity`
+/// keys, and when a new key arrives at capacity, evicts the current
+/// minimum and seeds the newcomer at `min_count + weight` with `min_count`
+/// recorded as its error bound -- the same algorithm `analytics.rs` uses
+/// for its own top-K estimators, reimplemented here for `FlowStats`-shaped
+/// keys rather than sharing an instance across modules.
+struct SpaceSavingFlow {
+    capacity: usize,
+    counters: HashMap<String, (u64, u64)>,
+}
+
+impl SpaceSavingFlow {
+    fn new(capacity: usize) -> Self {
+        Self { capacity: capacity.max(1), counters: HashMap::new() }
+    }
+
+    fn record(&mut self, key: String, weight: u64) {
+        if let Some(entry) = self.counters.get_mut(&key) {
+            entry.0 += weight;
+            return;
+        }
+
+        if self.counters.len() < self.capacity {
+            self.counters.insert(key, (weight, 0));
+            return;
+        }
+
+        let Some((min_key, min_count)) = self
+            .counters
+            .iter()
+            .min_by_key(|(_, &(count, _))| count)
+            .map(|(k, &(count, _))| (k.clone(), count))
+        else {
+            return;
+        };
+        self.counters.remove(&min_key);
+        self.counters.insert(key, (min_count + weight, min_count));
+    }
+
+    fn top(&self, n: usize) -> Vec<(String, u64, u64)> {
+        let mut entries: Vec<(String, u64, u64)> =
+            self.counters.iter().map(|(key, &(count, error))| (key.clone(), count, error)).collect();
+        entries.sort_by(|a, b| b.1.cmp(&a.1));
+        entries.truncate(n);
+        entries
+    }
+}
+
+/// HyperLogLog cardinality estimator over hashed flow keys: `2^HLL_PRECISION_BITS`
+/// one-byte registers, each holding the longest run of trailing zero bits
+/// seen in any hashed key routed to it, with a linear-counting correction
+/// for when most registers are still empty.
+struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    fn new() -> Self {
+        Self { registers: vec![0u8; 1usize << HLL_PRECISION_BITS] }
+    }
+
+    fn add(&mut self, key: &str) {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        key.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        let index = (hash & (self.registers.len() as u64 - 1)) as usize;
+        let remaining = hash >> HLL_PRECISION_BITS;
+        let rank = ((remaining.trailing_zeros() + 1) as u8).min(64 - HLL_PRECISION_BITS as u8);
+        if rank > self.registers[index] {
+            self.registers[index] = rank;
+        }
+    }
+
+    fn estimate(&self) -> f64 {
+        let m = self.registers.len() as f64;
+        let alpha_m = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&rank| 2f64.powi(-(rank as i32))).sum();
+        let raw_estimate = alpha_m * m * m / sum;
+
+        // Small-range correction: when most registers are still empty, the
+        // raw estimator above is unreliable -- fall back to linear counting.
+        let zero_registers = self.registers.iter().filter(|&&rank| rank == 0).count();
+        if raw_estimate <= 2.5 * m && zero_registers > 0 {
+            m * (m / zero_registers as f64).ln()
+        } else {
+            raw_estimate
+        }
+    }
+}
+
+/// Bundles the three sketches `MeasurementMode::Sketch` records into
+/// instead of an exact per-flow `HashMap`: byte-volume estimates, top-K
+/// heavy hitters, and distinct-flow cardinality.
+struct FlowSketch {
+    byte_volume: CountMinSketch,
+    top_flows: SpaceSavingFlow,
+    cardinality: HyperLogLog,
+}
+
+impl FlowSketch {
+    fn new() -> Self {
+        Self {
+            byte_volume: CountMinSketch::new(CMS_DEPTH, CMS_WIDTH),
+            top_flows: SpaceSavingFlow::new(SKETCH_TOP_K_CAPACITY),
+            cardinality: HyperLogLog::new(),
+        }
+    }
+
+    fn record(&mut self, flow_id: &str, size: u32) {
+        self.byte_volume.add(flow_id, size as u64);
+        self.top_flows.record(flow_id.to_string(), size as u64);
+        self.cardinality.add(flow_id);
+    }
+}
+
+/// Per-source-IP sliding-window state `process_packet` uses to detect port
+/// scans and floods, fail2ban-style. Events older than `ABUSE_WINDOW_SECS`
+/// are trimmed on every `record` call so the tracker's memory stays bounded
+/// regardless of how long a source has been sending traffic.
+#[derive(Debug, Clone, Default)]
+struct AbuseTracker {
+    /// `(timestamp, dest_port)` for every packet seen within the window.
+    events: VecDeque<(u64, u16)>,
+    /// Timestamps of ICMP packets seen within the window.
+    icmp_events: VecDeque<u64>,
+}
+
+impl AbuseTracker {
+    fn record(&mut self, current_time: u64, dest_port: u16, protocol: u8) {
+        self.events.push_back((current_time, dest_port));
+        while let Some(&(timestamp, _)) = self.events.front() {
+            if current_time.saturating_sub(timestamp) > ABUSE_WINDOW_SECS {
+                self.events.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        if protocol == 1 {
+            self.icmp_events.push_back(current_time);
+        }
+        while let Some(&timestamp) = self.icmp_events.front() {
+            if current_time.saturating_sub(timestamp) > ABUSE_WINDOW_SECS {
+                self.icmp_events.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn distinct_ports(&self) -> usize {
+        self.events.iter().map(|&(_, port)| port).collect::<HashSet<_>>().len()
+    }
+
+    fn packet_rate(&self) -> usize {
+        self.events.len()
+    }
+
+    fn icmp_rate(&self) -> usize {
+        self.icmp_events.len()
+    }
+
+    /// Whether this tracker has seen no traffic within the window, and so
+    /// can be dropped during a cleanup pass instead of lingering forever.
+    fn is_stale(&self, current_time: u64) -> bool {
+        self.events.back().is_none_or(|&(timestamp, _)| current_time.saturating_sub(timestamp) > ABUSE_WINDOW_SECS)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Packet {
     pub timestamp: u64,
@@ -54,19 +382,67 @@ pub struct RouterStats {
     pub interfaces: Vec<InterfaceStats>,
 }
 
+/// Ingests packets through a bounded producer/consumer pipeline instead of
+/// processing each one inline: `process_packet` is a cheap enqueue, and
+/// worker threads started with `start_workers` drain the queue, update the
+/// flow/interface/router tables, and run flow/ban cleanup on a timer. The
+/// shared tables use `parking_lot::RwLock` so read-heavy query methods
+/// (`get_all_flows`, `get_router_stats`, etc.) don't contend with ingest.
 pub struct PacketEngine {
-    flows: Arc<Mutex<HashMap<String, FlowStats>>>,
-    interfaces: Arc<Mutex<HashMap<String, InterfaceStats>>>,
-    router_stats: Arc<Mutex<RouterStats>>,
+    flows: Arc<RwLock<HashMap<String, FlowStats>>>,
+    interfaces: Arc<RwLock<HashMap<String, InterfaceStats>>>,
+    router_stats: Arc<RwLock<RouterStats>>,
     flow_timeout: u64,
+    /// Sliding-window abuse state per source IP; see [`AbuseTracker`].
+    abuse_trackers: Arc<RwLock<HashMap<String, AbuseTracker>>>,
+    /// Source IP -> ban-expiry Unix timestamp.
+    blocklist: Arc<RwLock<HashMap<String, u64>>>,
+    sender: Sender<Packet>,
+    receiver: Receiver<Packet>,
+    /// Checked with `Acquire`/`Release` ordering so `shutdown` is visible
+    /// to every worker thread promptly.
+    stop: Arc<AtomicBool>,
+    workers: Mutex<Vec<thread::JoinHandle<()>>>,
+    /// Packets dropped because the ingest queue was full.
+    dropped_packets: Arc<AtomicU64>,
+    /// Senders handed out by `subscribe_expired_flows`; a flow evicted by
+    /// `run_cleanup` is pushed to each of these before being dropped, so a
+    /// [`FlowExporter`] can export it instead of silently losing it.
+    expired_flow_subscribers: Arc<Mutex<Vec<Sender<FlowStats>>>>,
+    start_time: Instant,
+    start_time_unix_secs: u64,
+    measurement_mode: MeasurementMode,
+    /// Only populated in `MeasurementMode::Sketch` -- `flows` stays empty
+    /// in that mode, since the whole point is not keeping an exact table.
+    sketch: Arc<RwLock<FlowSketch>>,
 }
 
 impl PacketEngine {
     pub fn new(router_id: String, flow_timeout: u64) -> Self {
+        Self::with_queue_capacity(router_id, flow_timeout, DEFAULT_QUEUE_CAPACITY)
+    }
+
+    /// Create a new engine with a non-default ingest queue capacity --
+    /// mainly for tests that want to exercise backpressure deterministically.
+    pub fn with_queue_capacity(router_id: String, flow_timeout: u64, queue_capacity: usize) -> Self {
+        Self::with_measurement_mode(router_id, flow_timeout, queue_capacity, MeasurementMode::Exact)
+    }
+
+    /// Create a new engine in either `MeasurementMode::Exact` (an unbounded
+    /// exact flow table, the default) or `MeasurementMode::Sketch` (fixed
+    /// memory via `FlowSketch`, trading exactness for a bounded footprint
+    /// on a busy router).
+    pub fn with_measurement_mode(
+        router_id: String,
+        flow_timeout: u64,
+        queue_capacity: usize,
+        measurement_mode: MeasurementMode,
+    ) -> Self {
+        let (sender, receiver) = crossbeam_channel::bounded(queue_capacity);
         Self {
-            flows: Arc::new(Mutex::new(HashMap::new())),
-            interfaces: Arc::new(Mutex::new(HashMap::new())),
-            router_stats: Arc::new(Mutex::new(RouterStats {
+            flows: Arc::new(RwLock::new(HashMap::new())),
+            interfaces: Arc::new(RwLock::new(HashMap::new())),
+            router_stats: Arc::new(RwLock::new(RouterStats {
                 router_id,
                 total_packets: 0,
                 total_bytes: 0,
@@ -76,137 +452,335 @@ impl PacketEngine {
                 interfaces: Vec::new(),
             })),
             flow_timeout,
+            abuse_trackers: Arc::new(RwLock::new(HashMap::new())),
+            blocklist: Arc::new(RwLock::new(HashMap::new())),
+            sender,
+            receiver,
+            stop: Arc::new(AtomicBool::new(false)),
+            workers: Mutex::new(Vec::new()),
+            dropped_packets: Arc::new(AtomicU64::new(0)),
+            expired_flow_subscribers: Arc::new(Mutex::new(Vec::new())),
+            start_time: Instant::now(),
+            start_time_unix_secs: unix_now(),
+            measurement_mode,
+            sketch: Arc::new(RwLock::new(FlowSketch::new())),
         }
     }
 
-    pub fn process_packet(&self, packet: Packet) -> Result<(), String> {
-        let current_time = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap()
-            .as_secs();
+    /// Spawn `worker_count` threads draining the ingest queue. Safe to call
+    /// again after `shutdown` to restart processing.
+    pub fn start_workers(&self, worker_count: usize) {
+        self.stop.store(false, Ordering::Release);
+        let mut workers = self.workers.lock().unwrap();
+        for _ in 0..worker_count {
+            let receiver = self.receiver.clone();
+            let flows = Arc::clone(&self.flows);
+            let interfaces = Arc::clone(&self.interfaces);
+            let router_stats = Arc::clone(&self.router_stats);
+            let abuse_trackers = Arc::clone(&self.abuse_trackers);
+            let blocklist = Arc::clone(&self.blocklist);
+            let expired_flow_subscribers = Arc::clone(&self.expired_flow_subscribers);
+            let sketch = Arc::clone(&self.sketch);
+            let measurement_mode = self.measurement_mode;
+            let flow_timeout = self.flow_timeout;
+            let stop = Arc::clone(&self.stop);
+            workers.push(thread::spawn(move || {
+                Self::worker_loop(
+                    receiver,
+                    flows,
+                    interfaces,
+                    router_stats,
+                    abuse_trackers,
+                    blocklist,
+                    expired_flow_subscribers,
+                    sketch,
+                    measurement_mode,
+                    flow_timeout,
+                    stop,
+                );
+            }));
+        }
+    }
 
-        // Update flow statistics
-        self.update_flow_stats(&packet, current_time)?;
+    /// Signal every worker to stop, then block until each has drained its
+    /// in-flight packets and exited.
+    pub fn shutdown(&self) {
+        self.stop.store(true, Ordering::Release);
+        let mut workers = self.workers.lock().unwrap();
+        for handle in workers.drain(..) {
+            let _ = handle.join();
+        }
+    }
 
-        // Update interface statistics
-        self.update_interface_stats(&packet)?;
+    /// Packets dropped so far because the ingest queue was full.
+    pub fn dropped_packet_count(&self) -> u64 {
+        self.dropped_packets.load(Ordering::Relaxed)
+    }
 
-        // Update router statistics
-        self.update_router_stats(&packet)?;
+    /// Subscribe to flows evicted by the periodic cleanup pass -- each is
+    /// pushed here right before it's removed from the flow table, so a
+    /// [`FlowExporter`] (or any other consumer) can export it instead of
+    /// losing it.
+    pub fn subscribe_expired_flows(&self) -> FlowExportSubscription {
+        let (sender, receiver) = crossbeam_channel::bounded(EXPIRED_FLOW_CHANNEL_CAPACITY);
+        self.expired_flow_subscribers.lock().unwrap().push(sender);
+        FlowExportSubscription { receiver }
+    }
 
-        // Clean up expired flows
-        self.cleanup_expired_flows(current_time);
+    /// Enqueue `packet` for a worker thread to process. Rejects packets
+    /// from a currently-banned source outright, and drops (counting via
+    /// `dropped_packet_count`) rather than blocking if the queue is full.
+    pub fn process_packet(&self, packet: Packet) -> Result<(), String> {
+        if self.is_blocked(&packet.source_ip) {
+            return Err("blocked source".to_string());
+        }
 
-        Ok(())
+        match self.sender.try_send(packet) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                self.dropped_packets.fetch_add(1, Ordering::Relaxed);
+                Err("ingest queue full, packet dropped".to_string())
+            }
+            Err(TrySendError::Disconnected(_)) => Err("ingest queue closed".to_string()),
+        }
     }
 
-    fn update_flow_stats(&self, packet: &Packet, current_time: u64) -> Result<(), String> {
-        let mut flows = self.flows.lock().map_err(|_| "Failed to lock flows")?;
-        
-        let flow_entry = flows.entry(packet.flow_id.clone()).or_insert(FlowStats {
-            flow_id: packet.flow_id.clone(),
-            packets: 0,
-            bytes: 0,
-            start_time: current_time,
-            last_seen: current_time,
-            source_ip: packet.source_ip.clone(),
-            dest_ip: packet.dest_ip.clone(),
-            protocol: packet.protocol,
-            interface: packet.interface.clone(),
-        });
+    fn worker_loop(
+        receiver: Receiver<Packet>,
+        flows: Arc<RwLock<HashMap<String, FlowStats>>>,
+        interfaces: Arc<RwLock<HashMap<String, InterfaceStats>>>,
+        router_stats: Arc<RwLock<RouterStats>>,
+        abuse_trackers: Arc<RwLock<HashMap<String, AbuseTracker>>>,
+        blocklist: Arc<RwLock<HashMap<String, u64>>>,
+        expired_flow_subscribers: Arc<Mutex<Vec<Sender<FlowStats>>>>,
+        sketch: Arc<RwLock<FlowSketch>>,
+        measurement_mode: MeasurementMode,
+        flow_timeout: u64,
+        stop: Arc<AtomicBool>,
+    ) {
+        let mut last_cleanup = SystemTime::now();
 
-        flow_entry.packets += 1;
-        flow_entry.bytes += packet.size as u64;
-        flow_entry.last_seen = current_time;
+        loop {
+            match receiver.recv_timeout(WORKER_POLL_INTERVAL) {
+                Ok(packet) => {
+                    let current_time = unix_now();
+                    Self::ingest_packet(
+                        &flows,
+                        &interfaces,
+                        &router_stats,
+                        &abuse_trackers,
+                        &blocklist,
+                        &sketch,
+                        measurement_mode,
+                        &packet,
+                        current_time,
+                    );
+                }
+                Err(RecvTimeoutError::Timeout) => {}
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
 
-        Ok(())
+            if stop.load(Ordering::Acquire) {
+                // Drain whatever is already queued rather than discarding
+                // in-flight packets on shutdown.
+                while let Ok(packet) = receiver.try_recv() {
+                    let current_time = unix_now();
+                    Self::ingest_packet(
+                        &flows,
+                        &interfaces,
+                        &router_stats,
+                        &abuse_trackers,
+                        &blocklist,
+                        &sketch,
+                        measurement_mode,
+                        &packet,
+                        current_time,
+                    );
+                }
+                break;
+            }
+
+            if SystemTime::now().duration_since(last_cleanup).unwrap_or_default() >= CLEANUP_INTERVAL {
+                Self::run_cleanup(
+                    &flows,
+                    &interfaces,
+                    &router_stats,
+                    &abuse_trackers,
+                    &blocklist,
+                    &expired_flow_subscribers,
+                    flow_timeout,
+                    unix_now(),
+                );
+                last_cleanup = SystemTime::now();
+            }
+        }
     }
 
-    fn update_interface_stats(&self, packet: &Packet) -> Result<(), String> {
-        let mut interfaces = self.interfaces.lock().map_err(|_| "Failed to lock interfaces")?;
-        
-        let interface_entry = interfaces.entry(packet.interface.clone()).or_insert(InterfaceStats {
-            interface: packet.interface.clone(),
-            packets_in: 0,
-            packets_out: 0,
-            bytes_in: 0,
-            bytes_out: 0,
-            drops: 0,
-            errors: 0,
-        });
+    /// Apply one packet to the flow/interface tables and bump the running
+    /// totals in `router_stats`. Does *not* touch `router_stats.active_flows`
+    /// or `router_stats.interfaces` -- those are refreshed lazily by
+    /// `run_cleanup` instead of being rebuilt on every packet.
+    fn ingest_packet(
+        flows: &Arc<RwLock<HashMap<String, FlowStats>>>,
+        interfaces: &Arc<RwLock<HashMap<String, InterfaceStats>>>,
+        router_stats: &Arc<RwLock<RouterStats>>,
+        abuse_trackers: &Arc<RwLock<HashMap<String, AbuseTracker>>>,
+        blocklist: &Arc<RwLock<HashMap<String, u64>>>,
+        sketch: &Arc<RwLock<FlowSketch>>,
+        measurement_mode: MeasurementMode,
+        packet: &Packet,
+        current_time: u64,
+    ) {
+        Self::ingest_abuse_tracking(abuse_trackers, blocklist, packet, current_time);
 
-        // Determine if packet is incoming or outgoing based on source IP
-        // This is a simplified heuristic - in reality, you'd check routing tables
-        if packet.source_ip.starts_with("192.168.") || packet.source_ip.starts_with("10.") {
-            interface_entry.packets_in += 1;
-            interface_entry.bytes_in += packet.size as u64;
-        } else {
-            interface_entry.packets_out += 1;
-            interface_entry.bytes_out += packet.size as u64;
+        match measurement_mode {
+            MeasurementMode::Exact => {
+                let mut flows = flows.write();
+                let flow_entry = flows.entry(packet.flow_id.clone()).or_insert_with(|| FlowStats {
+                    flow_id: packet.flow_id.clone(),
+                    packets: 0,
+                    bytes: 0,
+                    start_time: current_time,
+                    last_seen: current_time,
+                    source_ip: packet.source_ip.clone(),
+                    dest_ip: packet.dest_ip.clone(),
+                    protocol: packet.protocol,
+                    interface: packet.interface.clone(),
+                });
+                flow_entry.packets += 1;
+                flow_entry.bytes += packet.size as u64;
+                flow_entry.last_seen = current_time;
+            }
+            MeasurementMode::Sketch => {
+                sketch.write().record(&packet.flow_id, packet.size);
+            }
         }
 
-        Ok(())
-    }
+        {
+            let mut interfaces = interfaces.write();
+            let interface_entry = interfaces.entry(packet.interface.clone()).or_insert_with(|| InterfaceStats {
+                interface: packet.interface.clone(),
+                packets_in: 0,
+                packets_out: 0,
+                bytes_in: 0,
+                bytes_out: 0,
+                drops: 0,
+                errors: 0,
+            });
+
+            // Determine if packet is incoming or outgoing based on source IP.
+            // This is a simplified heuristic - in reality, you'd check routing tables.
+            if packet.source_ip.starts_with("192.168.") || packet.source_ip.starts_with("10.") {
+                interface_entry.packets_in += 1;
+                interface_entry.bytes_in += packet.size as u64;
+            } else {
+                interface_entry.packets_out += 1;
+                interface_entry.bytes_out += packet.size as u64;
+            }
+        }
 
-    fn update_router_stats(&self, packet: &Packet) -> Result<(), String> {
-        let mut router_stats = self.router_stats.lock().map_err(|_| "Failed to lock router stats")?;
-        
+        let mut router_stats = router_stats.write();
         router_stats.total_packets += 1;
         router_stats.total_bytes += packet.size as u64;
+    }
 
-        // Update active flows count
-        let flows = self.flows.lock().map_err(|_| "Failed to lock flows")?;
-        router_stats.active_flows = flows.len() as u32;
+    /// Feed `packet` into its source IP's [`AbuseTracker`], and ban the
+    /// source if it just crossed the port-scan, flood, or ICMP-flood
+    /// threshold.
+    fn ingest_abuse_tracking(
+        abuse_trackers: &Arc<RwLock<HashMap<String, AbuseTracker>>>,
+        blocklist: &Arc<RwLock<HashMap<String, u64>>>,
+        packet: &Packet,
+        current_time: u64,
+    ) {
+        let should_ban = {
+            let mut trackers = abuse_trackers.write();
+            let tracker = trackers.entry(packet.source_ip.clone()).or_insert_with(AbuseTracker::default);
+            tracker.record(current_time, packet.dest_port, packet.protocol);
 
-        // Update interface list
-        let interfaces = self.interfaces.lock().map_err(|_| "Failed to lock interfaces")?;
-        router_stats.interfaces = interfaces.values().cloned().collect();
+            tracker.distinct_ports() >= PORT_SCAN_PORT_THRESHOLD
+                || tracker.packet_rate() >= FLOOD_PACKET_THRESHOLD
+                || tracker.icmp_rate() >= ICMP_FLOOD_PACKET_THRESHOLD
+        };
 
-        Ok(())
+        if should_ban {
+            blocklist.write().insert(packet.source_ip.clone(), current_time + BAN_DURATION_SECS);
+        }
     }
 
-    fn cleanup_expired_flows(&self, current_time: u64) {
-        let mut flows = match self.flows.lock() {
-            Ok(flows) => flows,
-            Err(_) => return,
+    /// Age out expired flows and bans, drop idle abuse trackers, and
+    /// refresh the `active_flows`/`interfaces` snapshot in `router_stats`.
+    /// Run periodically by a worker thread rather than per packet. Each
+    /// evicted flow is broadcast to `expired_flow_subscribers` before being
+    /// dropped, so a [`FlowExporter`] can still export it.
+    fn run_cleanup(
+        flows: &Arc<RwLock<HashMap<String, FlowStats>>>,
+        interfaces: &Arc<RwLock<HashMap<String, InterfaceStats>>>,
+        router_stats: &Arc<RwLock<RouterStats>>,
+        abuse_trackers: &Arc<RwLock<HashMap<String, AbuseTracker>>>,
+        blocklist: &Arc<RwLock<HashMap<String, u64>>>,
+        expired_flow_subscribers: &Arc<Mutex<Vec<Sender<FlowStats>>>>,
+        flow_timeout: u64,
+        current_time: u64,
+    ) {
+        let expired: Vec<FlowStats> = {
+            let mut flows = flows.write();
+            let expired_ids: Vec<String> = flows
+                .iter()
+                .filter(|(_, flow)| current_time - flow.last_seen >= flow_timeout)
+                .map(|(flow_id, _)| flow_id.clone())
+                .collect();
+            expired_ids.into_iter().filter_map(|flow_id| flows.remove(&flow_id)).collect()
         };
 
-        flows.retain(|_, flow| {
-            current_time - flow.last_seen < self.flow_timeout
-        });
+        if !expired.is_empty() {
+            let subscribers = expired_flow_subscribers.lock().unwrap();
+            for flow in &expired {
+                for sender in subscribers.iter() {
+                    let _ = sender.try_send(flow.clone());
+                }
+            }
+        }
+
+        blocklist.write().retain(|_, &mut expiry| expiry > current_time);
+        abuse_trackers.write().retain(|_, tracker| !tracker.is_stale(current_time));
+
+        let active_flows = flows.read().len() as u32;
+        let interface_snapshot: Vec<InterfaceStats> = interfaces.read().values().cloned().collect();
+        let mut router_stats = router_stats.write();
+        router_stats.active_flows = active_flows;
+        router_stats.interfaces = interface_snapshot;
+    }
+
+    /// Whether `ip` is currently banned (its blocklist entry hasn't expired).
+    pub fn is_blocked(&self, ip: &str) -> bool {
+        let current_time = unix_now();
+        self.blocklist.read().get(ip).is_some_and(|&expiry| expiry > current_time)
+    }
+
+    /// Currently-banned source IPs and their ban-expiry Unix timestamps.
+    pub fn get_blocklist(&self) -> Vec<(String, u64)> {
+        self.blocklist.read().iter().map(|(ip, &expiry)| (ip.clone(), expiry)).collect()
     }
 
     pub fn get_flow_stats(&self, flow_id: &str) -> Option<FlowStats> {
-        let flows = self.flows.lock().ok()?;
-        flows.get(flow_id).cloned()
+        self.flows.read().get(flow_id).cloned()
     }
 
     pub fn get_all_flows(&self) -> Vec<FlowStats> {
-        let flows = self.flows.lock().unwrap_or_else(|_| std::sync::Mutex::new(HashMap::new()).lock().unwrap());
-        flows.values().cloned().collect()
+        self.flows.read().values().cloned().collect()
     }
 
     pub fn get_interface_stats(&self, interface: &str) -> Option<InterfaceStats> {
-        let interfaces = self.interfaces.lock().ok()?;
-        interfaces.get(interface).cloned()
+        self.interfaces.read().get(interface).cloned()
     }
 
     pub fn get_all_interface_stats(&self) -> Vec<InterfaceStats> {
-        let interfaces = self.interfaces.lock().unwrap_or_else(|_| std::sync::Mutex::new(HashMap::new()).lock().unwrap());
-        interfaces.values().cloned().collect()
+        self.interfaces.read().values().cloned().collect()
     }
 
     pub fn get_router_stats(&self) -> RouterStats {
-        let router_stats = self.router_stats.lock().unwrap_or_else(|_| std::sync::Mutex::new(RouterStats {
-            router_id: "unknown".to_string(),
-            total_packets: 0,
-            total_bytes: 0,
-            total_drops: 0,
-            total_errors: 0,
-            active_flows: 0,
-            interfaces: Vec::new(),
-        }));
-        router_stats.clone()
+        self.router_stats.read().clone()
     }
 
     pub fn get_top_flows(&self, limit: usize) -> Vec<FlowStats> {
@@ -216,6 +790,28 @@ impl PacketEngine {
         flows
     }
 
+    /// Approximate heavy-hitter flows by byte volume, as `(flow_id,
+    /// estimated_bytes, error_bound)`, from the Space-Saving tracker kept
+    /// in `MeasurementMode::Sketch`. Always empty in `MeasurementMode::Exact`
+    /// -- use `get_top_flows` there for an exact ranking instead.
+    pub fn get_top_flows_approx(&self, k: usize) -> Vec<(String, u64, u64)> {
+        self.sketch.read().top_flows.top(k)
+    }
+
+    /// Count-Min Sketch estimate of `flow_id`'s total byte volume -- the
+    /// minimum across `CMS_DEPTH` hashed counters, so it's never an
+    /// under-estimate. Only updated in `MeasurementMode::Sketch`.
+    pub fn estimate_flow_bytes(&self, flow_id: &str) -> u64 {
+        self.sketch.read().byte_volume.estimate(flow_id)
+    }
+
+    /// HyperLogLog estimate of the number of distinct flows seen. Only
+    /// updated in `MeasurementMode::Sketch` -- use `get_all_flows().len()`
+    /// for an exact count in `MeasurementMode::Exact`.
+    pub fn estimate_active_flow_count(&self) -> f64 {
+        self.sketch.read().cardinality.estimate()
+    }
+
     pub fn get_flows_by_interface(&self, interface: &str) -> Vec<FlowStats> {
         self.get_all_flows()
             .into_iter()
@@ -231,13 +827,10 @@ impl PacketEngine {
     }
 
     pub fn reset_stats(&self) {
-        if let Ok(mut flows) = self.flows.lock() {
-            flows.clear();
-        }
-        if let Ok(mut interfaces) = self.interfaces.lock() {
-            interfaces.clear();
-        }
-        if let Ok(mut router_stats) = self.router_stats.lock() {
+        self.flows.write().clear();
+        self.interfaces.write().clear();
+        {
+            let mut router_stats = self.router_stats.write();
             router_stats.total_packets = 0;
             router_stats.total_bytes = 0;
             router_stats.total_drops = 0;
@@ -245,15 +838,18 @@ impl PacketEngine {
             router_stats.active_flows = 0;
             router_stats.interfaces.clear();
         }
+        self.abuse_trackers.write().clear();
+        self.blocklist.write().clear();
+        *self.sketch.write() = FlowSketch::new();
     }
 
     pub fn generate_flow_id(&self, packet: &Packet) -> String {
         // Generate a flow ID based on 5-tuple
-        format!("{}-{}-{}-{}-{}", 
-                packet.source_ip, 
-                packet.dest_ip, 
-                packet.source_port, 
-                packet.dest_port, 
+        format!("{}-{}-{}-{}-{}",
+                packet.source_ip,
+                packet.dest_ip,
+                packet.source_port,
+                packet.dest_port,
                 packet.protocol)
     }
 
@@ -294,8 +890,8 @@ impl PacketEngine {
     }
 
     pub fn calculate_bandwidth_utilization(&self, interface: &str, duration_seconds: u64) -> f64 {
-        let interfaces = self.interfaces.lock().unwrap_or_else(|_| std::sync::Mutex::new(HashMap::new()).lock().unwrap());
-        
+        let interfaces = self.interfaces.read();
+
         if let Some(interface_stats) = interfaces.get(interface) {
             let total_bytes = interface_stats.bytes_in + interface_stats.bytes_out;
             let bits_per_second = (total_bytes * 8) as f64 / duration_seconds as f64;
@@ -307,20 +903,18 @@ impl PacketEngine {
 
     pub fn get_protocol_distribution(&self) -> HashMap<u8, u64> {
         let mut distribution = HashMap::new();
-        let flows = self.flows.lock().unwrap_or_else(|_| std::sync::Mutex::new(HashMap::new()).lock().unwrap());
-        
-        for flow in flows.values() {
+
+        for flow in self.flows.read().values() {
             *distribution.entry(flow.protocol).or_insert(0) += flow.packets;
         }
-        
+
         distribution
     }
 
     pub fn get_interface_utilization(&self) -> HashMap<String, f64> {
         let mut utilization = HashMap::new();
-        let interfaces = self.interfaces.lock().unwrap_or_else(|_| std::sync::Mutex::new(HashMap::new()).lock().unwrap());
-        
-        for (interface, stats) in interfaces.iter() {
+
+        for (interface, stats) in self.interfaces.read().iter() {
             let total_bytes = stats.bytes_in + stats.bytes_out;
             let utilization_percent = if stats.packets_in + stats.packets_out > 0 {
                 (total_bytes as f64 / (stats.packets_in + stats.packets_out) as f64) * 100.0
@@ -329,19 +923,273 @@ impl PacketEngine {
             };
             utilization.insert(interface.clone(), utilization_percent);
         }
-        
+
         utilization
     }
 }
 
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
+
+/// A receiver handed out by `PacketEngine::subscribe_expired_flows`.
+pub struct FlowExportSubscription {
+    receiver: Receiver<FlowStats>,
+}
+
+impl FlowExportSubscription {
+    /// Block until a flow is evicted, or the engine itself is dropped.
+    pub fn recv(&self) -> Option<FlowStats> {
+        self.receiver.recv().ok()
+    }
+
+    /// Return an already-evicted flow without blocking, if one is queued.
+    pub fn try_recv(&self) -> Option<FlowStats> {
+        self.receiver.try_recv().ok()
+    }
+}
+
+/// Map an interface name to a stable pseudo-ifIndex for the `INPUT_SNMP`
+/// field -- `FlowStats` only tracks interfaces by name, not by the numeric
+/// SNMP index IPFIX collectors expect, so this hash stands in for one.
+fn interface_pseudo_index(name: &str) -> u32 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    name.hash(&mut hasher);
+    hasher.finish() as u32
+}
+
+/// Encode a NetFlow v9 Template FlowSet (FlowSet ID 0) describing `fields`
+/// under `template_id`.
+fn netflow_v9_template_flowset(template_id: u16, fields: &[(u16, u16)]) -> Vec<u8> {
+    let mut record = Vec::new();
+    record.extend_from_slice(&template_id.to_be_bytes());
+    record.extend_from_slice(&(fields.len() as u16).to_be_bytes());
+    for &(field_type, field_len) in fields {
+        record.extend_from_slice(&field_type.to_be_bytes());
+        record.extend_from_slice(&field_len.to_be_bytes());
+    }
+
+    let mut flowset = Vec::new();
+    flowset.extend_from_slice(&0u16.to_be_bytes()); // FlowSet ID 0 marks a template
+    flowset.extend_from_slice(&((4 + record.len()) as u16).to_be_bytes());
+    flowset.extend_from_slice(&record);
+    flowset
+}
+
+/// Encode `flow` as one NetFlow v9 data record matching [`NETFLOW_V9_FIELDS_V4`]
+/// or [`NETFLOW_V9_FIELDS_V6`] (selected by its own address family). Returns
+/// `None` if the flow's addresses don't parse, or source/destination are
+/// from different families.
+fn netflow_v9_data_record(flow: &FlowStats, start_time_unix_secs: u64) -> Option<Vec<u8>> {
+    let source: IpAddr = flow.source_ip.parse().ok()?;
+    let destination: IpAddr = flow.dest_ip.parse().ok()?;
+    let first_switched = (flow.start_time.saturating_sub(start_time_unix_secs) * 1000) as u32;
+    let last_switched = (flow.last_seen.saturating_sub(start_time_unix_secs) * 1000) as u32;
+    let ingress_interface = interface_pseudo_index(&flow.interface);
+
+    let mut record = Vec::new();
+    match (source, destination) {
+        (IpAddr::V4(source), IpAddr::V4(destination)) => {
+            record.extend_from_slice(&source.octets());
+            record.extend_from_slice(&destination.octets());
+        }
+        (IpAddr::V6(source), IpAddr::V6(destination)) => {
+            record.extend_from_slice(&source.octets());
+            record.extend_from_slice(&destination.octets());
+        }
+        _ => return None,
+    }
+    record.push(flow.protocol);
+    record.extend_from_slice(&(flow.packets as u32).to_be_bytes());
+    record.extend_from_slice(&(flow.bytes as u32).to_be_bytes());
+    record.extend_from_slice(&first_switched.to_be_bytes());
+    record.extend_from_slice(&last_switched.to_be_bytes());
+    record.extend_from_slice(&ingress_interface.to_be_bytes());
+    Some(record)
+}
+
+/// Concatenate `records` into one Data FlowSet for `template_id`, padded to
+/// a 4-byte boundary as the spec requires. Returns `None` for empty input.
+fn netflow_v9_data_flowset(template_id: u16, records: &[Vec<u8>]) -> Option<Vec<u8>> {
+    if records.is_empty() {
+        return None;
+    }
+    let mut body: Vec<u8> = records.concat();
+    let unpadded_len = 4 + body.len();
+    let padding = (4 - unpadded_len % 4) % 4;
+    body.resize(body.len() + padding, 0);
+
+    let mut flowset = Vec::new();
+    flowset.extend_from_slice(&template_id.to_be_bytes());
+    flowset.extend_from_slice(&((4 + body.len()) as u16).to_be_bytes());
+    flowset.extend_from_slice(&body);
+    Some(flowset)
+}
+
+/// Build one full NetFlow v9 message: header, the two cached template
+/// flowsets, then a data flowset per address family actually present in
+/// `flows`.
+fn render_netflow_v9_packet(
+    flows: &[FlowStats],
+    engine_start: Instant,
+    start_time_unix_secs: u64,
+    sequence: u32,
+    source_id: u32,
+    template_v4: &[u8],
+    template_v6: &[u8],
+) -> Vec<u8> {
+    let sys_uptime_ms = Instant::now().saturating_duration_since(engine_start).as_millis() as u32;
+    let unix_secs = unix_now() as u32;
+
+    let mut v4_records = Vec::new();
+    let mut v6_records = Vec::new();
+    for flow in flows {
+        let Ok(source) = flow.source_ip.parse::<IpAddr>() else { continue };
+        let Some(record) = netflow_v9_data_record(flow, start_time_unix_secs) else { continue };
+        match source {
+            IpAddr::V4(_) => v4_records.push(record),
+            IpAddr::V6(_) => v6_records.push(record),
+        }
+    }
+
+    let v4_flowset = netflow_v9_data_flowset(NETFLOW_V9_TEMPLATE_ID_V4, &v4_records);
+    let v6_flowset = netflow_v9_data_flowset(NETFLOW_V9_TEMPLATE_ID_V6, &v6_records);
+    let record_count = 2 + v4_records.len() + v6_records.len(); // 2 template records + one per flow
+
+    let mut packet = Vec::new();
+    packet.extend_from_slice(&9u16.to_be_bytes()); // version
+    packet.extend_from_slice(&(record_count as u16).to_be_bytes());
+    packet.extend_from_slice(&sys_uptime_ms.to_be_bytes());
+    packet.extend_from_slice(&unix_secs.to_be_bytes());
+    packet.extend_from_slice(&sequence.to_be_bytes());
+    packet.extend_from_slice(&source_id.to_be_bytes());
+    packet.extend_from_slice(template_v4);
+    packet.extend_from_slice(template_v6);
+    if let Some(flowset) = v4_flowset {
+        packet.extend_from_slice(&flowset);
+    }
+    if let Some(flowset) = v6_flowset {
+        packet.extend_from_slice(&flowset);
+    }
+    packet
+}
+
+/// Exports `PacketEngine`'s flows as NetFlow v9 over UDP: on `template_refresh`
+/// cadence it flushes a full snapshot, and it also flushes each flow the
+/// instant it's evicted (see `PacketEngine::subscribe_expired_flows`) so
+/// short-lived flows aren't lost between refreshes. Mirrors the NetFlow v9
+/// exporter in `analytics.rs`, adapted from tokio tasks to plain OS threads
+/// to match this module's synchronous architecture.
+pub struct FlowExporter {
+    engine: Arc<PacketEngine>,
+    collector_addr: SocketAddr,
+    template_refresh: Duration,
+}
+
+impl FlowExporter {
+    pub fn new(engine: Arc<PacketEngine>, collector_addr: SocketAddr, template_refresh: Duration) -> Self {
+        Self { engine, collector_addr, template_refresh }
+    }
+
+    /// Spawn the exporter on its own OS thread; runs until the process exits
+    /// or a socket operation fails.
+    pub fn spawn(self) -> thread::JoinHandle<std::io::Result<()>> {
+        thread::spawn(move || self.serve())
+    }
+
+    /// Build one NetFlow v9 packet from the engine's current flows and send
+    /// it to the collector right now, outside the `template_refresh` cadence.
+    pub fn export_now(&self) -> std::io::Result<()> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(self.collector_addr)?;
+        let template_v4 = netflow_v9_template_flowset(NETFLOW_V9_TEMPLATE_ID_V4, NETFLOW_V9_FIELDS_V4);
+        let template_v6 = netflow_v9_template_flowset(NETFLOW_V9_TEMPLATE_ID_V6, NETFLOW_V9_FIELDS_V6);
+        let flows = self.engine.get_all_flows();
+        let packet = render_netflow_v9_packet(
+            &flows,
+            self.engine.start_time,
+            self.engine.start_time_unix_secs,
+            1,
+            0,
+            &template_v4,
+            &template_v6,
+        );
+        socket.send(&packet)?;
+        Ok(())
+    }
+
+    fn serve(self) -> std::io::Result<()> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(self.collector_addr)?;
+        let template_v4 = netflow_v9_template_flowset(NETFLOW_V9_TEMPLATE_ID_V4, NETFLOW_V9_FIELDS_V4);
+        let template_v6 = netflow_v9_template_flowset(NETFLOW_V9_TEMPLATE_ID_V6, NETFLOW_V9_FIELDS_V6);
+
+        let subscription = self.engine.subscribe_expired_flows();
+        let mut sequence: u32 = 0;
+        let mut last_refresh = Instant::now();
+
+        loop {
+            // Flush a flow the instant it's evicted rather than waiting for
+            // the next periodic refresh.
+            while let Some(flow) = subscription.try_recv() {
+                sequence += 1;
+                let packet = render_netflow_v9_packet(
+                    std::slice::from_ref(&flow),
+                    self.engine.start_time,
+                    self.engine.start_time_unix_secs,
+                    sequence,
+                    0,
+                    &template_v4,
+                    &template_v6,
+                );
+                socket.send(&packet)?;
+            }
+
+            if last_refresh.elapsed() >= self.template_refresh {
+                let flows = self.engine.get_all_flows();
+                if !flows.is_empty() {
+                    sequence += 1;
+                    let packet = render_netflow_v9_packet(
+                        &flows,
+                        self.engine.start_time,
+                        self.engine.start_time_unix_secs,
+                        sequence,
+                        0,
+                        &template_v4,
+                        &template_v6,
+                    );
+                    socket.send(&packet)?;
+                }
+                last_refresh = Instant::now();
+            }
+
+            thread::sleep(WORKER_POLL_INTERVAL);
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Poll `check` until it returns `Some`, or panic after ~1s -- the
+    /// ingest pipeline is async, so tests have to wait for a worker thread
+    /// to drain the queue instead of asserting immediately.
+    fn wait_for<T>(mut check: impl FnMut() -> Option<T>) -> T {
+        for _ in 0..100 {
+            if let Some(value) = check() {
+                return value;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("condition not met in time");
+    }
+
     #[test]
     fn test_packet_processing() {
         let engine = PacketEngine::new("router1".to_string(), 300);
-        
+        engine.start_workers(1);
+
         let packet = Packet {
             timestamp: 1234567890,
             source_ip: "192.168.1.1".to_string(),
@@ -358,16 +1206,21 @@ mod tests {
         };
 
         assert!(engine.process_packet(packet).is_ok());
-        
-        let stats = engine.get_router_stats();
+
+        let stats = wait_for(|| {
+            let stats = engine.get_router_stats();
+            (stats.total_packets == 1).then_some(stats)
+        });
         assert_eq!(stats.total_packets, 1);
         assert_eq!(stats.total_bytes, 1500);
+
+        engine.shutdown();
     }
 
     #[test]
     fn test_flow_classification() {
         let engine = PacketEngine::new("router1".to_string(), 300);
-        
+
         let packet = Packet {
             timestamp: 1234567890,
             source_ip: "192.168.1.1".to_string(),
@@ -386,4 +1239,188 @@ mod tests {
         let classification = engine.classify_packet(&packet);
         assert_eq!(classification, "HTTP");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_port_scan_source_gets_blocked() {
+        let engine = PacketEngine::new("router1".to_string(), 300);
+        engine.start_workers(1);
+
+        for port in 0..(PORT_SCAN_PORT_THRESHOLD as u16) {
+            let packet = Packet {
+                timestamp: 1234567890,
+                source_ip: "10.0.0.1".to_string(),
+                dest_ip: "192.168.1.2".to_string(),
+                source_port: 12345,
+                dest_port: 1000 + port,
+                protocol: 6,
+                size: 60,
+                interface: "eth0".to_string(),
+                router_id: "router1".to_string(),
+                flow_id: format!("flow{}", port),
+                priority: 0,
+                dscp: 0,
+            };
+            let _ = engine.process_packet(packet);
+        }
+
+        wait_for(|| engine.is_blocked("10.0.0.1").then_some(()));
+        assert_eq!(engine.get_blocklist().len(), 1);
+
+        let next_packet = Packet {
+            timestamp: 1234567890,
+            source_ip: "10.0.0.1".to_string(),
+            dest_ip: "192.168.1.2".to_string(),
+            source_port: 12345,
+            dest_port: 2000,
+            protocol: 6,
+            size: 60,
+            interface: "eth0".to_string(),
+            router_id: "router1".to_string(),
+            flow_id: "flowN".to_string(),
+            priority: 0,
+            dscp: 0,
+        };
+        assert_eq!(engine.process_packet(next_packet), Err("blocked source".to_string()));
+
+        engine.shutdown();
+    }
+
+    #[test]
+    fn test_full_queue_drops_packets_and_counts_them() {
+        // No workers started, so nothing drains the queue and the second
+        // packet is guaranteed to find it full.
+        let engine = PacketEngine::with_queue_capacity("router1".to_string(), 300, 1);
+        let make_packet = |flow_id: &str| Packet {
+            timestamp: 1234567890,
+            source_ip: "192.168.1.1".to_string(),
+            dest_ip: "192.168.1.2".to_string(),
+            source_port: 1,
+            dest_port: 1,
+            protocol: 6,
+            size: 100,
+            interface: "eth0".to_string(),
+            router_id: "router1".to_string(),
+            flow_id: flow_id.to_string(),
+            priority: 0,
+            dscp: 0,
+        };
+
+        assert!(engine.process_packet(make_packet("a")).is_ok());
+        assert!(engine.process_packet(make_packet("b")).is_err());
+        assert_eq!(engine.dropped_packet_count(), 1);
+    }
+
+    #[test]
+    fn test_expired_flow_is_broadcast_before_being_dropped() {
+        let engine = PacketEngine::new("router1".to_string(), 5);
+        let subscription = engine.subscribe_expired_flows();
+
+        engine.flows.write().insert(
+            "flow1".to_string(),
+            FlowStats {
+                flow_id: "flow1".to_string(),
+                packets: 3,
+                bytes: 300,
+                start_time: 1_000,
+                last_seen: 1_000,
+                source_ip: "192.168.1.1".to_string(),
+                dest_ip: "192.168.1.2".to_string(),
+                protocol: 6,
+                interface: "eth0".to_string(),
+            },
+        );
+
+        PacketEngine::run_cleanup(
+            &engine.flows,
+            &engine.interfaces,
+            &engine.router_stats,
+            &engine.abuse_trackers,
+            &engine.blocklist,
+            &engine.expired_flow_subscribers,
+            engine.flow_timeout,
+            1_100, // well past a 5-second flow_timeout from last_seen = 1_000
+        );
+
+        let broadcast = subscription.try_recv().expect("evicted flow should have been broadcast");
+        assert_eq!(broadcast.flow_id, "flow1");
+        assert!(engine.get_flow_stats("flow1").is_none());
+    }
+
+    #[test]
+    fn test_render_netflow_v9_packet_encodes_ipv4_flow() {
+        let flow = FlowStats {
+            flow_id: "flow1".to_string(),
+            packets: 10,
+            bytes: 1500,
+            start_time: 1_000,
+            last_seen: 1_010,
+            source_ip: "192.168.1.1".to_string(),
+            dest_ip: "192.168.1.2".to_string(),
+            protocol: 6,
+            interface: "eth0".to_string(),
+        };
+
+        let template_v4 = netflow_v9_template_flowset(NETFLOW_V9_TEMPLATE_ID_V4, NETFLOW_V9_FIELDS_V4);
+        let template_v6 = netflow_v9_template_flowset(NETFLOW_V9_TEMPLATE_ID_V6, NETFLOW_V9_FIELDS_V6);
+        let packet = render_netflow_v9_packet(&[flow], Instant::now(), 1_000, 1, 7, &template_v4, &template_v6);
+
+        assert_eq!(&packet[0..2], &9u16.to_be_bytes()); // version
+        assert_eq!(&packet[2..4], &3u16.to_be_bytes()); // 2 template records + 1 data record
+        assert_eq!(&packet[12..16], &1u32.to_be_bytes()); // sequence number
+        assert_eq!(&packet[16..20], &7u32.to_be_bytes()); // source ID
+    }
+
+    #[test]
+    fn test_sketch_mode_estimates_flow_bytes_and_top_flows() {
+        let engine = PacketEngine::with_measurement_mode(
+            "router1".to_string(),
+            300,
+            DEFAULT_QUEUE_CAPACITY,
+            MeasurementMode::Sketch,
+        );
+        engine.start_workers(1);
+
+        for _ in 0..5 {
+            let packet = Packet {
+                timestamp: 1234567890,
+                source_ip: "192.168.1.1".to_string(),
+                dest_ip: "192.168.1.2".to_string(),
+                source_port: 1,
+                dest_port: 1,
+                protocol: 6,
+                size: 1000,
+                interface: "eth0".to_string(),
+                router_id: "router1".to_string(),
+                flow_id: "flowA".to_string(),
+                priority: 0,
+                dscp: 0,
+            };
+            assert!(engine.process_packet(packet).is_ok());
+        }
+
+        wait_for(|| (engine.estimate_flow_bytes("flowA") >= 5000).then_some(()));
+
+        // Sketch mode never populates the exact flow table.
+        assert!(engine.get_all_flows().is_empty());
+
+        let top = engine.get_top_flows_approx(5);
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].0, "flowA");
+        assert!(top[0].1 >= 5000);
+
+        engine.shutdown();
+    }
+
+    #[test]
+    fn test_hyperloglog_estimate_is_within_tolerance_of_true_cardinality() {
+        let mut hll = HyperLogLog::new();
+        let true_cardinality = 2000;
+        for i in 0..true_cardinality {
+            hll.add(&format!("flow-{}", i));
+        }
+
+        let estimate = hll.estimate();
+        let error = (estimate - true_cardinality as f64).abs() / true_cardinality as f64;
+        assert!(error < 0.1, "estimate {} too far from true cardinality {}", estimate, true_cardinality);
+    }
+}
@@ -1,8 +1,10 @@
 //! High-performance packet processor implementation in Rust
 
 use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use tokio::sync::RwLock;
@@ -10,6 +12,137 @@ use tracing::{info, warn, error, debug};
 use rayon::prelude::*;
 use crossbeam::channel::{self, Receiver, Sender};
 use dashmap::DashMap;
+use zerocopy::{FromBytes, FromZeroes, Ref, Unaligned};
+
+use crate::connection_validator::{ConnectionValidator, ConnectionValidatorConfig};
+use crate::flow_table::{self, FlowKey, FlowTable, FlowTableConfig};
+use crate::fragment_reassembly::{self, FragmentKey, FragmentReassembler, ReassemblyConfig};
+use crate::pcap_writer::{PcapConfig, PcapWriter};
+
+/// Ethernet header overlaid directly on the wire bytes (network byte order).
+#[derive(FromZeroes, FromBytes, Unaligned, Debug)]
+#[repr(C)]
+struct EthernetHeader {
+    dst_mac: [u8; 6],
+    src_mac: [u8; 6],
+    ethertype: [u8; 2],
+}
+
+impl EthernetHeader {
+    fn ethertype(&self) -> u16 {
+        u16::from_be_bytes(self.ethertype)
+    }
+}
+
+/// IPv4 header view (fixed 20-byte portion, options are skipped over separately).
+#[derive(FromZeroes, FromBytes, Unaligned, Debug)]
+#[repr(C)]
+struct Ipv4Header {
+    version_ihl: u8,
+    tos: u8,
+    total_length: [u8; 2],
+    identification: [u8; 2],
+    flags_fragment_offset: [u8; 2],
+    ttl: u8,
+    protocol: u8,
+    checksum: [u8; 2],
+    src_addr: [u8; 4],
+    dst_addr: [u8; 4],
+}
+
+impl Ipv4Header {
+    fn version(&self) -> u8 {
+        (self.version_ihl >> 4) & 0x0F
+    }
+
+    fn ihl_bytes(&self) -> usize {
+        (self.version_ihl & 0x0F) as usize * 4
+    }
+
+    fn identification(&self) -> u16 {
+        u16::from_be_bytes(self.identification)
+    }
+
+    fn flags_fragment_offset(&self) -> u16 {
+        u16::from_be_bytes(self.flags_fragment_offset)
+    }
+
+    fn src_ip(&self) -> IpAddr {
+        IpAddr::from(self.src_addr)
+    }
+
+    fn dst_ip(&self) -> IpAddr {
+        IpAddr::from(self.dst_addr)
+    }
+}
+
+/// IPv6 fixed header view (40 bytes, extension headers are not walked here).
+#[derive(FromZeroes, FromBytes, Unaligned, Debug)]
+#[repr(C)]
+struct Ipv6Header {
+    version_tc_flow: [u8; 4],
+    payload_length: [u8; 2],
+    next_header: u8,
+    hop_limit: u8,
+    src_addr: [u8; 16],
+    dst_addr: [u8; 16],
+}
+
+impl Ipv6Header {
+    fn version(&self) -> u8 {
+        (self.version_tc_flow[0] >> 4) & 0x0F
+    }
+
+    fn src_ip(&self) -> IpAddr {
+        IpAddr::from(self.src_addr)
+    }
+
+    fn dst_ip(&self) -> IpAddr {
+        IpAddr::from(self.dst_addr)
+    }
+}
+
+/// IPv6 fragment extension header (RFC 8200 section 4.5).
+#[derive(FromZeroes, FromBytes, Unaligned, Debug)]
+#[repr(C)]
+struct Ipv6FragmentHeader {
+    next_header: u8,
+    reserved: u8,
+    offset_res_m: [u8; 2],
+    identification: [u8; 4],
+}
+
+impl Ipv6FragmentHeader {
+    fn offset_bytes(&self) -> usize {
+        ((u16::from_be_bytes(self.offset_res_m) >> 3) as usize) * 8
+    }
+
+    fn more_fragments(&self) -> bool {
+        u16::from_be_bytes(self.offset_res_m) & 0x1 != 0
+    }
+
+    fn identification(&self) -> u32 {
+        u32::from_be_bytes(self.identification)
+    }
+}
+
+/// L4 port header view, valid for TCP and UDP alike (first 4 bytes match).
+#[derive(FromZeroes, FromBytes, Unaligned, Debug)]
+#[repr(C)]
+struct PortHeader {
+    src_port: [u8; 2],
+    dst_port: [u8; 2],
+}
+
+impl PortHeader {
+    fn src_port(&self) -> u16 {
+        u16::from_be_bytes(self.src_port)
+    }
+
+    fn dst_port(&self) -> u16 {
+        u16::from_be_bytes(self.dst_port)
+    }
+}
 
 /// Packet structure for high-performance processing
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -30,6 +163,14 @@ pub struct Packet {
     pub identification: u16,
 }
 
+/// Result of dispatching a single packet through the processing path.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PacketOutcome {
+    Processed,
+    Dropped,
+    SpoofedDropped,
+}
+
 /// Packet processing statistics
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PacketStats {
@@ -43,6 +184,10 @@ pub struct PacketStats {
     pub bytes_forwarded: u64,
     pub errors: u64,
     pub processing_time_ns: u64,
+    pub packets_dropped_backpressure: u64,
+    pub active_flows: usize,
+    pub reassembly_timeouts: u64,
+    pub spoofed_dropped: u64,
     pub last_update: Instant,
 }
 
@@ -59,11 +204,63 @@ impl Default for PacketStats {
             bytes_forwarded: 0,
             errors: 0,
             processing_time_ns: 0,
+            packets_dropped_backpressure: 0,
+            active_flows: 0,
+            reassembly_timeouts: 0,
+            spoofed_dropped: 0,
             last_update: Instant::now(),
         }
     }
 }
 
+/// What to do when an interface's bounded channel is full
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BackpressurePolicy {
+    /// Reject the packet immediately and count it as dropped
+    Drop,
+    /// Wait up to a deadline for room in the channel before giving up
+    Block,
+}
+
+/// Configuration for a `PacketProcessor`
+#[derive(Debug, Clone)]
+pub struct PacketProcessorConfig {
+    /// Capacity of each per-interface bounded channel
+    pub channel_capacity: usize,
+    /// What happens when a channel is at capacity
+    pub backpressure_policy: BackpressurePolicy,
+    /// Maximum time to wait for room when `backpressure_policy` is `Block`
+    pub block_timeout: std::time::Duration,
+    /// Idle timeouts for the 5-tuple flow table
+    pub flow_table: FlowTableConfig,
+    /// How often the flow table sweeper checks for expired flows
+    pub flow_sweep_interval: Duration,
+    /// Byte cap and idle timeout for buffered IP fragment reassembly
+    pub fragment_reassembly: ReassemblyConfig,
+    /// How often the reassembly sweeper checks for expired partial datagrams
+    pub reassembly_sweep_interval: Duration,
+    /// Per-interface source-validation policy guarding against spoofed packets
+    pub connection_validator: ConnectionValidatorConfig,
+    /// Raw packet capture sink; `None` disables capture entirely.
+    pub pcap: Option<PcapConfig>,
+}
+
+impl Default for PacketProcessorConfig {
+    fn default() -> Self {
+        Self {
+            channel_capacity: 4096,
+            backpressure_policy: BackpressurePolicy::Drop,
+            block_timeout: std::time::Duration::from_millis(10),
+            flow_table: FlowTableConfig::default(),
+            flow_sweep_interval: Duration::from_secs(5),
+            fragment_reassembly: ReassemblyConfig::default(),
+            reassembly_sweep_interval: Duration::from_secs(5),
+            connection_validator: ConnectionValidatorConfig::default(),
+            pcap: None,
+        }
+    }
+}
+
 /// High-performance packet processor
 pub struct PacketProcessor {
     interfaces: Arc<DashMap<String, InterfaceData>>,
@@ -71,6 +268,16 @@ pub struct PacketProcessor {
     processing_workers: Vec<tokio::task::JoinHandle<()>>,
     stats: Arc<RwLock<PacketStats>>,
     is_running: Arc<RwLock<bool>>,
+    config: PacketProcessorConfig,
+    /// Bumped every time `add_interface`/`remove_interface` changes the channel set,
+    /// so workers know to rebuild their `Select` over the current receivers.
+    topology_version: Arc<AtomicU64>,
+    flow_table: Arc<FlowTable>,
+    flow_sweeper: Option<tokio::task::JoinHandle<()>>,
+    fragment_reassembler: Arc<FragmentReassembler>,
+    reassembly_sweeper: Option<tokio::task::JoinHandle<()>>,
+    connection_validator: Arc<ConnectionValidator>,
+    pcap_writer: Option<Arc<PcapWriter>>,
 }
 
 struct InterfaceData {
@@ -82,12 +289,34 @@ struct InterfaceData {
 
 impl PacketProcessor {
     pub fn new() -> Self {
+        Self::with_config(PacketProcessorConfig::default())
+    }
+
+    pub fn with_config(config: PacketProcessorConfig) -> Self {
+        let flow_table = FlowTable::new(config.flow_table);
+        let fragment_reassembler = FragmentReassembler::new(config.fragment_reassembly);
+        let connection_validator = ConnectionValidator::new(config.connection_validator.clone());
+        let pcap_writer = config.pcap.clone().and_then(|pcap_config| match PcapWriter::new(pcap_config) {
+            Ok(writer) => Some(writer),
+            Err(err) => {
+                error!("Failed to start pcap capture sink: {}", err);
+                None
+            }
+        });
         Self {
             interfaces: Arc::new(DashMap::new()),
             packet_channels: Arc::new(DashMap::new()),
             processing_workers: Vec::new(),
             stats: Arc::new(RwLock::new(PacketStats::default())),
             is_running: Arc::new(RwLock::new(false)),
+            config,
+            topology_version: Arc::new(AtomicU64::new(0)),
+            flow_table,
+            flow_sweeper: None,
+            fragment_reassembler,
+            reassembly_sweeper: None,
+            connection_validator,
+            pcap_writer,
         }
     }
 
@@ -102,13 +331,31 @@ impl PacketProcessor {
             let stats = self.stats.clone();
             let is_running = self.is_running.clone();
             
+            let topology_version = self.topology_version.clone();
+            let flow_table = self.flow_table.clone();
+            let fragment_reassembler = self.fragment_reassembler.clone();
+            let connection_validator = self.connection_validator.clone();
+            let pcap_writer = self.pcap_writer.clone();
+
             let worker = tokio::spawn(async move {
-                Self::processing_worker(i, interfaces, packet_channels, stats, is_running).await;
+                Self::processing_worker(
+                    i, interfaces, packet_channels, stats, is_running, topology_version,
+                    flow_table, fragment_reassembler, connection_validator, pcap_writer,
+                ).await;
             });
             
             self.processing_workers.push(worker);
         }
-        
+
+        let sweeper = flow_table::spawn_sweeper(self.flow_table.clone(), self.config.flow_sweep_interval);
+        self.flow_sweeper = Some(sweeper);
+
+        let reassembly_sweeper = fragment_reassembly::spawn_sweeper(
+            self.fragment_reassembler.clone(),
+            self.config.reassembly_sweep_interval,
+        );
+        self.reassembly_sweeper = Some(reassembly_sweeper);
+
         info!("Packet processor initialized with {} workers", worker_count);
         Ok(())
     }
@@ -135,7 +382,15 @@ impl PacketProcessor {
         for worker in &self.processing_workers {
             worker.abort();
         }
-        
+
+        if let Some(sweeper) = self.flow_sweeper.take() {
+            sweeper.abort();
+        }
+
+        if let Some(sweeper) = self.reassembly_sweeper.take() {
+            sweeper.abort();
+        }
+
         info!("Packet processor stopped");
         Ok(())
     }
@@ -143,45 +398,74 @@ impl PacketProcessor {
     pub async fn add_interface(&self, name: String, device: String) -> Result<()> {
         info!("Adding interface: {} -> {}", name, device);
         
-        let (tx, rx) = channel::unbounded();
+        let (tx, rx) = channel::bounded(self.config.channel_capacity);
         self.packet_channels.insert(name.clone(), (tx, rx));
-        
+
         let interface_data = InterfaceData {
             name: name.clone(),
             device,
             is_active: true,
             stats: Arc::new(RwLock::new(PacketStats::default())),
         };
-        
+
         self.interfaces.insert(name, interface_data);
-        
+        self.topology_version.fetch_add(1, Ordering::SeqCst);
+
         info!("Interface added successfully");
         Ok(())
     }
 
     pub async fn remove_interface(&self, name: &str) -> Result<()> {
         info!("Removing interface: {}", name);
-        
+
         self.interfaces.remove(name);
         self.packet_channels.remove(name);
-        
+        self.topology_version.fetch_add(1, Ordering::SeqCst);
+
         info!("Interface removed successfully");
         Ok(())
     }
 
     pub async fn process_packet(&self, packet: Packet) -> Result<()> {
         let interface_name = packet.interface.clone();
-        
-        if let Some((tx, _)) = self.packet_channels.get(&interface_name) {
-            tx.send(packet)?;
-            Ok(())
-        } else {
-            Err(anyhow::anyhow!("Interface not found: {}", interface_name))
+
+        let tx = match self.packet_channels.get(&interface_name) {
+            Some(entry) => entry.value().0.clone(),
+            None => return Err(anyhow::anyhow!("Interface not found: {}", interface_name)),
+        };
+
+        match tx.try_send(packet) {
+            Ok(()) => Ok(()),
+            Err(channel::TrySendError::Full(packet)) => {
+                match self.config.backpressure_policy {
+                    BackpressurePolicy::Drop => {
+                        let mut stats = self.stats.write().await;
+                        stats.packets_dropped_backpressure += 1;
+                        Err(anyhow::anyhow!("WouldBlock: channel full for interface {}", interface_name))
+                    }
+                    BackpressurePolicy::Block => {
+                        match tx.send_timeout(packet, self.config.block_timeout) {
+                            Ok(()) => Ok(()),
+                            Err(_) => {
+                                let mut stats = self.stats.write().await;
+                                stats.packets_dropped_backpressure += 1;
+                                Err(anyhow::anyhow!("WouldBlock: channel full for interface {} after waiting {:?}", interface_name, self.config.block_timeout))
+                            }
+                        }
+                    }
+                }
+            }
+            Err(channel::TrySendError::Disconnected(_)) => {
+                Err(anyhow::anyhow!("Interface channel closed: {}", interface_name))
+            }
         }
     }
 
     pub async fn get_stats(&self) -> PacketStats {
-        self.stats.read().await.clone()
+        let mut stats = self.stats.read().await.clone();
+        stats.active_flows = self.flow_table.active_flow_count();
+        stats.reassembly_timeouts = self.fragment_reassembler.timeouts_total();
+        stats
     }
 
     pub async fn get_interface_stats(&self, interface: &str) -> Option<PacketStats> {
@@ -192,50 +476,80 @@ impl PacketProcessor {
         }
     }
 
+    /// Recv timeout used only to re-check `is_running` while blocked on `Select`.
+    const SELECT_RECHECK_INTERVAL: Duration = Duration::from_millis(100);
+    const BATCH_SIZE: usize = 1000;
+
     async fn processing_worker(
         worker_id: usize,
         interfaces: Arc<DashMap<String, InterfaceData>>,
         packet_channels: Arc<DashMap<String, (Sender<Packet>, Receiver<Packet>)>>,
         global_stats: Arc<RwLock<PacketStats>>,
         is_running: Arc<RwLock<bool>>,
+        topology_version: Arc<AtomicU64>,
+        flow_table: Arc<FlowTable>,
+        fragment_reassembler: Arc<FragmentReassembler>,
+        connection_validator: Arc<ConnectionValidator>,
+        pcap_writer: Option<Arc<PcapWriter>>,
     ) {
         info!("Starting packet processing worker {}", worker_id);
-        
-        let mut packet_batch = Vec::with_capacity(1000);
-        
+
+        let mut packet_batch = Vec::with_capacity(Self::BATCH_SIZE);
+        let mut built_version = u64::MAX;
+        // Receivers kept alongside the Select so indices line up; rebuilt on topology change.
+        let mut receivers: Vec<Receiver<Packet>> = Vec::new();
+
         loop {
-            // Check if we should stop
             {
                 let running = is_running.read().await;
                 if !*running {
                     break;
                 }
             }
-            
-            // Collect packets from all interfaces
-            for entry in packet_channels.iter() {
-                let (_, rx) = entry.value();
-                
-                // Try to collect a batch of packets
-                while let Ok(packet) = rx.try_recv() {
-                    packet_batch.push(packet);
-                    
-                    if packet_batch.len() >= 1000 {
-                        break;
-                    }
+
+            let current_version = topology_version.load(Ordering::SeqCst);
+            if current_version != built_version || receivers.is_empty() {
+                receivers = packet_channels.iter().map(|entry| entry.value().1.clone()).collect();
+                built_version = current_version;
+            }
+
+            if receivers.is_empty() {
+                tokio::time::sleep(Self::SELECT_RECHECK_INTERVAL).await;
+                continue;
+            }
+
+            // Block on all interface receivers at once; this is a blocking crossbeam
+            // operation, so run it on the blocking pool rather than the async executor.
+            let recv_receivers = receivers.clone();
+            let ready = tokio::task::block_in_place(move || {
+                let mut select = channel::Select::new();
+                for rx in &recv_receivers {
+                    select.recv(rx);
+                }
+                select.ready_timeout(Self::SELECT_RECHECK_INTERVAL).ok()
+            });
+
+            let Some(ready_index) = ready else {
+                continue; // Timed out; loop back and re-check is_running/topology.
+            };
+
+            let ready_rx = &receivers[ready_index];
+            while let Ok(packet) = ready_rx.try_recv() {
+                packet_batch.push(packet);
+                if packet_batch.len() >= Self::BATCH_SIZE {
+                    break;
                 }
             }
-            
-            // Process the batch of packets
+
             if !packet_batch.is_empty() {
-                Self::process_packet_batch(&packet_batch, &interfaces, &global_stats).await;
+                Self::process_packet_batch(
+                    &packet_batch, &interfaces, &global_stats, &flow_table, &fragment_reassembler,
+                    &connection_validator, &pcap_writer,
+                ).await;
                 packet_batch.clear();
             }
-            
-            // Small delay to prevent busy waiting
-            tokio::time::sleep(tokio::time::Duration::from_micros(100)).await;
         }
-        
+
         info!("Packet processing worker {} stopped", worker_id);
     }
 
@@ -243,143 +557,224 @@ impl PacketProcessor {
         packets: &[Packet],
         interfaces: &Arc<DashMap<String, InterfaceData>>,
         global_stats: &Arc<RwLock<PacketStats>>,
+        flow_table: &Arc<FlowTable>,
+        fragment_reassembler: &Arc<FragmentReassembler>,
+        connection_validator: &Arc<ConnectionValidator>,
+        pcap_writer: &Option<Arc<PcapWriter>>,
     ) {
         let start_time = Instant::now();
-        
+
+        if let Some(pcap_writer) = pcap_writer {
+            for packet in packets {
+                pcap_writer.capture(
+                    &packet.data, &packet.src_ip, &packet.dst_ip,
+                    packet.src_port, packet.dst_port, packet.protocol,
+                );
+            }
+        }
+
         // Process packets in parallel
         let results: Vec<_> = packets
             .par_iter()
-            .map(|packet| Self::process_single_packet(packet))
+            .map(|packet| {
+                Self::process_single_packet(packet, flow_table, fragment_reassembler, connection_validator)
+            })
             .collect();
-        
+
         // Update statistics
         let mut stats = global_stats.write().await;
-        for result in results {
+        for (packet, result) in packets.iter().zip(results) {
             match result {
-                Ok(processed) => {
-                    if processed {
-                        stats.packets_processed += 1;
-                        stats.bytes_processed += packet.size as u64;
-                    } else {
-                        stats.packets_dropped += 1;
-                        stats.bytes_dropped += packet.size as u64;
-                    }
+                Ok(PacketOutcome::Processed) => {
+                    stats.packets_processed += 1;
+                    stats.bytes_processed += packet.size as u64;
+                }
+                Ok(PacketOutcome::Dropped) => {
+                    stats.packets_dropped += 1;
+                    stats.bytes_dropped += packet.size as u64;
+                }
+                Ok(PacketOutcome::SpoofedDropped) => {
+                    stats.spoofed_dropped += 1;
+                    stats.bytes_dropped += packet.size as u64;
                 }
                 Err(_) => {
                     stats.errors += 1;
                 }
             }
         }
-        
+
         stats.packets_received += packets.len() as u64;
         stats.bytes_received += packets.iter().map(|p| p.size as u64).sum::<u64>();
         stats.processing_time_ns += start_time.elapsed().as_nanos() as u64;
         stats.last_update = Instant::now();
     }
 
-    fn process_single_packet(packet: &Packet) -> Result<bool> {
-        // Basic packet validation
-        if packet.data.len() < 14 {
-            return Ok(false); // Too small for Ethernet
+    fn process_single_packet(
+        packet: &Packet,
+        flow_table: &FlowTable,
+        fragment_reassembler: &FragmentReassembler,
+        connection_validator: &ConnectionValidator,
+    ) -> Result<PacketOutcome> {
+        let Ok((eth, rest)) = Ref::<_, EthernetHeader>::new_from_prefix(packet.data.as_slice()) else {
+            return Ok(PacketOutcome::Dropped); // Too small for Ethernet
+        };
+        let eth: &EthernetHeader = eth.into_ref();
+
+        match eth.ethertype() {
+            0x0800 => Self::process_ipv4_packet(packet, rest, flow_table, fragment_reassembler, connection_validator),
+            0x86DD => Self::process_ipv6_packet(packet, rest, flow_table, fragment_reassembler, connection_validator),
+            _ => Ok(PacketOutcome::Processed), // Other protocols
         }
-        
-        // Parse Ethernet header
-        let eth_header = &packet.data[0..14];
-        let ethertype = u16::from_be_bytes([eth_header[12], eth_header[13]]);
-        
-        match ethertype {
-            0x0800 => {
-                // IPv4
-                Self::process_ipv4_packet(packet)
-            }
-            0x86DD => {
-                // IPv6
-                Self::process_ipv6_packet(packet)
-            }
-            _ => {
-                // Other protocols
-                Ok(true)
-            }
+    }
+
+    /// Validate the packet's source against `interface`'s policy, then (if it
+    /// passes) record it in the flow table. `l4` is the already-dispatchable
+    /// (i.e. not awaiting reassembly) L4 payload.
+    fn validate_and_record_flow(
+        connection_validator: &ConnectionValidator,
+        flow_table: &FlowTable,
+        interface: &str,
+        src_ip: IpAddr,
+        dst_ip: IpAddr,
+        protocol: u8,
+        l4: &[u8],
+        bytes: u64,
+    ) -> PacketOutcome {
+        let (src_port, dst_port) = Ref::<_, PortHeader>::new_from_prefix(l4)
+            .map(|(ports, _)| {
+                let ports: &PortHeader = ports.into_ref();
+                (ports.src_port(), ports.dst_port())
+            })
+            .unwrap_or((0, 0));
+
+        // TCP flags live in the 14th byte of the TCP header (after the 4-byte
+        // port pair, 8 bytes of seq/ack, and the data-offset/reserved byte).
+        let tcp_flags = (protocol == 6).then(|| l4.get(13).copied()).flatten();
+        // Non-TCP protocols have no handshake, so every packet counts as setup;
+        // TCP only counts a SYN as establishing a new binding.
+        let is_connection_setup = tcp_flags.map(|flags| flags & flow_table::tcp_flags::SYN != 0).unwrap_or(true);
+
+        if !connection_validator.validate(interface, src_ip, src_port, is_connection_setup) {
+            return PacketOutcome::SpoofedDropped;
         }
+
+        let key = FlowKey { src_ip, dst_ip, protocol, src_port, dst_port };
+        flow_table.record_packet(key, bytes, tcp_flags);
+        PacketOutcome::Processed
     }
 
-    fn process_ipv4_packet(packet: &Packet) -> Result<bool> {
-        if packet.data.len() < 34 {
-            return Ok(false); // Too small for IPv4 header
+    fn process_ipv4_packet(
+        packet: &Packet,
+        rest: &[u8],
+        flow_table: &FlowTable,
+        fragment_reassembler: &FragmentReassembler,
+        connection_validator: &ConnectionValidator,
+    ) -> Result<PacketOutcome> {
+        let Ok((ip_header, after_header)) = Ref::<_, Ipv4Header>::new_from_prefix(rest) else {
+            return Ok(PacketOutcome::Dropped); // Too small for IPv4 header
+        };
+        let ip_header: &Ipv4Header = ip_header.into_ref();
+
+        if ip_header.version() != 4 {
+            return Ok(PacketOutcome::Dropped); // Not IPv4
         }
-        
-        let ip_header = &packet.data[14..34];
-        let version = (ip_header[0] >> 4) & 0x0F;
-        
-        if version != 4 {
-            return Ok(false); // Not IPv4
+
+        let ihl = ip_header.ihl_bytes();
+        let fixed_header_len = std::mem::size_of::<Ipv4Header>();
+        if ihl < fixed_header_len || rest.len() < ihl {
+            return Ok(PacketOutcome::Dropped); // Invalid header length
         }
-        
-        let ihl = (ip_header[0] & 0x0F) as usize * 4;
-        if ihl < 20 || packet.data.len() < 14 + ihl {
-            return Ok(false); // Invalid header length
+
+        // Options (if any) sit between the fixed header and the L4 payload.
+        let l4 = &after_header[(ihl - fixed_header_len)..];
+        let protocol = ip_header.protocol;
+        let flags_fragment_offset = ip_header.flags_fragment_offset();
+        let more_fragments = flags_fragment_offset & 0x2000 != 0;
+        let fragment_offset = ((flags_fragment_offset & 0x1FFF) as usize) * 8;
+
+        if tracing::enabled!(tracing::Level::DEBUG) {
+            debug!(
+                "Processed IPv4 packet: {} -> {} (protocol: {}, fragment_offset: {})",
+                ip_header.src_ip(), ip_header.dst_ip(), protocol, fragment_offset
+            );
         }
-        
-        // Extract IP addresses
-        let src_ip = format!("{}.{}.{}.{}", 
-            ip_header[12], ip_header[13], ip_header[14], ip_header[15]);
-        let dst_ip = format!("{}.{}.{}.{}", 
-            ip_header[16], ip_header[17], ip_header[18], ip_header[19]);
-        
-        // Extract protocol
-        let protocol = ip_header[9];
-        
-        // Extract ports if TCP/UDP
-        let src_port = if packet.data.len() >= 14 + ihl + 4 {
-            u16::from_be_bytes([packet.data[14 + ihl], packet.data[14 + ihl + 1]])
-        } else {
-            0
-        };
-        
-        let dst_port = if packet.data.len() >= 14 + ihl + 4 {
-            u16::from_be_bytes([packet.data[14 + ihl + 2], packet.data[14 + ihl + 3]])
-        } else {
-            0
-        };
-        
-        debug!("Processed IPv4 packet: {}:{} -> {}:{} (protocol: {})", 
-            src_ip, src_port, dst_ip, dst_port, protocol);
-        
-        Ok(true)
+
+        if more_fragments || fragment_offset != 0 {
+            let key = FragmentKey {
+                src_ip: ip_header.src_ip(),
+                dst_ip: ip_header.dst_ip(),
+                protocol,
+                identification: ip_header.identification() as u32,
+            };
+            let Some(reassembled) =
+                fragment_reassembler.add_fragment(key, fragment_offset, more_fragments, l4)
+            else {
+                return Ok(PacketOutcome::Processed); // Buffered awaiting more fragments (or rejected/oversized).
+            };
+            return Ok(Self::validate_and_record_flow(
+                connection_validator, flow_table, &packet.interface,
+                ip_header.src_ip(), ip_header.dst_ip(), protocol, &reassembled, packet.size as u64,
+            ));
+        }
+
+        Ok(Self::validate_and_record_flow(
+            connection_validator, flow_table, &packet.interface,
+            ip_header.src_ip(), ip_header.dst_ip(), protocol, l4, packet.size as u64,
+        ))
     }
 
-    fn process_ipv6_packet(packet: &Packet) -> Result<bool> {
-        if packet.data.len() < 54 {
-            return Ok(false); // Too small for IPv6 header
+    fn process_ipv6_packet(
+        packet: &Packet,
+        rest: &[u8],
+        flow_table: &FlowTable,
+        fragment_reassembler: &FragmentReassembler,
+        connection_validator: &ConnectionValidator,
+    ) -> Result<PacketOutcome> {
+        let Ok((ip_header, after_header)) = Ref::<_, Ipv6Header>::new_from_prefix(rest) else {
+            return Ok(PacketOutcome::Dropped); // Too small for IPv6 header
+        };
+        let ip_header: &Ipv6Header = ip_header.into_ref();
+
+        if ip_header.version() != 6 {
+            return Ok(PacketOutcome::Dropped); // Not IPv6
         }
-        
-        let ip_header = &packet.data[14..54];
-        let version = (ip_header[0] >> 4) & 0x0F;
-        
-        if version != 6 {
-            return Ok(false); // Not IPv6
+
+        if tracing::enabled!(tracing::Level::DEBUG) {
+            debug!(
+                "Processed IPv6 packet: {} -> {} (next header: {})",
+                ip_header.src_ip(), ip_header.dst_ip(), ip_header.next_header
+            );
         }
-        
-        // Extract IPv6 addresses
-        let src_ip = format!("{:02x}{:02x}:{:02x}{:02x}:{:02x}{:02x}:{:02x}{:02x}:{:02x}{:02x}:{:02x}{:02x}:{:02x}{:02x}:{:02x}{:02x}",
-            ip_header[8], ip_header[9], ip_header[10], ip_header[11],
-            ip_header[12], ip_header[13], ip_header[14], ip_header[15],
-            ip_header[16], ip_header[17], ip_header[18], ip_header[19],
-            ip_header[20], ip_header[21], ip_header[22], ip_header[23]);
-        
-        let dst_ip = format!("{:02x}{:02x}:{:02x}{:02x}:{:02x}{:02x}:{:02x}{:02x}:{:02x}{:02x}:{:02x}{:02x}:{:02x}{:02x}:{:02x}{:02x}",
-            ip_header[24], ip_header[25], ip_header[26], ip_header[27],
-            ip_header[28], ip_header[29], ip_header[30], ip_header[31],
-            ip_header[32], ip_header[33], ip_header[34], ip_header[35],
-            ip_header[36], ip_header[37], ip_header[38], ip_header[39]);
-        
-        // Extract next header (protocol)
-        let next_header = ip_header[6];
-        
-        debug!("Processed IPv6 packet: {} -> {} (next header: {})", 
-            src_ip, dst_ip, next_header);
-        
-        Ok(true)
+
+        // The Fragment extension header (44) is the only one we walk; other
+        // extension header types are treated as opaque like the rest of this path.
+        if ip_header.next_header == 44 {
+            let Ok((frag_header, l4)) = Ref::<_, Ipv6FragmentHeader>::new_from_prefix(after_header) else {
+                return Ok(PacketOutcome::Dropped); // Too small for the fragment header
+            };
+            let frag_header: &Ipv6FragmentHeader = frag_header.into_ref();
+
+            let key = FragmentKey {
+                src_ip: ip_header.src_ip(),
+                dst_ip: ip_header.dst_ip(),
+                protocol: frag_header.next_header,
+                identification: frag_header.identification(),
+            };
+            let Some(reassembled) = fragment_reassembler.add_fragment(
+                key, frag_header.offset_bytes(), frag_header.more_fragments(), l4,
+            ) else {
+                return Ok(PacketOutcome::Processed); // Buffered awaiting more fragments (or rejected/oversized).
+            };
+            return Ok(Self::validate_and_record_flow(
+                connection_validator, flow_table, &packet.interface,
+                ip_header.src_ip(), ip_header.dst_ip(), frag_header.next_header, &reassembled, packet.size as u64,
+            ));
+        }
+
+        Ok(Self::validate_and_record_flow(
+            connection_validator, flow_table, &packet.interface,
+            ip_header.src_ip(), ip_header.dst_ip(), ip_header.next_header, after_header, packet.size as u64,
+        ))
     }
 }
 
@@ -0,0 +1,195 @@
+//! Optional raw-packet capture sink: write selected packets to a pcap file for
+//! offline inspection (the "tcpdump to file" workflow), without blocking the
+//! forwarding path.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crossbeam::channel::{self, Sender, TrySendError};
+
+const PCAP_MAGIC: u32 = 0xa1b2c3d4;
+const PCAP_VERSION_MAJOR: u16 = 2;
+const PCAP_VERSION_MINOR: u16 = 4;
+const LINKTYPE_ETHERNET: u32 = 1;
+
+/// Predicate over a packet's parsed 5-tuple; `None` fields match anything.
+#[derive(Debug, Clone, Default)]
+pub struct CaptureFilter {
+    pub src_ip: Option<String>,
+    pub dst_ip: Option<String>,
+    pub src_port: Option<u16>,
+    pub dst_port: Option<u16>,
+    pub protocol: Option<u16>,
+}
+
+impl CaptureFilter {
+    fn matches(&self, src_ip: &str, dst_ip: &str, src_port: u16, dst_port: u16, protocol: u16) -> bool {
+        self.src_ip.as_deref().map_or(true, |ip| ip == src_ip)
+            && self.dst_ip.as_deref().map_or(true, |ip| ip == dst_ip)
+            && self.src_port.map_or(true, |p| p == src_port)
+            && self.dst_port.map_or(true, |p| p == dst_port)
+            && self.protocol.map_or(true, |p| p == protocol)
+    }
+}
+
+/// Configuration for the capture sink.
+#[derive(Debug, Clone)]
+pub struct PcapConfig {
+    /// Where to write the active capture file; rotated files sit alongside it.
+    pub path: PathBuf,
+    pub filter: CaptureFilter,
+    /// Capacity of the bounded ring buffer between `capture()` and the writer.
+    pub channel_capacity: usize,
+    pub max_bytes_per_file: u64,
+    pub max_file_age: Duration,
+}
+
+impl Default for PcapConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("capture.pcap"),
+            filter: CaptureFilter::default(),
+            channel_capacity: 4096,
+            max_bytes_per_file: 100 * 1024 * 1024,
+            max_file_age: Duration::from_secs(3600),
+        }
+    }
+}
+
+struct CaptureRecord {
+    data: Vec<u8>,
+    captured_at: SystemTime,
+}
+
+/// Writes selected packets to a rotating pcap file via a bounded ring buffer,
+/// so a slow disk never backs up into the forwarding path: a full buffer just
+/// drops the capture instead of blocking the caller.
+pub struct PcapWriter {
+    sender: Sender<CaptureRecord>,
+    filter: CaptureFilter,
+    dropped: AtomicU64,
+}
+
+impl PcapWriter {
+    pub fn new(config: PcapConfig) -> io::Result<Arc<Self>> {
+        let (sender, receiver) = channel::bounded(config.channel_capacity);
+        let mut sink = RotatingPcapFile::open(config.path, config.max_bytes_per_file, config.max_file_age)?;
+
+        tokio::spawn(async move {
+            loop {
+                let Ok(record) = tokio::task::block_in_place(|| receiver.recv()) else {
+                    break; // Sender dropped: shut down.
+                };
+                if let Err(err) = sink.write_record(&record) {
+                    tracing::warn!("pcap capture write failed: {}", err);
+                }
+            }
+        });
+
+        Ok(Arc::new(Self {
+            sender,
+            filter: config.filter,
+            dropped: AtomicU64::new(0),
+        }))
+    }
+
+    /// Enqueue `data` for capture if the 5-tuple passes the configured filter.
+    /// Never blocks: a full ring buffer just drops the record.
+    pub fn capture(&self, data: &[u8], src_ip: &str, dst_ip: &str, src_port: u16, dst_port: u16, protocol: u16) {
+        if !self.filter.matches(src_ip, dst_ip, src_port, dst_port, protocol) {
+            return;
+        }
+
+        let record = CaptureRecord { data: data.to_vec(), captured_at: SystemTime::now() };
+        if let Err(TrySendError::Full(_) | TrySendError::Disconnected(_)) = self.sender.try_send(record) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Total captures dropped because the ring buffer was full or the writer
+    /// task's receiver was gone.
+    pub fn dropped_total(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// Owns the currently-open pcap file and rotates it by size or age.
+struct RotatingPcapFile {
+    base_path: PathBuf,
+    file: BufWriter<File>,
+    bytes_written: u64,
+    opened_at: Instant,
+    max_bytes: u64,
+    max_age: Duration,
+    rotation: u64,
+}
+
+impl RotatingPcapFile {
+    fn open(base_path: PathBuf, max_bytes: u64, max_age: Duration) -> io::Result<Self> {
+        let file = Self::create_with_header(&base_path)?;
+        Ok(Self { base_path, file, bytes_written: 0, opened_at: Instant::now(), max_bytes, max_age, rotation: 0 })
+    }
+
+    fn create_with_header(path: &std::path::Path) -> io::Result<BufWriter<File>> {
+        let mut file = BufWriter::new(File::create(path)?);
+        file.write_all(&PCAP_MAGIC.to_le_bytes())?;
+        file.write_all(&PCAP_VERSION_MAJOR.to_le_bytes())?;
+        file.write_all(&PCAP_VERSION_MINOR.to_le_bytes())?;
+        file.write_all(&0i32.to_le_bytes())?; // thiszone
+        file.write_all(&0u32.to_le_bytes())?; // sigfigs
+        file.write_all(&65535u32.to_le_bytes())?; // snaplen
+        file.write_all(&LINKTYPE_ETHERNET.to_le_bytes())?;
+        file.flush()?;
+        Ok(file)
+    }
+
+    fn rotate_if_needed(&mut self) -> io::Result<()> {
+        if self.bytes_written < self.max_bytes && self.opened_at.elapsed() < self.max_age {
+            return Ok(());
+        }
+        self.rotation += 1;
+        let rotated_path = self.base_path.with_extension(format!("{}.pcap", self.rotation));
+        self.file = Self::create_with_header(&rotated_path)?;
+        self.bytes_written = 0;
+        self.opened_at = Instant::now();
+        Ok(())
+    }
+
+    fn write_record(&mut self, record: &CaptureRecord) -> io::Result<()> {
+        self.rotate_if_needed()?;
+
+        let since_epoch = record.captured_at.duration_since(UNIX_EPOCH).unwrap_or_default();
+        let len = record.data.len() as u32;
+        self.file.write_all(&(since_epoch.as_secs() as u32).to_le_bytes())?;
+        self.file.write_all(&since_epoch.subsec_micros().to_le_bytes())?;
+        self.file.write_all(&len.to_le_bytes())?; // captured length
+        self.file.write_all(&len.to_le_bytes())?; // original length
+        self.file.write_all(&record.data)?;
+        self.file.flush()?;
+
+        self.bytes_written += 16 + record.data.len() as u64;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_matches_only_configured_fields() {
+        let filter = CaptureFilter { dst_port: Some(443), ..Default::default() };
+        assert!(filter.matches("10.0.0.1", "10.0.0.2", 5555, 443, 6));
+        assert!(!filter.matches("10.0.0.1", "10.0.0.2", 5555, 8080, 6));
+    }
+
+    #[test]
+    fn empty_filter_matches_everything() {
+        let filter = CaptureFilter::default();
+        assert!(filter.matches("10.0.0.1", "10.0.0.2", 1, 2, 17));
+    }
+}
@@ -1,16 +1,217 @@
+use std::cmp::{Ordering, Reverse};
 use std::collections::HashMap;
 use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::path::Path;
 use std::str::FromStr;
 
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
 use crate::{Route, RoutingStats};
 
+/// A route's rank for best-path comparison: BGP's LOCAL_PREF/AS_PATH/MED
+/// tiebreakers first (highest LOCAL_PREF, then shortest AS_PATH, then lowest
+/// MED), then plain `metric` as the final tiebreak. A route missing a BGP
+/// attribute ranks as if it carried that tier's least-preferred value, so a
+/// route with no BGP attributes at all degrades to pure-metric comparison.
+fn rank_tuple(route: &Route) -> (Reverse<u32>, u32, u32, u32) {
+    (
+        Reverse(route.local_pref.unwrap_or(0)),
+        route.as_path_len.unwrap_or(u32::MAX),
+        route.med.unwrap_or(u32::MAX),
+        route.metric,
+    )
+}
+
+/// Decides whether `candidate` should displace `installed` when two routes
+/// land on the same prefix. `Ordering::Less` means `candidate` wins.
+/// [`RoutingTable`] calls this through a pluggable [`PathComparator`] field
+/// so non-BGP tables can keep today's pure-metric behavior via [`metric_only`].
+pub type PathComparator = fn(candidate: &Route, installed: &Route) -> Ordering;
+
+/// Standard BGP best-path order: highest `local_pref`, then shortest
+/// `as_path_len`, then lowest `med`, then lowest `metric` as the final
+/// tiebreak.
+pub fn bgp_best_path(candidate: &Route, installed: &Route) -> Ordering {
+    rank_tuple(candidate).cmp(&rank_tuple(installed))
+}
+
+/// Today's default best-path rule, unchanged by the addition of BGP
+/// attributes: the lowest `metric` wins.
+pub fn metric_only(candidate: &Route, installed: &Route) -> Ordering {
+    candidate.metric.cmp(&installed.metric)
+}
+
+/// Packed, byte-aligned key for a route's network prefix. Replaces the old
+/// heap-allocated `"192.168.1.0/24"`-style `String` key: it's `Copy`, derives
+/// `Hash`/`Eq` directly off the address octets, and is parsed once (at
+/// `add_route` time) instead of being re-split on every lookup. Host bits
+/// are always masked off, so two destinations differing only outside their
+/// prefix length land on the same key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum PrefixKey {
+    V4 { addr: [u8; 4], prefix_len: u8 },
+    V6 { addr: [u8; 16], prefix_len: u8 },
+}
+
+/// Zero every bit of `bytes` past `prefix_len`.
+fn mask_bits<const N: usize>(mut bytes: [u8; N], prefix_len: u8) -> [u8; N] {
+    let full_bytes = (prefix_len / 8) as usize;
+    let remaining_bits = prefix_len % 8;
+    for b in bytes.iter_mut().skip(full_bytes) {
+        *b = 0;
+    }
+    if remaining_bits > 0 && full_bytes < N {
+        let mask = 0xFFu8 << (8 - remaining_bits);
+        bytes[full_bytes] &= mask;
+    }
+    bytes
+}
+
+impl PrefixKey {
+    fn new(addr: IpAddr, prefix_len: u8) -> Self {
+        match addr {
+            IpAddr::V4(v4) => PrefixKey::V4 { addr: mask_bits(v4.octets(), prefix_len), prefix_len },
+            IpAddr::V6(v6) => PrefixKey::V6 { addr: mask_bits(v6.octets(), prefix_len), prefix_len },
+        }
+    }
+
+    /// Parse a `"192.168.1.0/24"`-style destination into its packed key,
+    /// normalizing the network address by masking off host bits.
+    fn parse(destination: &str) -> Result<Self, String> {
+        let (addr_part, prefix_part) = destination
+            .split_once('/')
+            .ok_or_else(|| format!("Destination {} is not in network/prefix form", destination))?;
+        let addr = IpAddr::from_str(addr_part).map_err(|e| e.to_string())?;
+        let prefix_len = prefix_part.parse::<u8>().map_err(|e| e.to_string())?;
+        match addr {
+            IpAddr::V4(_) if prefix_len > 32 => Err(format!("Prefix length {} exceeds 32 for IPv4", prefix_len)),
+            IpAddr::V6(_) if prefix_len > 128 => Err(format!("Prefix length {} exceeds 128 for IPv6", prefix_len)),
+            _ => Ok(Self::new(addr, prefix_len)),
+        }
+    }
+
+    fn octets(&self) -> &[u8] {
+        match self {
+            PrefixKey::V4 { addr, .. } => addr,
+            PrefixKey::V6 { addr, .. } => addr,
+        }
+    }
+
+    fn prefix_len(&self) -> u8 {
+        match self {
+            PrefixKey::V4 { prefix_len, .. } | PrefixKey::V6 { prefix_len, .. } => *prefix_len,
+        }
+    }
+}
+
+/// One node of a binary radix (Patricia) trie keyed by address bits:
+/// `children[0]`/`children[1]` descend on the next 0/1 bit, and `entry` holds
+/// the key of the route whose prefix terminates exactly at this depth, if
+/// any. A given `(masked address, prefix_len)` combination always lands on
+/// the same node, so which *route* currently wins that prefix is decided
+/// separately, by [`RoutingTable::add_route`]'s [`PathComparator`] — the trie
+/// only needs to know the prefix is occupied.
+#[derive(Default)]
+struct TrieNode {
+    children: [Option<Box<TrieNode>>; 2],
+    entry: Option<PrefixKey>,
+}
+
+impl TrieNode {
+    fn is_empty(&self) -> bool {
+        self.children[0].is_none() && self.children[1].is_none() && self.entry.is_none()
+    }
+}
+
+/// Binary radix trie giving O(address-width) longest-prefix-match lookups,
+/// one instance per address family (IPv4 stays 32 bits wide, IPv6 128, so the
+/// two never share nodes).
+#[derive(Default)]
+struct RadixTrie {
+    root: TrieNode,
+}
+
+fn bit_at(bytes: &[u8], index: usize) -> u8 {
+    (bytes[index / 8] >> (7 - (index % 8))) & 1
+}
+
+impl RadixTrie {
+    /// Place `key` at the node for its own prefix length, walking `key`'s
+    /// octets bit by bit from MSB.
+    fn insert(&mut self, key: PrefixKey) {
+        let bits = key.octets();
+        let mut node = &mut self.root;
+        for i in 0..key.prefix_len() as usize {
+            node = node.children[bit_at(bits, i) as usize].get_or_insert_with(Box::default);
+        }
+        node.entry = Some(key);
+    }
+
+    /// Clear `key`'s entry and prune any interior nodes left with no
+    /// children and no entry of their own.
+    fn remove(&mut self, key: &PrefixKey) {
+        Self::remove_at(&mut self.root, key.octets(), key.prefix_len(), 0, key);
+    }
+
+    fn remove_at(node: &mut TrieNode, bits: &[u8], prefix_len: u8, depth: usize, key: &PrefixKey) -> bool {
+        if depth == prefix_len as usize {
+            if node.entry.as_ref() == Some(key) {
+                node.entry = None;
+            }
+        } else {
+            let bit = bit_at(bits, depth) as usize;
+            if let Some(child) = node.children[bit].as_mut() {
+                if Self::remove_at(child, bits, prefix_len, depth + 1, key) {
+                    node.children[bit] = None;
+                }
+            }
+        }
+        node.is_empty()
+    }
+
+    /// Descend `addr_bits` of `bits`, remembering the deepest node whose
+    /// entry passes `is_live` — that's the most-specific (longest-prefix)
+    /// match that isn't inactive or expired. Checking liveness during the
+    /// descent, rather than filtering the single deepest entry afterward,
+    /// means a dead `/32` falls back to a live, less-specific route instead
+    /// of blackholing the lookup.
+    fn lookup(&self, bits: &[u8], addr_bits: u8, is_live: impl Fn(&PrefixKey) -> bool) -> Option<PrefixKey> {
+        let mut node = &self.root;
+        let mut best = node.entry.filter(|k| is_live(k));
+        for i in 0..addr_bits as usize {
+            match &node.children[bit_at(bits, i) as usize] {
+                Some(child) => {
+                    node = child;
+                    if let Some(k) = node.entry {
+                        if is_live(&k) {
+                            best = Some(k);
+                        }
+                    }
+                }
+                None => break,
+            }
+        }
+        best
+    }
+}
+
 /// High-performance routing table implementation
 pub struct RoutingTable {
-    routes: HashMap<String, Route>,
+    routes: HashMap<PrefixKey, Route>,
     protocol_counts: HashMap<String, usize>,
     interface_counts: HashMap<String, usize>,
     last_update: u64,
     total_updates: u64,
+    /// LPM tries mirroring `routes`, keyed by address family so native IPv6
+    /// prefixes never share nodes with IPv4-mapped ones.
+    v4_trie: RadixTrie,
+    v6_trie: RadixTrie,
+    /// Decides which route wins when a new one collides on an
+    /// already-occupied prefix. Defaults to [`metric_only`]; swap in
+    /// [`bgp_best_path`] via [`Self::with_path_comparator`] for a table that
+    /// carries BGP-learned routes.
+    comparator: PathComparator,
 }
 
 impl RoutingTable {
@@ -21,16 +222,80 @@ impl RoutingTable {
             interface_counts: HashMap::new(),
             last_update: 0,
             total_updates: 0,
+            v4_trie: RadixTrie::default(),
+            v6_trie: RadixTrie::default(),
+            comparator: metric_only,
+        }
+    }
+
+    /// Build a table that decides prefix collisions with `comparator`
+    /// instead of the default [`metric_only`] rule.
+    pub fn with_path_comparator(comparator: PathComparator) -> Self {
+        Self { comparator, ..Self::new() }
+    }
+
+    /// Swap the best-path comparator on an existing table.
+    pub fn set_path_comparator(&mut self, comparator: PathComparator) {
+        self.comparator = comparator;
+    }
+
+    fn trie_for(&mut self, key: &PrefixKey) -> &mut RadixTrie {
+        match key {
+            PrefixKey::V4 { .. } => &mut self.v4_trie,
+            PrefixKey::V6 { .. } => &mut self.v6_trie,
+        }
+    }
+
+    /// A route past its TTL is treated as inactive everywhere a caller asks
+    /// for "active" routes, even though it isn't removed until a sweep runs.
+    fn is_expired(route: &Route, now: u64) -> bool {
+        route.expires_at.is_some_and(|expires_at| now >= expires_at)
+    }
+
+    /// Remove `key`'s route (if present), keeping the trie and the
+    /// protocol/interface statistics in sync. Shared by `remove_route` and
+    /// `purge_expired`.
+    fn remove_key(&mut self, key: PrefixKey) -> Option<Route> {
+        let route = self.routes.remove(&key)?;
+        self.trie_for(&key).remove(&key);
+
+        if let Some(count) = self.protocol_counts.get_mut(&route.protocol) {
+            *count -= 1;
+            if *count == 0 {
+                self.protocol_counts.remove(&route.protocol);
+            }
+        }
+
+        if let Some(count) = self.interface_counts.get_mut(&route.interface) {
+            *count -= 1;
+            if *count == 0 {
+                self.interface_counts.remove(&route.interface);
+            }
         }
+
+        Some(route)
     }
 
-    /// Add a route to the routing table
+    /// Add a route to the routing table. If a route is already installed for
+    /// the same prefix, `route` only displaces it if `self.comparator` picks
+    /// `route` over the one already there — otherwise this is a no-op and the
+    /// existing route keeps the prefix. A prefix's identity is its masked
+    /// network address and length, so destinations that differ only in host
+    /// bits compete for the same prefix; to force an update to a specific logical route regardless of the
+    /// comparator, use [`Self::update_route`] instead.
     pub fn add_route(&mut self, route: &Route) -> Result<(), String> {
         // Validate route
         self.validate_route(route)?;
 
-        let destination = route.destination.clone();
-        let old_route = self.routes.insert(destination.clone(), route.clone());
+        let key = PrefixKey::parse(&route.destination)?;
+        if let Some(existing) = self.routes.get(&key) {
+            if (self.comparator)(route, existing) != Ordering::Less {
+                return Ok(());
+            }
+        }
+
+        let old_route = self.routes.insert(key, route.clone());
+        self.trie_for(&key).insert(key);
 
         // Update statistics
         if old_route.is_none() {
@@ -46,22 +311,8 @@ impl RoutingTable {
 
     /// Remove a route from the routing table
     pub fn remove_route(&mut self, destination: &str) -> Result<(), String> {
-        if let Some(route) = self.routes.remove(destination) {
-            // Update statistics
-            if let Some(count) = self.protocol_counts.get_mut(&route.protocol) {
-                *count -= 1;
-                if *count == 0 {
-                    self.protocol_counts.remove(&route.protocol);
-                }
-            }
-
-            if let Some(count) = self.interface_counts.get_mut(&route.interface) {
-                *count -= 1;
-                if *count == 0 {
-                    self.interface_counts.remove(&route.interface);
-                }
-            }
-
+        let key = PrefixKey::parse(destination)?;
+        if self.remove_key(key).is_some() {
             self.update_timestamp();
             self.total_updates += 1;
         }
@@ -69,14 +320,47 @@ impl RoutingTable {
         Ok(())
     }
 
+    /// Remove every route whose `expires_at` deadline has passed, returning
+    /// how many were reclaimed. Mirrors a periodic sweep over learned
+    /// dynamic-protocol and DHCP-supplied routes, so a stale entry doesn't
+    /// linger forever as a candidate best path.
+    pub fn purge_expired(&mut self) -> usize {
+        let now = Self::now_ms();
+        let expired: Vec<PrefixKey> = self
+            .routes
+            .iter()
+            .filter(|(_, route)| Self::is_expired(route, now))
+            .map(|(key, _)| *key)
+            .collect();
+
+        for key in &expired {
+            self.remove_key(*key);
+        }
+
+        if !expired.is_empty() {
+            self.update_timestamp();
+            self.total_updates += expired.len() as u64;
+        }
+
+        expired.len()
+    }
+
+    /// Earliest `expires_at` deadline across all routes, so a caller can
+    /// schedule its next `purge_expired` sweep instead of polling blindly.
+    pub fn next_expiry(&self) -> Option<u64> {
+        self.routes.values().filter_map(|route| route.expires_at).min()
+    }
+
     /// Update an existing route
     pub fn update_route(&mut self, destination: &str, route: &Route) -> Result<(), String> {
-        if !self.routes.contains_key(destination) {
+        let key = PrefixKey::parse(destination)?;
+        if !self.routes.contains_key(&key) {
             return Err(format!("Route {} not found", destination));
         }
 
         self.validate_route(route)?;
-        self.routes.insert(destination.to_string(), route.clone());
+        self.routes.insert(key, route.clone());
+        self.trie_for(&key).insert(key);
         self.update_timestamp();
         self.total_updates += 1;
 
@@ -85,7 +369,8 @@ impl RoutingTable {
 
     /// Get a route by destination
     pub fn get_route(&self, destination: &str) -> Option<&Route> {
-        self.routes.get(destination)
+        let key = PrefixKey::parse(destination).ok()?;
+        self.routes.get(&key)
     }
 
     /// Get all routes
@@ -107,115 +392,94 @@ impl RoutingTable {
             .collect()
     }
 
-    /// Get active routes only
+    /// Get active routes only, excluding any that have aged past `expires_at`
     pub fn get_active_routes(&self) -> Vec<&Route> {
+        let now = Self::now_ms();
         self.routes.values()
-            .filter(|route| route.is_active)
+            .filter(|route| route.is_active && !Self::is_expired(route, now))
             .collect()
     }
 
-    /// Find the best route for a destination IP
+    /// Find the best route for a destination IP via longest-prefix-match:
+    /// descend the address-family trie remembering the deepest node that
+    /// carried a route, so an exact `/32` or `/128` always wins over a
+    /// shorter prefix and a `0.0.0.0/0`/`::/0` default always falls back.
+    /// A route past its TTL is treated as if it were inactive.
     pub fn find_best_route(&self, dest_ip: &str) -> Option<&Route> {
-        let dest_addr = match IpAddr::from_str(dest_ip) {
-            Ok(addr) => addr,
-            Err(_) => return None,
-        };
+        let dest_addr = IpAddr::from_str(dest_ip).ok()?;
+        self.lookup_addr(dest_addr)
+    }
 
-        let mut best_route: Option<&Route> = None;
-        let mut best_metric = u32::MAX;
+    fn lookup_addr(&self, dest_addr: IpAddr) -> Option<&Route> {
+        let now = Self::now_ms();
+        let is_live = |key: &PrefixKey| {
+            self.routes.get(key).is_some_and(|route| route.is_active && !Self::is_expired(route, now))
+        };
 
-        for route in self.routes.values() {
-            if !route.is_active {
-                continue;
-            }
+        let key = match dest_addr {
+            IpAddr::V4(v4) => self.v4_trie.lookup(&v4.octets(), 32, is_live),
+            IpAddr::V6(v6) => self.v6_trie.lookup(&v6.octets(), 128, is_live),
+        }?;
 
-            if self.matches_destination(dest_addr, &route.destination) {
-                if route.metric < best_metric {
-                    best_metric = route.metric;
-                    best_route = Some(route);
-                }
-            }
-        }
-
-        best_route
+        self.routes.get(&key)
     }
 
-    /// Check if a destination IP matches a route
-    fn matches_destination(&self, dest_ip: IpAddr, route_dest: &str) -> bool {
-        // Parse route destination (e.g., "192.168.1.0/24")
-        let parts: Vec<&str> = route_dest.split('/').collect();
-        if parts.len() != 2 {
-            return false;
-        }
-
-        let network_addr = match IpAddr::from_str(parts[0]) {
-            Ok(addr) => addr,
-            Err(_) => return false,
+    /// Parse a raw L3 packet buffer and resolve its destination against the
+    /// routing table in one step, for a forwarding fast-path that hands over
+    /// the packet it received instead of pre-formatting a destination string.
+    /// The IP version comes from the high nibble of byte 0: IPv4 (version 4)
+    /// needs at least 20 bytes with the destination at bytes 16..20, IPv6
+    /// (version 6) needs at least 40 bytes with the destination at
+    /// bytes 24..40. Anything empty, truncated, or an unrecognized version
+    /// is rejected rather than panicking on an out-of-bounds slice.
+    pub fn resolve_packet(&self, packet: &[u8]) -> Option<(&Route, IpAddr)> {
+        let version = packet.first()? >> 4;
+
+        let dest_addr = match version {
+            4 if packet.len() >= 20 => {
+                let mut octets = [0u8; 4];
+                octets.copy_from_slice(&packet[16..20]);
+                IpAddr::V4(Ipv4Addr::from(octets))
+            }
+            6 if packet.len() >= 40 => {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(&packet[24..40]);
+                IpAddr::V6(Ipv6Addr::from(octets))
+            }
+            _ => return None,
         };
 
-        let prefix_len = match parts[1].parse::<u8>() {
-            Ok(len) => len,
-            Err(_) => return false,
-        };
+        self.lookup_addr(dest_addr).map(|route| (route, dest_addr))
+    }
 
-        // Check if IPs are the same family
-        match (dest_ip, network_addr) {
-            (IpAddr::V4(dest), IpAddr::V4(net)) => {
-                self.matches_ipv4_network(dest, net, prefix_len)
+    /// Check if a destination IP matches a network key, operating directly
+    /// on the packed octets rather than re-parsing a destination string.
+    fn matches_destination(&self, dest_ip: IpAddr, key: &PrefixKey) -> bool {
+        match (dest_ip, key) {
+            (IpAddr::V4(dest), PrefixKey::V4 { addr, prefix_len }) => {
+                self.matches_ipv4_network(dest, *addr, *prefix_len)
             }
-            (IpAddr::V6(dest), IpAddr::V6(net)) => {
-                self.matches_ipv6_network(dest, net, prefix_len)
+            (IpAddr::V6(dest), PrefixKey::V6 { addr, prefix_len }) => {
+                self.matches_ipv6_network(dest, *addr, *prefix_len)
             }
             _ => false,
         }
     }
 
-    /// Check if an IPv4 address matches a network
-    fn matches_ipv4_network(&self, dest: Ipv4Addr, network: Ipv4Addr, prefix_len: u8) -> bool {
+    /// Check if an IPv4 address matches a network's stored (already masked) octets
+    fn matches_ipv4_network(&self, dest: Ipv4Addr, network: [u8; 4], prefix_len: u8) -> bool {
         if prefix_len > 32 {
             return false;
         }
-
-        let mask = if prefix_len == 0 {
-            0
-        } else {
-            !((1u32 << (32 - prefix_len)) - 1)
-        };
-
-        let dest_u32 = u32::from(dest);
-        let network_u32 = u32::from(network);
-
-        (dest_u32 & mask) == (network_u32 & mask)
+        mask_bits(dest.octets(), prefix_len) == network
     }
 
-    /// Check if an IPv6 address matches a network
-    fn matches_ipv6_network(&self, dest: Ipv6Addr, network: Ipv6Addr, prefix_len: u8) -> bool {
+    /// Check if an IPv6 address matches a network's stored (already masked) octets
+    fn matches_ipv6_network(&self, dest: Ipv6Addr, network: [u8; 16], prefix_len: u8) -> bool {
         if prefix_len > 128 {
             return false;
         }
-
-        let dest_bytes = dest.octets();
-        let network_bytes = network.octets();
-
-        let full_bytes = (prefix_len / 8) as usize;
-        let remaining_bits = prefix_len % 8;
-
-        // Check full bytes
-        for i in 0..full_bytes {
-            if dest_bytes[i] != network_bytes[i] {
-                return false;
-            }
-        }
-
-        // Check remaining bits
-        if remaining_bits > 0 && full_bytes < 16 {
-            let mask = 0xFF << (8 - remaining_bits);
-            if (dest_bytes[full_bytes] & mask) != (network_bytes[full_bytes] & mask) {
-                return false;
-            }
-        }
-
-        true
+        mask_bits(dest.octets(), prefix_len) == network
     }
 
     /// Validate a route before adding/updating
@@ -248,18 +512,23 @@ impl RoutingTable {
         Ok(())
     }
 
-    /// Update timestamp
-    fn update_timestamp(&mut self) {
-        self.last_update = std::time::SystemTime::now()
+    fn now_ms() -> u64 {
+        std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
-            .as_millis() as u64;
+            .as_millis() as u64
+    }
+
+    /// Update timestamp
+    fn update_timestamp(&mut self) {
+        self.last_update = Self::now_ms();
     }
 
     /// Get routing statistics
     pub fn get_stats(&self) -> RoutingStats {
+        let now = Self::now_ms();
         let total_routes = self.routes.len();
-        let active_routes = self.routes.values().filter(|r| r.is_active).count();
+        let active_routes = self.routes.values().filter(|r| r.is_active && !Self::is_expired(r, now)).count();
         let bgp_routes = self.protocol_counts.get("bgp").unwrap_or(&0);
         let ospf_routes = self.protocol_counts.get("ospf").unwrap_or(&0);
         let isis_routes = self.protocol_counts.get("isis").unwrap_or(&0);
@@ -305,6 +574,8 @@ impl RoutingTable {
         self.routes.clear();
         self.protocol_counts.clear();
         self.interface_counts.clear();
+        self.v4_trie = RadixTrie::default();
+        self.v6_trie = RadixTrie::default();
         self.last_update = 0;
         self.total_updates = 0;
     }
@@ -344,6 +615,49 @@ impl RoutingTable {
             total_updates: self.total_updates,
         }
     }
+
+    /// Serialize every route plus `last_update`/`total_updates` to a
+    /// human-readable JSON file, so a restarting router can recover its
+    /// table without re-converging and an operator can diff it directly.
+    pub fn save_to_path(&self, path: &Path) -> Result<(), String> {
+        let snapshot = PersistedRoutingTable {
+            routes: self.routes.values().cloned().collect(),
+            last_update: self.last_update,
+            total_updates: self.total_updates,
+        };
+        let json = serde_json::to_string_pretty(&snapshot).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| e.to_string())
+    }
+
+    /// Rebuild a table (routes, protocol/interface counters, and tries) from
+    /// a file written by [`Self::save_to_path`]. Tolerant of partial
+    /// corruption: individual routes that fail `validate_route` are dropped
+    /// and logged rather than aborting the whole load. Only a malformed file
+    /// itself (unreadable or not valid JSON) fails the load outright.
+    pub fn load_from_path(path: &Path) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let snapshot: PersistedRoutingTable = serde_json::from_str(&json).map_err(|e| e.to_string())?;
+
+        let mut table = Self::new();
+        for route in snapshot.routes {
+            if let Err(e) = table.add_route(&route) {
+                warn!(destination = %route.destination, error = %e, "dropping corrupt route while loading routing table");
+            }
+        }
+        table.last_update = snapshot.last_update;
+        table.total_updates = snapshot.total_updates;
+
+        Ok(table)
+    }
+}
+
+/// On-disk form of a [`RoutingTable`]: its routes plus the counters that
+/// aren't derivable from them alone.
+#[derive(Debug, Serialize, Deserialize)]
+struct PersistedRoutingTable {
+    routes: Vec<Route>,
+    last_update: u64,
+    total_updates: u64,
 }
 
 /// Detailed routing statistics
@@ -415,7 +729,7 @@ mod tests {
     #[test]
     fn test_find_best_route() {
         let mut table = RoutingTable::new();
-        
+
         // Add default route
         let default_route = Route::new(
             "0.0.0.0/0".to_string(),
@@ -447,7 +761,7 @@ mod tests {
     #[test]
     fn test_route_validation() {
         let mut table = RoutingTable::new();
-        
+
         // Test invalid route (empty destination)
         let invalid_route = Route::new(
             "".to_string(),
@@ -474,23 +788,336 @@ mod tests {
     #[test]
     fn test_ipv4_network_matching() {
         let table = RoutingTable::new();
-        
+
         // Test exact match
         assert!(table.matches_destination(
             IpAddr::V4(Ipv4Addr::new(192, 168, 1, 100)),
-            "192.168.1.100/32"
+            &PrefixKey::parse("192.168.1.100/32").unwrap()
         ));
 
         // Test network match
         assert!(table.matches_destination(
             IpAddr::V4(Ipv4Addr::new(192, 168, 1, 100)),
-            "192.168.1.0/24"
+            &PrefixKey::parse("192.168.1.0/24").unwrap()
         ));
 
         // Test no match
         assert!(!table.matches_destination(
             IpAddr::V4(Ipv4Addr::new(192, 168, 2, 100)),
-            "192.168.1.0/24"
+            &PrefixKey::parse("192.168.1.0/24").unwrap()
         ));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_exact_match_beats_shorter_prefix_regardless_of_metric() {
+        let mut table = RoutingTable::new();
+
+        let broad = Route::new("192.168.0.0/16".to_string(), "192.168.1.1".to_string(), "eth0".to_string(), 16, 1, "static".to_string());
+        table.add_route(&broad).unwrap();
+
+        let exact = Route::new("192.168.1.100/32".to_string(), "192.168.1.1".to_string(), "eth0".to_string(), 32, 100, "static".to_string());
+        table.add_route(&exact).unwrap();
+
+        let best = table.find_best_route("192.168.1.100").unwrap();
+        assert_eq!(best.destination, "192.168.1.100/32");
+    }
+
+    #[test]
+    fn test_default_route_is_the_fallback() {
+        let mut table = RoutingTable::new();
+
+        let default_route = Route::new("0.0.0.0/0".to_string(), "192.168.1.1".to_string(), "eth0".to_string(), 0, 10, "static".to_string());
+        table.add_route(&default_route).unwrap();
+
+        let best = table.find_best_route("8.8.8.8").unwrap();
+        assert_eq!(best.destination, "0.0.0.0/0");
+    }
+
+    #[test]
+    fn test_removed_prefix_no_longer_matches() {
+        let mut table = RoutingTable::new();
+
+        let route = Route::new("10.0.0.0/24".to_string(), "10.0.0.1".to_string(), "eth0".to_string(), 24, 1, "static".to_string());
+        table.add_route(&route).unwrap();
+        assert!(table.find_best_route("10.0.0.5").is_some());
+
+        table.remove_route("10.0.0.0/24").unwrap();
+        assert!(table.find_best_route("10.0.0.5").is_none());
+    }
+
+    #[test]
+    fn test_ipv4_and_ipv6_tries_stay_independent() {
+        let mut table = RoutingTable::new();
+
+        let v4_default = Route::new("0.0.0.0/0".to_string(), "192.168.1.1".to_string(), "eth0".to_string(), 0, 10, "static".to_string());
+        table.add_route(&v4_default).unwrap();
+
+        let v6_route = Route::new("2001:db8::/32".to_string(), "::1".to_string(), "eth0".to_string(), 32, 10, "static".to_string());
+        table.add_route(&v6_route).unwrap();
+
+        assert_eq!(table.find_best_route("2001:db8::1").unwrap().destination, "2001:db8::/32");
+        assert!(table.find_best_route("2001:db9::1").is_none());
+    }
+
+    #[test]
+    fn test_destinations_with_the_same_masked_network_share_a_key() {
+        let mut table = RoutingTable::new();
+
+        let first = Route::new("10.0.0.5/24".to_string(), "10.0.0.1".to_string(), "eth0".to_string(), 24, 5, "static".to_string());
+        table.add_route(&first).unwrap();
+
+        // Same network once host bits are masked off, so this overwrites the
+        // first route's entry rather than creating a second one.
+        let second = Route::new("10.0.0.9/24".to_string(), "10.0.0.1".to_string(), "eth0".to_string(), 24, 1, "static".to_string());
+        table.add_route(&second).unwrap();
+
+        assert_eq!(table.get_stats().total_routes, 1);
+        assert_eq!(table.find_best_route("10.0.0.200").unwrap().destination, "10.0.0.9/24");
+    }
+
+    #[test]
+    fn test_expired_route_is_treated_as_inactive() {
+        let mut table = RoutingTable::new();
+
+        let mut route = Route::new("10.0.0.0/24".to_string(), "10.0.0.1".to_string(), "eth0".to_string(), 24, 1, "static".to_string());
+        route.expires_at = Some(1); // already in the past
+        table.add_route(&route).unwrap();
+
+        assert!(table.find_best_route("10.0.0.5").is_none());
+        assert!(table.get_active_routes().is_empty());
+        assert_eq!(table.get_stats().active_routes, 0);
+        assert_eq!(table.get_stats().total_routes, 1);
+    }
+
+    #[test]
+    fn test_expired_exact_match_falls_back_to_active_shorter_prefix() {
+        let mut table = RoutingTable::new();
+
+        let broad = Route::new("10.0.0.0/16".to_string(), "10.0.1.1".to_string(), "eth0".to_string(), 16, 1, "static".to_string());
+        table.add_route(&broad).unwrap();
+
+        let mut exact = Route::new("10.0.0.5/32".to_string(), "10.0.0.1".to_string(), "eth1".to_string(), 32, 1, "static".to_string());
+        exact.expires_at = Some(1); // already in the past
+        table.add_route(&exact).unwrap();
+
+        // The more-specific /32 is expired, so the lookup should fall back
+        // to the still-active /16 rather than returning nothing.
+        let best = table.find_best_route("10.0.0.5").unwrap();
+        assert_eq!(best.destination, "10.0.0.0/16");
+    }
+
+    #[test]
+    fn test_purge_expired_removes_stale_routes_and_updates_counters() {
+        let mut table = RoutingTable::new();
+
+        let mut stale = Route::new("10.0.0.0/24".to_string(), "10.0.0.1".to_string(), "eth0".to_string(), 24, 1, "static".to_string());
+        stale.expires_at = Some(1);
+        table.add_route(&stale).unwrap();
+
+        let fresh = Route::new("192.168.1.0/24".to_string(), "192.168.1.1".to_string(), "eth0".to_string(), 24, 1, "static".to_string());
+        table.add_route(&fresh).unwrap();
+
+        assert_eq!(table.purge_expired(), 1);
+        let stats = table.get_stats();
+        assert_eq!(stats.total_routes, 1);
+        assert_eq!(stats.static_routes, 1);
+        assert!(table.get_route("10.0.0.0/24").is_none());
+    }
+
+    #[test]
+    fn test_next_expiry_returns_the_earliest_deadline() {
+        let mut table = RoutingTable::new();
+
+        let mut later = Route::new("10.0.0.0/24".to_string(), "10.0.0.1".to_string(), "eth0".to_string(), 24, 1, "static".to_string());
+        later.expires_at = Some(5000);
+        table.add_route(&later).unwrap();
+
+        let mut sooner = Route::new("192.168.1.0/24".to_string(), "192.168.1.1".to_string(), "eth0".to_string(), 24, 1, "static".to_string());
+        sooner.expires_at = Some(1000);
+        table.add_route(&sooner).unwrap();
+
+        assert_eq!(table.next_expiry(), Some(1000));
+    }
+
+    fn ipv4_packet(dest: Ipv4Addr) -> Vec<u8> {
+        let mut packet = vec![0u8; 20];
+        packet[0] = 0x45; // version 4, IHL 5
+        packet[16..20].copy_from_slice(&dest.octets());
+        packet
+    }
+
+    #[test]
+    fn test_resolve_packet_dissects_ipv4_destination_and_resolves_it() {
+        let mut table = RoutingTable::new();
+        let route = Route::new("192.168.1.0/24".to_string(), "192.168.1.1".to_string(), "eth0".to_string(), 24, 1, "static".to_string());
+        table.add_route(&route).unwrap();
+
+        let packet = ipv4_packet(Ipv4Addr::new(192, 168, 1, 42));
+        let (resolved, dest) = table.resolve_packet(&packet).unwrap();
+        assert_eq!(resolved.destination, "192.168.1.0/24");
+        assert_eq!(dest, IpAddr::V4(Ipv4Addr::new(192, 168, 1, 42)));
+    }
+
+    #[test]
+    fn test_resolve_packet_dissects_ipv6_destination_and_resolves_it() {
+        let mut table = RoutingTable::new();
+        let route = Route::new("2001:db8::/32".to_string(), "::1".to_string(), "eth0".to_string(), 32, 1, "static".to_string());
+        table.add_route(&route).unwrap();
+
+        let mut packet = vec![0u8; 40];
+        packet[0] = 0x60; // version 6
+        let dest = Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1);
+        packet[24..40].copy_from_slice(&dest.octets());
+
+        let (resolved, resolved_dest) = table.resolve_packet(&packet).unwrap();
+        assert_eq!(resolved.destination, "2001:db8::/32");
+        assert_eq!(resolved_dest, IpAddr::V6(dest));
+    }
+
+    #[test]
+    fn test_resolve_packet_rejects_empty_truncated_and_unknown_version_buffers() {
+        let table = RoutingTable::new();
+
+        assert!(table.resolve_packet(&[]).is_none());
+        assert!(table.resolve_packet(&[0x45, 0, 0]).is_none()); // version 4, too short
+        assert!(table.resolve_packet(&[0x60; 39]).is_none()); // version 6, one byte short
+        assert!(table.resolve_packet(&[0x90; 20]).is_none()); // unrecognized version
+    }
+
+    #[test]
+    fn test_default_comparator_keeps_the_lowest_metric_on_collision() {
+        let mut table = RoutingTable::new();
+
+        let worse = Route::new("10.0.0.0/24".to_string(), "10.0.0.1".to_string(), "eth0".to_string(), 24, 10, "static".to_string());
+        table.add_route(&worse).unwrap();
+
+        let better = Route::new("10.0.0.0/24".to_string(), "10.0.0.2".to_string(), "eth1".to_string(), 24, 1, "static".to_string());
+        table.add_route(&better).unwrap();
+
+        assert_eq!(table.find_best_route("10.0.0.5").unwrap().gateway, "10.0.0.2");
+
+        // A worse metric arriving after shouldn't displace the installed route.
+        let worst = Route::new("10.0.0.0/24".to_string(), "10.0.0.3".to_string(), "eth2".to_string(), 24, 20, "static".to_string());
+        table.add_route(&worst).unwrap();
+        assert_eq!(table.find_best_route("10.0.0.5").unwrap().gateway, "10.0.0.2");
+    }
+
+    #[test]
+    fn test_bgp_comparator_prefers_higher_local_pref_over_lower_metric() {
+        let mut table = RoutingTable::with_path_comparator(bgp_best_path);
+
+        let mut low_pref_low_metric = Route::new("10.0.0.0/24".to_string(), "10.0.0.1".to_string(), "eth0".to_string(), 24, 1, "bgp".to_string());
+        low_pref_low_metric.local_pref = Some(50);
+        table.add_route(&low_pref_low_metric).unwrap();
+
+        let mut high_pref_high_metric = Route::new("10.0.0.0/24".to_string(), "10.0.0.2".to_string(), "eth1".to_string(), 24, 100, "bgp".to_string());
+        high_pref_high_metric.local_pref = Some(200);
+        table.add_route(&high_pref_high_metric).unwrap();
+
+        assert_eq!(table.find_best_route("10.0.0.5").unwrap().gateway, "10.0.0.2");
+    }
+
+    #[test]
+    fn test_bgp_comparator_falls_back_to_as_path_len_then_med_then_metric() {
+        let mut table = RoutingTable::with_path_comparator(bgp_best_path);
+
+        let mut long_as_path = Route::new("10.0.0.0/24".to_string(), "10.0.0.1".to_string(), "eth0".to_string(), 24, 1, "bgp".to_string());
+        long_as_path.local_pref = Some(100);
+        long_as_path.as_path_len = Some(5);
+        table.add_route(&long_as_path).unwrap();
+
+        let mut short_as_path = Route::new("10.0.0.0/24".to_string(), "10.0.0.2".to_string(), "eth1".to_string(), 24, 50, "bgp".to_string());
+        short_as_path.local_pref = Some(100);
+        short_as_path.as_path_len = Some(2);
+        table.add_route(&short_as_path).unwrap();
+
+        assert_eq!(table.find_best_route("10.0.0.5").unwrap().gateway, "10.0.0.2");
+
+        let mut lower_med = Route::new("10.0.0.0/24".to_string(), "10.0.0.3".to_string(), "eth2".to_string(), 24, 50, "bgp".to_string());
+        lower_med.local_pref = Some(100);
+        lower_med.as_path_len = Some(2);
+        lower_med.med = Some(10);
+        table.add_route(&lower_med).unwrap();
+
+        assert_eq!(table.find_best_route("10.0.0.5").unwrap().gateway, "10.0.0.3");
+
+        let mut lower_metric = Route::new("10.0.0.0/24".to_string(), "10.0.0.4".to_string(), "eth3".to_string(), 24, 5, "bgp".to_string());
+        lower_metric.local_pref = Some(100);
+        lower_metric.as_path_len = Some(2);
+        lower_metric.med = Some(10);
+        table.add_route(&lower_metric).unwrap();
+
+        assert_eq!(table.find_best_route("10.0.0.5").unwrap().gateway, "10.0.0.4");
+    }
+
+    #[test]
+    fn test_route_update_always_replaces_regardless_of_comparator() {
+        let mut table = RoutingTable::with_path_comparator(bgp_best_path);
+
+        let mut original = Route::new("10.0.0.0/24".to_string(), "10.0.0.1".to_string(), "eth0".to_string(), 24, 1, "bgp".to_string());
+        original.local_pref = Some(200);
+        table.add_route(&original).unwrap();
+
+        // update_route always replaces the installed route for a destination,
+        // unlike add_route, which would reject this for losing the comparison.
+        let mut updated = original.clone();
+        updated.gateway = "10.0.0.9".to_string();
+        updated.local_pref = Some(1);
+        table.update_route("10.0.0.0/24", &updated).unwrap();
+
+        assert_eq!(table.find_best_route("10.0.0.5").unwrap().gateway, "10.0.0.9");
+        assert_eq!(table.get_stats().total_routes, 1);
+    }
+
+    fn scratch_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("routing_table_test_{}_{}.json", std::process::id(), name))
+    }
+
+    #[test]
+    fn test_save_and_load_round_trips_routes_and_counters() {
+        let path = scratch_path("round_trip");
+        let mut table = RoutingTable::new();
+        table.add_route(&Route::new("10.0.0.0/24".to_string(), "10.0.0.1".to_string(), "eth0".to_string(), 24, 1, "static".to_string())).unwrap();
+        table.add_route(&Route::new("0.0.0.0/0".to_string(), "192.168.1.1".to_string(), "eth1".to_string(), 0, 10, "bgp".to_string())).unwrap();
+
+        table.save_to_path(&path).unwrap();
+        let loaded = RoutingTable::load_from_path(&path).unwrap();
+
+        assert_eq!(loaded.get_stats().total_routes, 2);
+        assert_eq!(loaded.find_best_route("10.0.0.5").unwrap().gateway, "10.0.0.1");
+        assert_eq!(loaded.last_update, table.last_update);
+        assert_eq!(loaded.total_updates, table.total_updates);
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_load_drops_corrupt_routes_but_keeps_the_rest() {
+        let path = scratch_path("partial_corruption");
+        let json = r#"{
+            "routes": [
+                {"destination": "10.0.0.0/24", "gateway": "10.0.0.1", "interface": "eth0", "prefix_length": 24, "metric": 1, "protocol": "static", "is_active": true, "expires_at": null, "local_pref": null, "as_path_len": null, "med": null},
+                {"destination": "", "gateway": "10.0.0.1", "interface": "eth0", "prefix_length": 24, "metric": 1, "protocol": "static", "is_active": true, "expires_at": null, "local_pref": null, "as_path_len": null, "med": null}
+            ],
+            "last_update": 42,
+            "total_updates": 2
+        }"#;
+        std::fs::write(&path, json).unwrap();
+
+        let loaded = RoutingTable::load_from_path(&path).unwrap();
+        assert_eq!(loaded.get_stats().total_routes, 1);
+        assert!(loaded.get_route("10.0.0.0/24").is_some());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_load_from_path_rejects_malformed_json() {
+        let path = scratch_path("malformed");
+        std::fs::write(&path, "not json").unwrap();
+
+        assert!(RoutingTable::load_from_path(&path).is_err());
+
+        std::fs::remove_file(&path).unwrap();
+    }
+}
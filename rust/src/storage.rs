@@ -0,0 +1,478 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use crate::{MetricData, MetricFilter, Packet, PacketFilter, Route, RouteFilter};
+
+/// Storage for packets, routes, and metric samples recorded by
+/// [`crate::RouterAnalytics`], so [`crate::AnalyticsQuery`] can answer
+/// historical questions instead of only reporting the latest live state.
+/// Adapters are interchangeable behind this one interface, the same way a
+/// key-value store stays behind a single narrow `Db` trait regardless of
+/// which engine backs it.
+pub trait Backend: Send + Sync {
+    fn record_packet(&self, packet: &Packet) -> Result<(), String>;
+    fn record_route(&self, route: &Route) -> Result<(), String>;
+    fn record_metric(&self, metric: &MetricData) -> Result<(), String>;
+
+    fn query_packets(&self, filter: &PacketFilter) -> Result<Vec<Packet>, String>;
+    fn query_routes(&self, filter: &RouteFilter) -> Result<Vec<Route>, String>;
+    fn query_metrics(&self, filter: &MetricFilter) -> Result<Vec<MetricData>, String>;
+}
+
+fn packet_matches(filter: &PacketFilter, packet: &Packet) -> bool {
+    filter.source_ip.as_deref().map_or(true, |ip| ip == packet.source_ip)
+        && filter.dest_ip.as_deref().map_or(true, |ip| ip == packet.dest_ip)
+        && filter.protocol.map_or(true, |p| p == packet.protocol)
+        && filter
+            .port_range
+            .map_or(true, |(low, high)| packet.source_port >= low && packet.source_port <= high)
+        && filter
+            .time_range
+            .map_or(true, |(start, end)| packet.timestamp >= start && packet.timestamp <= end)
+        && filter.size_range.map_or(true, |(low, high)| packet.size >= low && packet.size <= high)
+}
+
+fn route_matches(filter: &RouteFilter, route: &Route) -> bool {
+    filter.destination.as_deref().map_or(true, |d| d == route.destination)
+        && filter.protocol.as_deref().map_or(true, |p| p == route.protocol)
+        && filter.interface.as_deref().map_or(true, |i| i == route.interface)
+        && filter.metric_range.map_or(true, |(low, high)| route.metric >= low && route.metric <= high)
+        && filter.is_active.map_or(true, |active| active == route.is_active)
+}
+
+fn metric_matches(filter: &MetricFilter, metric: &MetricData) -> bool {
+    filter.name.as_deref().map_or(true, |name| name == metric.name)
+        && filter
+            .time_range
+            .map_or(true, |(start, end)| metric.timestamp >= start && metric.timestamp <= end)
+        && filter.value_range.map_or(true, |(low, high)| metric.value >= low && metric.value <= high)
+}
+
+/// Render a metric's tags as `key=value` pairs joined by `,`, the same
+/// encoding [`SqliteBackend`] stores them with.
+fn encode_tags(tags: &HashMap<String, String>) -> String {
+    tags.iter().map(|(k, v)| format!("{k}={v}")).collect::<Vec<_>>().join(",")
+}
+
+fn decode_tags(raw: &str) -> HashMap<String, String> {
+    raw.split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+/// In-memory [`Backend`] that keeps the most recent `capacity` records of
+/// each kind, dropping the oldest once full. No persistence across restarts;
+/// intended for tests and for routers that don't need historical queries to
+/// survive a crash.
+pub struct RingBufferBackend {
+    capacity: usize,
+    packets: Mutex<VecDeque<Packet>>,
+    routes: Mutex<VecDeque<Route>>,
+    metrics: Mutex<VecDeque<MetricData>>,
+}
+
+impl RingBufferBackend {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            packets: Mutex::new(VecDeque::new()),
+            routes: Mutex::new(VecDeque::new()),
+            metrics: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    fn push<T>(buffer: &Mutex<VecDeque<T>>, capacity: usize, item: T) {
+        let mut buffer = buffer.lock().unwrap();
+        if buffer.len() >= capacity {
+            buffer.pop_front();
+        }
+        buffer.push_back(item);
+    }
+}
+
+impl Backend for RingBufferBackend {
+    fn record_packet(&self, packet: &Packet) -> Result<(), String> {
+        Self::push(&self.packets, self.capacity, packet.clone());
+        Ok(())
+    }
+
+    fn record_route(&self, route: &Route) -> Result<(), String> {
+        Self::push(&self.routes, self.capacity, route.clone());
+        Ok(())
+    }
+
+    fn record_metric(&self, metric: &MetricData) -> Result<(), String> {
+        Self::push(&self.metrics, self.capacity, metric.clone());
+        Ok(())
+    }
+
+    fn query_packets(&self, filter: &PacketFilter) -> Result<Vec<Packet>, String> {
+        Ok(self.packets.lock().unwrap().iter().filter(|p| packet_matches(filter, p)).cloned().collect())
+    }
+
+    fn query_routes(&self, filter: &RouteFilter) -> Result<Vec<Route>, String> {
+        Ok(self.routes.lock().unwrap().iter().filter(|r| route_matches(filter, r)).cloned().collect())
+    }
+
+    fn query_metrics(&self, filter: &MetricFilter) -> Result<Vec<MetricData>, String> {
+        Ok(self.metrics.lock().unwrap().iter().filter(|m| metric_matches(filter, m)).cloned().collect())
+    }
+}
+
+fn sqlite_schema_sql() -> &'static str {
+    "
+    CREATE TABLE IF NOT EXISTS packets (
+        id INTEGER, size INTEGER, priority INTEGER, source_ip TEXT, dest_ip TEXT,
+        source_port INTEGER, dest_port INTEGER, protocol INTEGER, timestamp INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS packets_timestamp_idx ON packets(timestamp);
+    CREATE INDEX IF NOT EXISTS packets_source_ip_idx ON packets(source_ip);
+    CREATE INDEX IF NOT EXISTS packets_protocol_idx ON packets(protocol);
+
+    CREATE TABLE IF NOT EXISTS routes (
+        destination TEXT, gateway TEXT, interface TEXT, prefix_length INTEGER,
+        metric INTEGER, protocol TEXT, is_active INTEGER
+    );
+    CREATE INDEX IF NOT EXISTS routes_destination_idx ON routes(destination);
+    CREATE INDEX IF NOT EXISTS routes_protocol_idx ON routes(protocol);
+
+    CREATE TABLE IF NOT EXISTS metric_samples (
+        name TEXT, value REAL, timestamp INTEGER, tags TEXT
+    );
+    CREATE INDEX IF NOT EXISTS metric_samples_name_idx ON metric_samples(name);
+    CREATE INDEX IF NOT EXISTS metric_samples_timestamp_idx ON metric_samples(timestamp);
+    "
+}
+
+/// [`Backend`] persisted to a SQLite file, for single-node deployments where
+/// historical queries need to survive a restart without standing up a
+/// separate database server.
+pub struct SqliteBackend {
+    conn: Mutex<rusqlite::Connection>,
+}
+
+impl SqliteBackend {
+    pub fn open(path: impl AsRef<std::path::Path>) -> Result<Self, String> {
+        let conn = rusqlite::Connection::open(path).map_err(|e| e.to_string())?;
+        conn.execute_batch(sqlite_schema_sql()).map_err(|e| e.to_string())?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    pub fn open_in_memory() -> Result<Self, String> {
+        let conn = rusqlite::Connection::open_in_memory().map_err(|e| e.to_string())?;
+        conn.execute_batch(sqlite_schema_sql()).map_err(|e| e.to_string())?;
+        Ok(Self { conn: Mutex::new(conn) })
+    }
+
+    /// Build a `WHERE` clause and its bound parameters from `conditions`,
+    /// each a `column = ?`, `column >= ?` or `column <= ?` predicate, joined
+    /// with `AND`; an equality field of a filter gives one predicate and a
+    /// range field gives two.
+    fn where_clause(conditions: Vec<(&'static str, Box<dyn rusqlite::ToSql>)>) -> (String, Vec<Box<dyn rusqlite::ToSql>>) {
+        if conditions.is_empty() {
+            return (String::new(), Vec::new());
+        }
+        let clause = conditions.iter().map(|(predicate, _)| *predicate).collect::<Vec<_>>().join(" AND ");
+        let params = conditions.into_iter().map(|(_, value)| value).collect();
+        (format!(" WHERE {clause}"), params)
+    }
+}
+
+impl Backend for SqliteBackend {
+    fn record_packet(&self, packet: &Packet) -> Result<(), String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO packets (id, size, priority, source_ip, dest_ip, source_port, dest_port, protocol, timestamp)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                rusqlite::params![
+                    packet.id as i64,
+                    packet.size as i64,
+                    packet.priority as i64,
+                    packet.source_ip,
+                    packet.dest_ip,
+                    packet.source_port as i64,
+                    packet.dest_port as i64,
+                    packet.protocol as i64,
+                    packet.timestamp as i64,
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn record_route(&self, route: &Route) -> Result<(), String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO routes (destination, gateway, interface, prefix_length, metric, protocol, is_active)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                rusqlite::params![
+                    route.destination,
+                    route.gateway,
+                    route.interface,
+                    route.prefix_length as i64,
+                    route.metric as i64,
+                    route.protocol,
+                    route.is_active as i64,
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn record_metric(&self, metric: &MetricData) -> Result<(), String> {
+        self.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "INSERT INTO metric_samples (name, value, timestamp, tags) VALUES (?1, ?2, ?3, ?4)",
+                rusqlite::params![metric.name, metric.value, metric.timestamp as i64, encode_tags(&metric.tags)],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    fn query_packets(&self, filter: &PacketFilter) -> Result<Vec<Packet>, String> {
+        let mut conditions: Vec<(&'static str, Box<dyn rusqlite::ToSql>)> = Vec::new();
+        if let Some(ip) = &filter.source_ip {
+            conditions.push(("source_ip = ?", Box::new(ip.clone())));
+        }
+        if let Some(ip) = &filter.dest_ip {
+            conditions.push(("dest_ip = ?", Box::new(ip.clone())));
+        }
+        if let Some(protocol) = filter.protocol {
+            conditions.push(("protocol = ?", Box::new(protocol as i64)));
+        }
+        if let Some((low, high)) = filter.port_range {
+            conditions.push(("source_port >= ?", Box::new(low as i64)));
+            conditions.push(("source_port <= ?", Box::new(high as i64)));
+        }
+        if let Some((start, end)) = filter.time_range {
+            conditions.push(("timestamp >= ?", Box::new(start as i64)));
+            conditions.push(("timestamp <= ?", Box::new(end as i64)));
+        }
+        if let Some((low, high)) = filter.size_range {
+            conditions.push(("size >= ?", Box::new(low as i64)));
+            conditions.push(("size <= ?", Box::new(high as i64)));
+        }
+        let (where_clause, params) = Self::where_clause(conditions);
+
+        let conn = self.conn.lock().unwrap();
+        let sql = format!(
+            "SELECT id, size, priority, source_ip, dest_ip, source_port, dest_port, protocol, timestamp FROM packets{where_clause}"
+        );
+        let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+        let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+        let rows = stmt
+            .query_map(rusqlite::params_from_iter(param_refs), |row| {
+                Ok(Packet {
+                    id: row.get::<_, i64>(0)? as u64,
+                    size: row.get::<_, i64>(1)? as u32,
+                    priority: row.get::<_, i64>(2)? as u32,
+                    source_ip: row.get(3)?,
+                    dest_ip: row.get(4)?,
+                    source_port: row.get::<_, i64>(5)? as u16,
+                    dest_port: row.get::<_, i64>(6)? as u16,
+                    protocol: row.get::<_, i64>(7)? as u8,
+                    timestamp: row.get::<_, i64>(8)? as u64,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    }
+
+    fn query_routes(&self, filter: &RouteFilter) -> Result<Vec<Route>, String> {
+        let mut conditions: Vec<(&'static str, Box<dyn rusqlite::ToSql>)> = Vec::new();
+        if let Some(destination) = &filter.destination {
+            conditions.push(("destination = ?", Box::new(destination.clone())));
+        }
+        if let Some(protocol) = &filter.protocol {
+            conditions.push(("protocol = ?", Box::new(protocol.clone())));
+        }
+        if let Some(interface) = &filter.interface {
+            conditions.push(("interface = ?", Box::new(interface.clone())));
+        }
+        if let Some((low, high)) = filter.metric_range {
+            conditions.push(("metric >= ?", Box::new(low as i64)));
+            conditions.push(("metric <= ?", Box::new(high as i64)));
+        }
+        if let Some(is_active) = filter.is_active {
+            conditions.push(("is_active = ?", Box::new(is_active as i64)));
+        }
+        let (where_clause, params) = Self::where_clause(conditions);
+
+        let conn = self.conn.lock().unwrap();
+        let sql =
+            format!("SELECT destination, gateway, interface, prefix_length, metric, protocol, is_active FROM routes{where_clause}");
+        let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+        let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+        let rows = stmt
+            .query_map(rusqlite::params_from_iter(param_refs), |row| {
+                Ok(Route {
+                    destination: row.get(0)?,
+                    gateway: row.get(1)?,
+                    interface: row.get(2)?,
+                    prefix_length: row.get::<_, i64>(3)? as u8,
+                    metric: row.get::<_, i64>(4)? as u32,
+                    protocol: row.get(5)?,
+                    is_active: row.get::<_, i64>(6)? != 0,
+                    expires_at: None,
+                    local_pref: None,
+                    as_path_len: None,
+                    med: None,
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    }
+
+    fn query_metrics(&self, filter: &MetricFilter) -> Result<Vec<MetricData>, String> {
+        let mut conditions: Vec<(&'static str, Box<dyn rusqlite::ToSql>)> = Vec::new();
+        if let Some(name) = &filter.name {
+            conditions.push(("name = ?", Box::new(name.clone())));
+        }
+        if let Some((start, end)) = filter.time_range {
+            conditions.push(("timestamp >= ?", Box::new(start as i64)));
+            conditions.push(("timestamp <= ?", Box::new(end as i64)));
+        }
+        if let Some((low, high)) = filter.value_range {
+            conditions.push(("value >= ?", Box::new(low)));
+            conditions.push(("value <= ?", Box::new(high)));
+        }
+        let (where_clause, params) = Self::where_clause(conditions);
+
+        let conn = self.conn.lock().unwrap();
+        let sql = format!("SELECT name, value, timestamp, tags FROM metric_samples{where_clause}");
+        let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+        let param_refs: Vec<&dyn rusqlite::ToSql> = params.iter().map(|p| p.as_ref()).collect();
+        let rows = stmt
+            .query_map(rusqlite::params_from_iter(param_refs), |row| {
+                let tags: String = row.get(3)?;
+                Ok(MetricData {
+                    name: row.get(0)?,
+                    value: row.get(1)?,
+                    timestamp: row.get::<_, i64>(2)? as u64,
+                    tags: decode_tags(&tags),
+                })
+            })
+            .map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_packet(source_ip: &str, timestamp: u64) -> Packet {
+        Packet {
+            id: 1,
+            size: 1500,
+            priority: 0,
+            source_ip: source_ip.to_string(),
+            dest_ip: "192.168.1.2".to_string(),
+            source_port: 80,
+            dest_port: 8080,
+            protocol: 6,
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn ring_buffer_drops_oldest_once_full() {
+        let backend = RingBufferBackend::new(2);
+        for id in 0..3u64 {
+            backend.record_packet(&Packet { id, ..sample_packet("10.0.0.1", id) }).unwrap();
+        }
+
+        let all = backend.query_packets(&PacketFilter {
+            source_ip: None,
+            dest_ip: None,
+            protocol: None,
+            port_range: None,
+            time_range: None,
+            size_range: None,
+        }).unwrap();
+
+        assert_eq!(all.len(), 2);
+        assert_eq!(all.iter().map(|p| p.id).collect::<Vec<_>>(), vec![1, 2]);
+    }
+
+    #[test]
+    fn ring_buffer_filters_by_source_ip_and_time_range() {
+        let backend = RingBufferBackend::new(10);
+        backend.record_packet(&sample_packet("10.0.0.1", 100)).unwrap();
+        backend.record_packet(&sample_packet("10.0.0.2", 200)).unwrap();
+
+        let filtered = backend
+            .query_packets(&PacketFilter {
+                source_ip: Some("10.0.0.1".to_string()),
+                dest_ip: None,
+                protocol: None,
+                port_range: None,
+                time_range: Some((0, 150)),
+                size_range: None,
+            })
+            .unwrap();
+
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].source_ip, "10.0.0.1");
+    }
+
+    #[test]
+    fn sqlite_backend_round_trips_packets_routes_and_tagged_metrics() {
+        let backend = SqliteBackend::open_in_memory().unwrap();
+        backend.record_packet(&sample_packet("10.0.0.1", 100)).unwrap();
+        backend
+            .record_route(&Route::new(
+                "10.0.0.0/24".to_string(),
+                "10.0.0.1".to_string(),
+                "eth0".to_string(),
+                24,
+                1,
+                "static".to_string(),
+            ))
+            .unwrap();
+        backend
+            .record_metric(&MetricData {
+                name: "cpu_usage".to_string(),
+                value: 42.5,
+                timestamp: 100,
+                tags: HashMap::from([("router".to_string(), "r1".to_string())]),
+            })
+            .unwrap();
+
+        let packets = backend
+            .query_packets(&PacketFilter {
+                source_ip: Some("10.0.0.1".to_string()),
+                dest_ip: None,
+                protocol: None,
+                port_range: None,
+                time_range: None,
+                size_range: None,
+            })
+            .unwrap();
+        assert_eq!(packets.len(), 1);
+
+        let routes = backend
+            .query_routes(&RouteFilter {
+                destination: None,
+                protocol: Some("static".to_string()),
+                interface: None,
+                metric_range: None,
+                is_active: None,
+            })
+            .unwrap();
+        assert_eq!(routes.len(), 1);
+
+        let metrics = backend
+            .query_metrics(&MetricFilter { name: Some("cpu_usage".to_string()), time_range: None, value_range: None })
+            .unwrap();
+        assert_eq!(metrics.len(), 1);
+        assert_eq!(metrics[0].tags.get("router"), Some(&"r1".to_string()));
+    }
+}